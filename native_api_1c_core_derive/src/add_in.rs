@@ -0,0 +1,433 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, Ident, LitStr, Type};
+
+use crate::common::{
+    find_name, get_name, into_return, registry, Names, NamesBuilder,
+};
+
+/// Property of the AddIn, declared with `#[add_in_prop(...)]`
+struct Prop {
+    ident: Ident,
+    ty: Type,
+    names: Names,
+    readable: bool,
+    writable: bool,
+}
+
+pub(crate) fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input,
+            "AddIn can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input,
+            "AddIn can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut extension_name = LitStr::new(&ident.to_string(), ident.span());
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("add_in")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                extension_name = meta.value()?.parse()?;
+                return Ok(());
+            }
+            Err(meta.error("unknown `add_in` argument"))
+        })?;
+    }
+
+    let mut connections = Vec::new();
+    let mut jobs = None;
+    let mut executor = None;
+    let mut props = Vec::new();
+    for field in &fields.named {
+        let Some(field_ident) = &field.ident else {
+            continue;
+        };
+        for attr in &field.attrs {
+            if attr.path().is_ident("add_in_con") {
+                connections.push(field_ident.clone());
+            }
+            if attr.path().is_ident("add_in_jobs") {
+                if jobs.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "only one field can be marked with `add_in_jobs`",
+                    ));
+                }
+                jobs = Some(field_ident.clone());
+            }
+            if attr.path().is_ident("add_in_executor") {
+                if executor.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "only one field can be marked with `add_in_executor`",
+                    ));
+                }
+                executor = Some(field_ident.clone());
+            }
+            if !attr.path().is_ident("add_in_prop") {
+                continue;
+            }
+            let mut names = NamesBuilder::default();
+            let mut readable = false;
+            let mut writable = false;
+            attr.parse_nested_meta(|meta| {
+                if names.parse(&meta)? {
+                    return Ok(());
+                }
+                if meta.path.is_ident("readable") {
+                    readable = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("writable") {
+                    writable = true;
+                    return Ok(());
+                }
+                Err(meta.error("unknown `add_in_prop` argument"))
+            })?;
+            props.push(Prop {
+                ident: field_ident.clone(),
+                ty: field.ty.clone(),
+                names: names.build(attr.span())?,
+                readable,
+                writable,
+            });
+        }
+    }
+
+    let methods = methods(jobs.as_ref());
+    let (start_jobs, stop_jobs) = match &jobs {
+        Some(jobs) => (
+            Some(quote!(self.#jobs.start(interface, #extension_name);)),
+            Some(quote!(self.#jobs.shutdown();)),
+        ),
+        None => (None, None),
+    };
+    let (start_executor, stop_executor) = match &executor {
+        Some(executor) => (
+            Some(quote!(self.#executor.start(interface, #extension_name);)),
+            Some(quote!(self.#executor.shutdown();)),
+        ),
+        None => (None, None),
+    };
+    let extension_name = extension_name
+        .value()
+        .encode_utf16()
+        .chain(Some(0))
+        .collect::<Vec<u16>>();
+    let names = props.iter().map(|p| p.names.clone()).collect::<Vec<_>>();
+    let n_props = props.len();
+    let registry = registry(&names);
+    let find_prop = find_name(quote!(name));
+    let get_prop_name = get_name(quote!(num), quote!(alias));
+
+    let core = quote!(::native_api_1c_core);
+    let mut get_prop_val = Vec::new();
+    let mut set_prop_val = Vec::new();
+    let mut readable = Vec::new();
+    let mut writable = Vec::new();
+    for (i, prop) in props.iter().enumerate() {
+        let field = &prop.ident;
+        let ty = &prop.ty;
+        let name = &prop.names.name;
+        if prop.readable {
+            let set = into_return(
+                quote!(val),
+                quote!(::std::clone::Clone::clone(&self.#field)),
+            );
+            get_prop_val.push(quote!(#i => { #set; Ok(()) }));
+            readable.push(i);
+        }
+        if prop.writable {
+            let from_param = quote! {
+                <#ty as #core::ffi::conversion::FromParam>
+            };
+            set_prop_val.push(quote! {
+                #i => match #from_param::from_param(val.into()) {
+                    Some(value) => {
+                        self.#field = value;
+                        Ok(())
+                    }
+                    None => Err(#core::interface::AddInError::new(
+                        ::std::format!(
+                            "{}: expected {}, found {}",
+                            #name,
+                            #from_param::EXPECTED,
+                            val.type_name(),
+                        ),
+                    )),
+                },
+            });
+            writable.push(i);
+        }
+    }
+
+    let readable = match readable.is_empty() {
+        true => quote!(false),
+        false => quote!(matches!(num, #(#readable)|*)),
+    };
+    let writable = match writable.is_empty() {
+        true => quote!(false),
+        false => quote!(matches!(num, #(#writable)|*)),
+    };
+
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    let executor = executor.map(|executor| {
+        quote! {
+            impl #impl_generics #core::interface::AddInExecutor
+                for #ident #ty_generics #where_clause
+            {
+                fn executor(&self) -> &#core::executor::Executor {
+                    &self.#executor
+                }
+            }
+        }
+    });
+    Ok(quote! {
+        #executor
+
+        const _: () = {
+            #registry
+
+            impl #impl_generics #core::interface::TryAddInWrapper
+                for #ident #ty_generics #where_clause
+            {
+                fn init(
+                    &mut self,
+                    interface: &'static #core::ffi::connection::Connection,
+                ) -> #core::interface::AddInResult {
+                    #(self.#connections = Some(interface);)*
+                    #start_jobs
+                    #start_executor
+                    <Self as #core::interface::AddInMethods>::on_init(
+                        self, interface,
+                    )
+                }
+
+                fn done(&mut self) {
+                    <Self as #core::interface::AddInMethods>::on_done(self);
+                    #stop_executor
+                    #stop_jobs
+                    #(self.#connections = None;)*
+                }
+
+                fn register_extension_as(&mut self) -> &[u16] {
+                    &[#(#extension_name),*]
+                }
+
+                fn get_n_props(&self) -> usize {
+                    #n_props
+                }
+
+                fn find_prop(&self, name: &[u16]) -> Option<usize> {
+                    #find_prop
+                }
+
+                fn get_prop_name(
+                    &self,
+                    num: usize,
+                    alias: usize,
+                ) -> Option<Vec<u16>> {
+                    #get_prop_name
+                }
+
+                fn get_prop_val(
+                    &self,
+                    num: usize,
+                    val: #core::ffi::provided_types::ReturnValue,
+                ) -> #core::interface::AddInResult {
+                    match num {
+                        #(#get_prop_val)*
+                        _ => Err(#core::interface::AddInError::new(
+                            ::std::format!("property #{} is not readable", num),
+                        )),
+                    }
+                }
+
+                fn set_prop_val(
+                    &mut self,
+                    num: usize,
+                    val: &#core::ffi::provided_types::ParamValue,
+                ) -> #core::interface::AddInResult {
+                    match num {
+                        #(#set_prop_val)*
+                        _ => Err(#core::interface::AddInError::new(
+                            ::std::format!("property #{} is not writable", num),
+                        )),
+                    }
+                }
+
+                fn is_prop_readable(&self, num: usize) -> bool {
+                    #readable
+                }
+
+                fn is_prop_writable(&self, num: usize) -> bool {
+                    #writable
+                }
+
+                #methods
+
+                fn set_locale(&mut self, _loc: &[u16]) {}
+
+                fn set_user_interface_language_code(&mut self, _lang: &[u16]) {}
+            }
+        };
+    })
+}
+
+/// Generates method-related part of `TryAddInWrapper`, delegated to
+/// `AddInMethods`. Methods of `JobManager` from `#[add_in_jobs]` field
+/// follow the methods of the AddIn
+fn methods(jobs: Option<&Ident>) -> TokenStream {
+    let core = quote!(::native_api_1c_core);
+    let methods = quote!(<Self as #core::interface::AddInMethods>);
+    let Some(jobs) = jobs else {
+        return quote! {
+            fn get_n_methods(&self) -> usize {
+                #methods::get_n_methods(self)
+            }
+
+            fn find_method(&self, name: &[u16]) -> Option<usize> {
+                #methods::find_method(self, name)
+            }
+
+            fn get_method_name(
+                &self,
+                num: usize,
+                alias: usize,
+            ) -> Option<Vec<u16>> {
+                #methods::get_method_name(self, num, alias)
+            }
+
+            fn get_n_params(&self, num: usize) -> usize {
+                #methods::get_n_params(self, num)
+            }
+
+            fn get_param_def_value(
+                &self,
+                method_num: usize,
+                param_num: usize,
+                value: #core::ffi::provided_types::ReturnValue,
+            ) -> bool {
+                #methods::get_param_def_value(
+                    self, method_num, param_num, value,
+                )
+            }
+
+            fn has_ret_val(&self, method_num: usize) -> bool {
+                #methods::has_ret_val(self, method_num)
+            }
+
+            fn call_as_proc(
+                &mut self,
+                method_num: usize,
+                params: &mut #core::ffi::provided_types::Params,
+            ) -> #core::interface::AddInResult {
+                #methods::call_as_proc(self, method_num, params)
+            }
+
+            fn call_as_func(
+                &mut self,
+                method_num: usize,
+                params: &mut #core::ffi::provided_types::Params,
+                val: #core::ffi::provided_types::ReturnValue,
+            ) -> #core::interface::AddInResult {
+                #methods::call_as_func(self, method_num, params, val)
+            }
+        };
+    };
+
+    let manager = quote!(#core::jobs::JobManager);
+    // Index of the job manager method is `Some` for methods, that follow
+    // the methods of the AddIn
+    let n_methods = quote!(#methods::get_n_methods(self));
+    quote! {
+        fn get_n_methods(&self) -> usize {
+            #methods::get_n_methods(self) + #manager::N_METHODS
+        }
+
+        fn find_method(&self, name: &[u16]) -> Option<usize> {
+            #methods::find_method(self, name).or_else(|| {
+                #manager::find_method(name).map(|num| num + #n_methods)
+            })
+        }
+
+        fn get_method_name(
+            &self,
+            num: usize,
+            alias: usize,
+        ) -> Option<Vec<u16>> {
+            match num.checked_sub(#n_methods) {
+                Some(num) => #manager::get_method_name(num, alias)
+                    .map(<[u16]>::to_vec),
+                None => #methods::get_method_name(self, num, alias),
+            }
+        }
+
+        fn get_n_params(&self, num: usize) -> usize {
+            match num.checked_sub(#n_methods) {
+                Some(num) => #manager::get_n_params(num),
+                None => #methods::get_n_params(self, num),
+            }
+        }
+
+        fn get_param_def_value(
+            &self,
+            method_num: usize,
+            param_num: usize,
+            value: #core::ffi::provided_types::ReturnValue,
+        ) -> bool {
+            match method_num.checked_sub(#n_methods) {
+                Some(_) => false,
+                None => #methods::get_param_def_value(
+                    self, method_num, param_num, value,
+                ),
+            }
+        }
+
+        fn has_ret_val(&self, method_num: usize) -> bool {
+            match method_num.checked_sub(#n_methods) {
+                Some(num) => num < #manager::N_METHODS,
+                None => #methods::has_ret_val(self, method_num),
+            }
+        }
+
+        fn call_as_proc(
+            &mut self,
+            method_num: usize,
+            params: &mut #core::ffi::provided_types::Params,
+        ) -> #core::interface::AddInResult {
+            match method_num.checked_sub(#n_methods) {
+                Some(num) => self.#jobs.call_method(num, params).map(|_| ()),
+                None => #methods::call_as_proc(self, method_num, params),
+            }
+        }
+
+        fn call_as_func(
+            &mut self,
+            method_num: usize,
+            params: &mut #core::ffi::provided_types::Params,
+            val: #core::ffi::provided_types::ReturnValue,
+        ) -> #core::interface::AddInResult {
+            match method_num.checked_sub(#n_methods) {
+                Some(num) => {
+                    let result = self.#jobs.call_method(num, params)?;
+                    #core::ffi::conversion::IntoReturn::into_return(
+                        result, val,
+                    );
+                    Ok(())
+                }
+                None => {
+                    #methods::call_as_func(self, method_num, params, val)
+                }
+            }
+        }
+    }
+}
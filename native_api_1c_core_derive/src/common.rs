@@ -0,0 +1,90 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{meta::ParseNestedMeta, LitStr};
+
+/// Bilingual name of a property or a method
+#[derive(Clone)]
+pub(crate) struct Names {
+    pub name: LitStr,
+    pub name_ru: LitStr,
+}
+
+/// Collects `name` and `name_ru` values from attribute arguments
+#[derive(Default)]
+pub(crate) struct NamesBuilder {
+    name: Option<LitStr>,
+    name_ru: Option<LitStr>,
+}
+
+impl NamesBuilder {
+    /// Tries to parse `name = "..."` or `name_ru = "..."` argument
+    /// # Returns
+    /// `syn::Result<bool>` - if the argument was recognized
+    pub fn parse(&mut self, meta: &ParseNestedMeta) -> syn::Result<bool> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+            return Ok(true);
+        }
+        if meta.path.is_ident("name_ru") {
+            self.name_ru = Some(meta.value()?.parse()?);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Builds names, Russian name defaults to English one
+    pub fn build(self, span: proc_macro2::Span) -> syn::Result<Names> {
+        let Some(name) = self.name else {
+            return Err(syn::Error::new(span, "`name` is required"));
+        };
+        let name_ru = self.name_ru.unwrap_or_else(|| name.clone());
+        Ok(Names { name, name_ru })
+    }
+}
+
+/// Generates statement, that sets `value` to `ReturnValue`
+pub(crate) fn into_return(
+    return_value: TokenStream,
+    value: TokenStream,
+) -> TokenStream {
+    quote! {
+        ::native_api_1c_core::ffi::conversion::IntoReturn::into_return(
+            #value,
+            #return_value,
+        )
+    }
+}
+
+/// Generates function `add_in_names`, that returns `&'static NameRegistry`
+/// with `names`, built on first use. Bodies from `find_name` and `get_name`
+/// call it, so both share one registry. The function should be emitted with
+/// the impl block into `const _: () = { ... };`, so it does not clash with
+/// other items
+pub(crate) fn registry(names: &[Names]) -> TokenStream {
+    let en = names.iter().map(|n| &n.name);
+    let ru = names.iter().map(|n| &n.name_ru);
+    let len = names.len();
+    let registry = quote!(::native_api_1c_core::names::NameRegistry);
+    quote! {
+        fn add_in_names() -> &'static #registry {
+            static NAMES: ::std::sync::OnceLock<#registry> =
+                ::std::sync::OnceLock::new();
+            NAMES.get_or_init(|| {
+                let names: [(&str, &str); #len] = [#((#en, #ru)),*];
+                names.into_iter().collect()
+            })
+        }
+    }
+}
+
+/// Generates body of `find_prop` or `find_method`, that searches for `name`
+/// among both aliases ignoring case, see `registry`
+pub(crate) fn find_name(name: TokenStream) -> TokenStream {
+    quote!(add_in_names().find(#name))
+}
+
+/// Generates body of `get_prop_name` or `get_method_name`, alias `0` is
+/// Russian and any other is English, see `registry`
+pub(crate) fn get_name(num: TokenStream, alias: TokenStream) -> TokenStream {
+    quote!(add_in_names().get_name(#num, #alias).map(<[u16]>::to_vec))
+}
@@ -0,0 +1,100 @@
+//! Procedural macros for native_api_1c_core crate, that implement
+//! `TryAddInWrapper` trait from an annotated struct and its methods.
+//!
+//! ```ignore
+//! use native_api_1c_core::{add_in_methods, ffi::connection::Connection, AddIn};
+//!
+//! #[derive(AddIn, Default)]
+//! #[add_in(name = "Sample")]
+//! struct Sample {
+//!     #[add_in_con]
+//!     connection: Option<&'static Connection>,
+//!     #[add_in_prop(name = "Counter", name_ru = "Счетчик", readable, writable)]
+//!     counter: i32,
+//! }
+//!
+//! #[add_in_methods]
+//! impl Sample {
+//!     #[add_in_method(name = "Add", name_ru = "Добавить")]
+//!     fn add(&mut self, #[add_in_arg(default = 1)] value: i32) -> i32 {
+//!         self.counter += value;
+//!         self.counter
+//!     }
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemImpl};
+
+mod add_in;
+mod common;
+mod methods;
+
+/// Implements `TryAddInWrapper` for a struct with named fields. Methods of the
+/// AddIn are taken from the `impl` block, marked with `#[add_in_methods]`,
+/// which is required even if the AddIn has no methods
+///
+/// Supported attributes:
+/// * `#[add_in(name = "...")]` on the struct - name of the AddIn, passed to
+///   `RegisterExtensionAs`, defaults to the struct name
+/// * `#[add_in_con]` on a field of type `Option<&'static Connection>` - field
+///   is set in `init` and cleared in `done`
+/// * `#[add_in_prop(name = "...", name_ru = "...", readable, writable)]` on a
+///   field - property of the AddIn, Russian name defaults to English one.
+///   Type of the field must implement `FromParam`, `IntoReturn` and `Clone`
+/// * `#[add_in_jobs]` on a field of type `JobManager` - workers are started
+///   in `init` and stopped in `done`, methods `JobStatus`, `JobResult` and
+///   `CancelJob` are added after the methods of the AddIn
+/// * `#[add_in_executor]` on a field of type `Executor` - executor of async
+///   methods, it is started in `init` and stopped in `done`
+#[proc_macro_derive(
+    AddIn,
+    attributes(add_in, add_in_con, add_in_executor, add_in_jobs, add_in_prop)
+)]
+pub fn derive_add_in(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    add_in::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Collects methods of the AddIn from an inherent `impl` block
+///
+/// Supported attributes:
+/// * `#[add_in_method(name = "...", name_ru = "...")]` on a method - method
+///   of the AddIn, it is a function if it returns a value and a procedure
+///   otherwise. Types of parameters must implement `FromParam` and return
+///   type must implement `IntoReturn`. Parameters of types `&[u16]`, `&[u8]`
+///   and `ParamRef` are borrowed from 1C platform without copying.
+///   Parameters of type `&mut ParamValue` are passed by reference, their
+///   values, changed by the method, are written back to 1C platform after
+///   the call. If the method returns `Result` or `AddInResult`, its error is
+///   reported to 1C platform
+/// * `async fn` methods without `self` and methods, returning
+///   `impl Future<Output = ...> + Send + 'static`, are run on the executor
+///   from `#[add_in_executor]` field. By default the method waits for the
+///   result for `executor::DEFAULT_TIMEOUT`, `timeout = <ms>` argument of
+///   `#[add_in_method(...)]` changes it. With `event = "..."` argument the
+///   method returns immediately and the result, converted with `ToString`,
+///   is sent to 1C platform as data of the external event with this name.
+///   Error is sent as the event with `Error` suffix.
+///
+///   **Async methods can not take `self`**, as their futures outlive the
+///   call: clone the needed state into the future, returned by a method with
+///   `impl Future` return type. **Executor has no I/O reactor or timers**:
+///   futures, that use tokio I/O or timers, panic, unless the executor is
+///   created with `Executor::with_tokio` (`tokio` feature of
+///   native_api_1c_core)
+/// * `#[add_in_arg(default = ...)]` on a method parameter - default value of
+///   the parameter
+/// * `#[add_in_init]` on a
+///   `fn(&mut self, &'static Connection) -> AddInResult` - called at the end
+///   of `init`
+/// * `#[add_in_done]` on a `fn(&mut self)` - called at the start of `done`
+#[proc_macro_attribute]
+pub fn add_in_methods(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+    methods::expand(item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
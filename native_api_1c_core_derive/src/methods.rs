@@ -0,0 +1,513 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    spanned::Spanned, AssocType, Expr, FnArg, GenericArgument, ImplItem,
+    ImplItemFn, ItemImpl, LitInt, LitStr, PathArguments, ReturnType, Type,
+    TypeParamBound,
+};
+
+use crate::common::{
+    find_name, get_name, into_return, registry, Names, NamesBuilder,
+};
+
+/// Method of the AddIn, declared with `#[add_in_method(...)]`
+struct Method {
+    ident: syn::Ident,
+    names: Names,
+    defaults: Vec<Option<Expr>>,
+    by_ref: Vec<bool>,
+    has_ret: bool,
+    returns_result: bool,
+    has_receiver: bool,
+    mode: Option<AsyncMode>,
+}
+
+/// How result of an async method is delivered to 1C platform
+enum AsyncMode {
+    /// Method waits for the result, but no longer than the timeout in ms
+    Block(Option<LitInt>),
+    /// Method returns immediately, result is sent as data of the external
+    /// event with the given name, if the method returns a value
+    Event(LitStr, bool),
+}
+
+pub(crate) fn expand(mut item: ItemImpl) -> syn::Result<TokenStream> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`add_in_methods` can only be used on inherent impl blocks",
+        ));
+    }
+
+    let mut methods = Vec::new();
+    let mut init = None;
+    let mut done = None;
+    for impl_item in &mut item.items {
+        let ImplItem::Fn(function) = impl_item else {
+            continue;
+        };
+        let mut names = None;
+        let mut timeout = None;
+        let mut event = None;
+        let mut errors = Vec::new();
+        function.attrs.retain(|attr| {
+            if attr.path().is_ident("add_in_init") {
+                init = Some(function.sig.ident.clone());
+                return false;
+            }
+            if attr.path().is_ident("add_in_done") {
+                done = Some(function.sig.ident.clone());
+                return false;
+            }
+            if !attr.path().is_ident("add_in_method") {
+                return true;
+            }
+            let mut builder = NamesBuilder::default();
+            let parsed = attr.parse_nested_meta(|meta| {
+                if builder.parse(&meta)? {
+                    return Ok(());
+                }
+                if meta.path.is_ident("timeout") {
+                    timeout = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("event") {
+                    event = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                Err(meta.error("unknown `add_in_method` argument"))
+            });
+            match parsed.and_then(|_| builder.build(attr.span())) {
+                Ok(built) => names = Some(built),
+                Err(err) => errors.push(err),
+            }
+            false
+        });
+        if let Some(err) = errors.into_iter().next() {
+            return Err(err);
+        }
+
+        let Some(names) = names else {
+            continue;
+        };
+        let defaults = parse_defaults(function)?;
+        let by_ref = by_ref_args(function)?;
+        let has_receiver = function.sig.receiver().is_some();
+        let output = match &function.sig.output {
+            ReturnType::Type(_, ty) => Some(ty.as_ref()),
+            ReturnType::Default => None,
+        };
+        let is_async = function.sig.asyncness.is_some();
+        if is_async && has_receiver {
+            return Err(syn::Error::new_spanned(
+                &function.sig,
+                "async methods can not borrow `self`, return \
+                 `impl Future<Output = ...> + Send + 'static` instead",
+            ));
+        }
+        let future_output = output.and_then(future_output);
+        let is_async = is_async || future_output.is_some();
+        let (has_ret, returns_result) = return_kind(future_output.or(output));
+        let mode = match (is_async, timeout, event) {
+            (false, None, None) => None,
+            (false, Some(timeout), _) => {
+                return Err(syn::Error::new_spanned(
+                    timeout,
+                    "`timeout` can only be used with async methods",
+                ));
+            }
+            (false, None, Some(event)) => {
+                return Err(syn::Error::new_spanned(
+                    event,
+                    "`event` can only be used with async methods",
+                ));
+            }
+            (true, Some(timeout), Some(_)) => {
+                return Err(syn::Error::new_spanned(
+                    timeout,
+                    "`timeout` can not be used with `event`",
+                ));
+            }
+            (true, timeout, None) => Some(AsyncMode::Block(timeout)),
+            (true, None, Some(event)) => Some(AsyncMode::Event(event, has_ret)),
+        };
+        if mode.is_some() && by_ref.contains(&true) {
+            return Err(syn::Error::new_spanned(
+                &function.sig,
+                "async methods can not take parameters by reference",
+            ));
+        }
+        methods.push(Method {
+            ident: function.sig.ident.clone(),
+            names,
+            defaults,
+            by_ref,
+            // Result of the method with `event` is delivered with the event
+            has_ret: has_ret && !matches!(mode, Some(AsyncMode::Event(..))),
+            returns_result,
+            has_receiver,
+            mode,
+        });
+    }
+
+    let names = methods.iter().map(|m| m.names.clone()).collect::<Vec<_>>();
+    let n_methods = methods.len();
+    let registry = registry(&names);
+    let find_method = find_name(quote!(name));
+    let get_method_name = get_name(quote!(num), quote!(alias));
+
+    let core = quote!(::native_api_1c_core);
+    let mut get_n_params = Vec::new();
+    let mut get_param_def_value = Vec::new();
+    let mut has_ret_val = Vec::new();
+    let mut call_as_proc = Vec::new();
+    let mut call_as_func = Vec::new();
+    for (i, method) in methods.iter().enumerate() {
+        let ident = &method.ident;
+        let name = &method.names.name;
+        let n_params = method.defaults.len();
+        get_n_params.push(quote!(#i => #n_params,));
+
+        let mut args = Vec::new();
+        let mut read_refs = Vec::new();
+        let mut write_refs = Vec::new();
+        for (j, default) in method.defaults.iter().enumerate() {
+            if let Some(default) = default {
+                let set = into_return(quote!(value), quote!(#default));
+                get_param_def_value.push(quote!((#i, #j) => { #set; true }));
+            }
+            let param = quote! {
+                #core::ffi::conversion::param(params, #j).map_err(|err| {
+                    #core::interface::AddInError::new(
+                        ::std::format!("{}: {}", #name, err),
+                    )
+                })?
+            };
+            if !method.by_ref[j] {
+                args.push(param);
+                continue;
+            }
+            // Value is copied, so the method can change it, and written
+            // back to 1C platform after the call if it was changed
+            let var = format_ident!("__param_{}", j);
+            read_refs.push(quote! {
+                let mut #var: #core::ffi::provided_types::ParamValue = #param;
+            });
+            args.push(quote!(&mut #var));
+            write_refs.push(quote! {
+                params.update(
+                    #j,
+                    #core::ffi::provided_types::ParamRef::from(&#var),
+                )?;
+            });
+        }
+        let check = quote! {
+            if params.len() != #n_params {
+                return Err(#core::interface::AddInError::new(
+                    ::std::format!(
+                        "{}: expected {} parameters, found {}",
+                        #name,
+                        #n_params,
+                        params.len(),
+                    ),
+                ));
+            }
+        };
+        let invoke = match method.has_receiver {
+            true => quote!(self.#ident(#(#args),*)),
+            false => quote!(Self::#ident(#(#args),*)),
+        };
+        let unwrap = method.returns_result.then(|| quote!(?));
+        let executor = quote! {
+            #core::interface::AddInExecutor::executor(self)
+        };
+        let call = match &method.mode {
+            None => quote!(#invoke #unwrap),
+            Some(AsyncMode::Block(timeout)) => {
+                let timeout = match timeout {
+                    Some(ms) => quote!(::std::time::Duration::from_millis(#ms)),
+                    None => quote!(#core::executor::DEFAULT_TIMEOUT),
+                };
+                quote! {{
+                    let future = #invoke;
+                    #executor.block_on(future, #timeout)? #unwrap
+                }}
+            }
+            Some(AsyncMode::Event(event, has_value)) => {
+                let (value, data) = match has_value {
+                    true => (
+                        quote!(value),
+                        quote!(::std::string::ToString::to_string(&value)),
+                    ),
+                    false => (quote!(_), quote!(::std::string::String::new())),
+                };
+                quote! {{
+                    let future = #invoke;
+                    #executor.notify(#event, async move {
+                        let #value = future.await #unwrap;
+                        Ok::<_, #core::interface::AddInError>(#data)
+                    })?;
+                }}
+            }
+        };
+
+        call_as_proc.push(quote! {
+            #i => { #check #(#read_refs)* #call; #(#write_refs)* Ok(()) }
+        });
+        match method.has_ret {
+            true => {
+                has_ret_val.push(i);
+                let set = into_return(quote!(val), quote!(result));
+                call_as_func.push(quote! {
+                    #i => {
+                        #check
+                        #(#read_refs)*
+                        let result = #call;
+                        #(#write_refs)*
+                        #set;
+                        Ok(())
+                    }
+                });
+            }
+            false => call_as_func.push(quote! {
+                #i => Err(#core::interface::AddInError::new(
+                    ::std::format!("{}: method does not return a value", #name),
+                )),
+            }),
+        }
+    }
+
+    let has_ret_val = match has_ret_val.is_empty() {
+        true => quote!(false),
+        false => quote!(matches!(method_num, #(#has_ret_val)|*)),
+    };
+    let on_init = match init {
+        Some(init) => quote!(self.#init(interface)),
+        None => quote!(Ok(())),
+    };
+    let on_done = done.map(|done| quote!(self.#done();));
+
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            #registry
+
+            #[allow(unused_variables)]
+            impl #impl_generics #core::interface::AddInMethods
+                for #self_ty #where_clause
+            {
+                fn on_init(
+                    &mut self,
+                    interface: &'static #core::ffi::connection::Connection,
+                ) -> #core::interface::AddInResult {
+                    #on_init
+                }
+
+                fn on_done(&mut self) {
+                    #on_done
+                }
+
+                fn get_n_methods(&self) -> usize {
+                    #n_methods
+                }
+
+                fn find_method(&self, name: &[u16]) -> Option<usize> {
+                    #find_method
+                }
+
+                fn get_method_name(
+                    &self,
+                    num: usize,
+                    alias: usize,
+                ) -> Option<Vec<u16>> {
+                    #get_method_name
+                }
+
+                fn get_n_params(&self, num: usize) -> usize {
+                    match num {
+                        #(#get_n_params)*
+                        _ => 0,
+                    }
+                }
+
+                fn get_param_def_value(
+                    &self,
+                    method_num: usize,
+                    param_num: usize,
+                    value: #core::ffi::provided_types::ReturnValue,
+                ) -> bool {
+                    match (method_num, param_num) {
+                        #(#get_param_def_value)*
+                        _ => false,
+                    }
+                }
+
+                fn has_ret_val(&self, method_num: usize) -> bool {
+                    #has_ret_val
+                }
+
+                fn call_as_proc(
+                    &mut self,
+                    method_num: usize,
+                    params: &mut #core::ffi::provided_types::Params,
+                ) -> #core::interface::AddInResult {
+                    match method_num {
+                        #(#call_as_proc)*
+                        _ => Err(#core::interface::AddInError::new(
+                            ::std::format!("method #{} not found", method_num),
+                        )),
+                    }
+                }
+
+                fn call_as_func(
+                    &mut self,
+                    method_num: usize,
+                    params: &mut #core::ffi::provided_types::Params,
+                    val: #core::ffi::provided_types::ReturnValue,
+                ) -> #core::interface::AddInResult {
+                    match method_num {
+                        #(#call_as_func)*
+                        _ => Err(#core::interface::AddInError::new(
+                            ::std::format!("method #{} not found", method_num),
+                        )),
+                    }
+                }
+            }
+        };
+    })
+}
+
+/// Collects default values of method parameters, removing
+/// `#[add_in_arg(...)]` attributes from the function signature
+fn parse_defaults(function: &mut ImplItemFn) -> syn::Result<Vec<Option<Expr>>> {
+    let mut defaults = Vec::new();
+    for arg in &mut function.sig.inputs {
+        let FnArg::Typed(arg) = arg else {
+            continue;
+        };
+        let mut default = None;
+        let mut result = Ok(());
+        arg.attrs.retain(|attr| {
+            if !attr.path().is_ident("add_in_arg") {
+                return true;
+            }
+            let parsed = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    default = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                Err(meta.error("unknown `add_in_arg` argument"))
+            });
+            if let Err(err) = parsed {
+                result = Err(err);
+            }
+            false
+        });
+        result?;
+        defaults.push(default);
+    }
+    Ok(defaults)
+}
+
+/// Finds method parameters, passed by reference. Such parameters must have
+/// type `&mut ParamValue`
+/// # Returns
+/// `Vec<bool>` - if each parameter, except the receiver, is passed by
+/// reference
+fn by_ref_args(function: &ImplItemFn) -> syn::Result<Vec<bool>> {
+    let mut by_ref = Vec::new();
+    for arg in &function.sig.inputs {
+        let FnArg::Typed(arg) = arg else {
+            continue;
+        };
+        let Type::Reference(reference) = arg.ty.as_ref() else {
+            by_ref.push(false);
+            continue;
+        };
+        if reference.mutability.is_none() {
+            by_ref.push(false);
+            continue;
+        }
+        let is_param_value = matches!(
+            reference.elem.as_ref(),
+            Type::Path(path) if path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "ParamValue")
+        );
+        if !is_param_value {
+            return Err(syn::Error::new_spanned(
+                &arg.ty,
+                "parameters, passed by reference, must have type \
+                 `&mut ParamValue`",
+            ));
+        }
+        by_ref.push(true);
+    }
+    Ok(by_ref)
+}
+
+/// Checks if the method returns a value and if the value is wrapped into
+/// `Result`, whose error is propagated to 1C platform
+/// # Arguments
+/// * `output` - return type of the method or output of its future, None for
+///   the default return type
+/// # Returns
+/// `(bool, bool)` - if the method returns a value and if it returns `Result`
+fn return_kind(output: Option<&Type>) -> (bool, bool) {
+    let is_unit =
+        |ty: &Type| matches!(ty, Type::Tuple(t) if t.elems.is_empty());
+    let Some(ty) = output else {
+        return (false, false);
+    };
+    let Type::Path(path) = ty else {
+        return (!is_unit(ty), false);
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return (true, false);
+    };
+    if segment.ident != "Result" && segment.ident != "AddInResult" {
+        return (true, false);
+    }
+    let ok = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.first(),
+        _ => None,
+    };
+    match ok {
+        Some(GenericArgument::Type(ok)) => (!is_unit(ok), true),
+        // `AddInResult` defaults to `AddInResult<()>`
+        _ => (false, true),
+    }
+}
+
+/// Extracts `T` from `impl Future<Output = T>` return type, such methods
+/// are run on the executor like async ones
+fn future_output(ty: &Type) -> Option<&Type> {
+    let Type::ImplTrait(ty) = ty else {
+        return None;
+    };
+    ty.bounds.iter().find_map(|bound| {
+        let TypeParamBound::Trait(bound) = bound else {
+            return None;
+        };
+        let segment = bound.path.segments.last()?;
+        if segment.ident != "Future" {
+            return None;
+        }
+        let PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        args.args.iter().find_map(|arg| match arg {
+            GenericArgument::AssocType(AssocType { ident, ty, .. })
+                if ident == "Output" =>
+            {
+                Some(ty)
+            }
+            _ => None,
+        })
+    })
+}
@@ -0,0 +1,314 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use native_api_1c_core::{
+    add_in_methods,
+    executor::Executor,
+    ffi::{connection::Connection, provided_types::ParamValue},
+    interface::{AddInError, AddInResult},
+    jobs::{JobId, JobManager},
+    testing::TestHost,
+    AddIn,
+};
+
+#[derive(AddIn)]
+#[add_in(name = "Sample")]
+struct Sample {
+    #[add_in_con]
+    connection: Option<&'static Connection>,
+    #[add_in_prop(name = "Counter", name_ru = "Счетчик", readable, writable)]
+    counter: i32,
+    #[add_in_prop(name = "Version", readable)]
+    version: String,
+    #[add_in_jobs]
+    jobs: JobManager,
+    #[add_in_executor]
+    executor: Executor,
+    initialized: Arc<AtomicBool>,
+    done: Arc<AtomicBool>,
+}
+
+impl Sample {
+    fn new(done: Arc<AtomicBool>) -> Self {
+        Self {
+            connection: None,
+            counter: 0,
+            version: "1.0".to_owned(),
+            jobs: JobManager::new(1),
+            executor: Executor::new(),
+            initialized: Arc::default(),
+            done,
+        }
+    }
+}
+
+#[add_in_methods]
+impl Sample {
+    #[add_in_method(name = "Add", name_ru = "Добавить")]
+    fn add(&mut self, #[add_in_arg(default = 1)] value: i32) -> i32 {
+        self.counter += value;
+        self.counter
+    }
+
+    #[add_in_method(name = "Divide", name_ru = "Разделить")]
+    fn divide(dividend: i32, divisor: i32) -> Result<i32, AddInError> {
+        dividend
+            .checked_div(divisor)
+            .ok_or_else(|| AddInError::new("division by zero"))
+    }
+
+    #[add_in_method(name = "Check", name_ru = "Проверить")]
+    fn check(&self, value: bool) -> AddInResult {
+        match value && self.connection.is_some() {
+            true => Ok(()),
+            false => Err(AddInError::new("check failed")),
+        }
+    }
+
+    #[add_in_method(name = "Start", name_ru = "Запустить")]
+    fn start(&self, value: i32) -> AddInResult<JobId> {
+        self.jobs.spawn(move |_| Ok(ParamValue::I32(value * 2)))
+    }
+
+    #[add_in_method(name = "Double", name_ru = "Удвоить", timeout = 5000)]
+    async fn double(value: i32) -> i32 {
+        value * 2
+    }
+
+    #[add_in_method(name = "Notify", name_ru = "Уведомить", event = "Notified")]
+    async fn notify(value: i32) -> AddInResult<i32> {
+        Ok(value + 1)
+    }
+
+    #[add_in_init]
+    fn on_init(&mut self, _: &'static Connection) -> AddInResult {
+        self.initialized.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    #[add_in_done]
+    fn on_done(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+
+    #[add_in_method(name = "Initialized")]
+    fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(AddIn, Default)]
+#[add_in(name = "Refs")]
+struct Refs {}
+
+#[add_in_methods]
+impl Refs {
+    #[add_in_method(name = "Swap", name_ru = "Обменять")]
+    fn swap(first: &mut ParamValue, second: &mut ParamValue) {
+        std::mem::swap(first, second);
+    }
+
+    #[add_in_method(name = "Increment", name_ru = "Увеличить")]
+    fn increment(value: &mut ParamValue, step: i32) -> bool {
+        match value {
+            ParamValue::I32(value) => {
+                *value += step;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn str(value: &str) -> ParamValue {
+    ParamValue::Str(value.encode_utf16().collect())
+}
+
+fn is_str(value: &Option<ParamValue>, expected: &str) -> bool {
+    matches!(
+        value,
+        Some(ParamValue::Str(s)) if String::from_utf16_lossy(s) == expected
+    )
+}
+
+fn descriptions(host: &TestHost) -> Vec<String> {
+    let errors = host.connection().errors();
+    errors.into_iter().map(|err| err.description).collect()
+}
+
+#[test]
+fn names_and_properties() {
+    let mut host = TestHost::new(Sample::new(Arc::default()));
+    assert!(host.init());
+    assert_eq!(host.register_extension_as().as_deref(), Some("Sample"));
+
+    assert_eq!(host.get_n_props(), 2);
+    assert_eq!(host.find_prop("счетчик"), Some(0));
+    assert_eq!(host.find_prop("COUNTER"), Some(0));
+    assert_eq!(host.find_prop("Version"), Some(1));
+    assert_eq!(host.find_prop("Unknown"), None);
+    assert_eq!(host.get_prop_name(0, 0).as_deref(), Some("Счетчик"));
+    assert_eq!(host.get_prop_name(0, 1).as_deref(), Some("Counter"));
+    // Russian name defaults to English one
+    assert_eq!(host.get_prop_name(1, 0).as_deref(), Some("Version"));
+
+    assert!(host.is_prop_readable(1));
+    assert!(!host.is_prop_writable(1));
+    assert!(is_str(&host.get_prop_val(1), "1.0"));
+    assert!(host.set_prop_val(0, &ParamValue::I32(5)));
+    assert!(matches!(host.get_prop_val(0), Some(ParamValue::I32(5))));
+    assert!(!host.set_prop_val(0, &str("five")));
+    assert!(!host.set_prop_val(1, &str("2.0")));
+    assert_eq!(
+        descriptions(&host),
+        [
+            "Counter: expected integer Number in range of i32, found String",
+            "property #1 is not writable",
+        ]
+    );
+
+    assert_eq!(host.find_method("добавить"), Some(0));
+    assert_eq!(host.get_method_name(0, 0).as_deref(), Some("Добавить"));
+    assert_eq!(host.get_method_name(0, 1).as_deref(), Some("Add"));
+    assert_eq!(host.get_method_name(6, 1).as_deref(), Some("Initialized"));
+    host.done();
+}
+
+#[test]
+fn methods_defaults_and_results() {
+    let done = Arc::new(AtomicBool::new(false));
+    let mut host = TestHost::new(Sample::new(done.clone()));
+    assert!(host.init());
+    assert!(matches!(
+        host.call_as_func(6, &mut []),
+        Some(ParamValue::Bool(true))
+    ));
+
+    assert_eq!(host.get_n_params(0), 1);
+    assert!(matches!(
+        host.get_param_def_value(0, 0),
+        Some(ParamValue::I32(1))
+    ));
+    assert!(host.get_param_def_value(1, 0).is_none());
+    let returns = (0..7).map(|num| host.has_ret_val(num)).collect::<Vec<_>>();
+    // `Check` returns `AddInResult<()>` and `Notify` delivers the result
+    // with the event
+    assert_eq!(returns, [true, true, false, true, true, false, true]);
+
+    let result = host.call_as_func(0, &mut [ParamValue::I32(2)]);
+    assert!(matches!(result, Some(ParamValue::I32(2))));
+    assert!(host.call_as_proc(0, &mut [ParamValue::I32(3)]));
+    assert!(matches!(host.get_prop_val(0), Some(ParamValue::I32(5))));
+
+    let mut params = [ParamValue::I32(7), ParamValue::I32(2)];
+    assert!(matches!(
+        host.call_as_func(1, &mut params),
+        Some(ParamValue::I32(3))
+    ));
+    let mut params = [ParamValue::I32(7), ParamValue::I32(0)];
+    assert!(host.call_as_func(1, &mut params).is_none());
+    assert!(host.call_as_proc(2, &mut [ParamValue::Bool(true)]));
+    assert!(!host.call_as_proc(2, &mut [ParamValue::Bool(false)]));
+    assert!(host
+        .call_as_func(2, &mut [ParamValue::Bool(true)])
+        .is_none());
+    assert!(!host.call_as_proc(0, &mut [str("two")]));
+    assert!(!host.call_as_proc(0, &mut []));
+    assert_eq!(
+        descriptions(&host),
+        [
+            "division by zero",
+            "check failed",
+            "Check: method does not return a value",
+            "Add: parameter #0: expected integer Number in range of i32, found String",
+            "Add: expected 1 parameters, found 0",
+        ]
+    );
+
+    host.done();
+    assert!(done.load(Ordering::SeqCst));
+}
+
+#[test]
+fn jobs_and_async_methods() {
+    let mut host = TestHost::new(Sample::new(Arc::default()));
+    assert!(host.init());
+    assert_eq!(host.get_n_methods(), 7 + JobManager::N_METHODS);
+    let status = host.find_method("СтатусЗадания").unwrap();
+    assert_eq!(status, 7);
+    assert_eq!(host.find_method("JobResult"), Some(8));
+    assert_eq!(host.get_method_name(9, 1).as_deref(), Some("CancelJob"));
+    assert_eq!(host.get_n_params(status), 1);
+
+    let id = match host.call_as_func(3, &mut [ParamValue::I32(21)]) {
+        Some(ParamValue::I32(id)) => id,
+        other => panic!("unexpected job id {other:?}"),
+    };
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !is_str(
+        &host.call_as_func(status, &mut [ParamValue::I32(id)]),
+        "Completed",
+    ) {
+        assert!(Instant::now() < deadline, "job is not completed");
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert!(matches!(
+        host.call_as_func(8, &mut [ParamValue::I32(id)]),
+        Some(ParamValue::I32(42))
+    ));
+
+    assert!(matches!(
+        host.call_as_func(4, &mut [ParamValue::I32(4)]),
+        Some(ParamValue::I32(8))
+    ));
+    assert!(host.call_as_proc(5, &mut [ParamValue::I32(1)]));
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while host.connection().events().len() < 2 {
+        assert!(Instant::now() < deadline, "events are not sent");
+        thread::sleep(Duration::from_millis(1));
+    }
+    let mut events = host
+        .connection()
+        .events()
+        .into_iter()
+        .map(|event| (event.source, event.name, event.data))
+        .collect::<Vec<_>>();
+    events.sort();
+    assert_eq!(
+        events,
+        [
+            ("Sample".into(), "JobFinished".into(), id.to_string()),
+            ("Sample".into(), "Notified".into(), "2".into()),
+        ]
+    );
+    host.done();
+}
+
+#[test]
+fn params_are_written_by_reference() {
+    let mut host = TestHost::new(Refs::default());
+    assert!(host.init());
+
+    let mut params = [ParamValue::I32(1), ParamValue::Str(vec![0x41])];
+    assert!(host.call_as_proc(0, &mut params));
+    assert!(matches!(
+        &params,
+        [ParamValue::Str(s), ParamValue::I32(1)] if s == &[0x41]
+    ));
+
+    let mut params = [ParamValue::I32(1), ParamValue::I32(2)];
+    let result = host.call_as_func(1, &mut params);
+    assert!(matches!(result, Some(ParamValue::Bool(true))));
+    assert!(matches!(params, [ParamValue::I32(3), ParamValue::I32(2)]));
+
+    host.done();
+    assert_eq!(host.memory().live_allocations(), 0);
+    assert_eq!(host.memory().invalid_frees(), 0);
+}
@@ -0,0 +1,11 @@
+use native_api_1c_core::add_in_methods;
+
+struct AsyncSelf;
+
+#[add_in_methods]
+impl AsyncSelf {
+    #[add_in_method(name = "Run")]
+    async fn run(&self) {}
+}
+
+fn main() {}
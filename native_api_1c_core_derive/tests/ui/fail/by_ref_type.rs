@@ -0,0 +1,13 @@
+use native_api_1c_core::add_in_methods;
+
+struct ByRefType;
+
+#[add_in_methods]
+impl ByRefType {
+    #[add_in_method(name = "Increment")]
+    fn increment(value: &mut i32) {
+        *value += 1;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use native_api_1c_core::{add_in_methods, jobs::JobManager, AddIn};
+
+#[derive(AddIn)]
+struct DuplicateJobs {
+    #[add_in_jobs]
+    first: JobManager,
+    #[add_in_jobs]
+    second: JobManager,
+}
+
+#[add_in_methods]
+impl DuplicateJobs {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use native_api_1c_core::AddIn;
+
+#[derive(AddIn)]
+enum Enum {
+    Variant,
+}
+
+fn main() {}
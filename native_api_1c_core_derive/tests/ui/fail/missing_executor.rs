@@ -0,0 +1,12 @@
+use native_api_1c_core::{add_in_methods, AddIn};
+
+#[derive(AddIn)]
+struct MissingExecutor {}
+
+#[add_in_methods]
+impl MissingExecutor {
+    #[add_in_method(name = "Run")]
+    async fn run() {}
+}
+
+fn main() {}
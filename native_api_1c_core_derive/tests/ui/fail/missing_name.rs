@@ -0,0 +1,12 @@
+use native_api_1c_core::{add_in_methods, AddIn};
+
+#[derive(AddIn)]
+struct MissingName {
+    #[add_in_prop(name_ru = "Значение", readable)]
+    value: i32,
+}
+
+#[add_in_methods]
+impl MissingName {}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use native_api_1c_core::add_in_methods;
+
+struct TimeoutOnSync;
+
+#[add_in_methods]
+impl TimeoutOnSync {
+    #[add_in_method(name = "Run", timeout = 100)]
+    fn run(&self) {}
+}
+
+fn main() {}
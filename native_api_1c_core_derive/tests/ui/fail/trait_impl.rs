@@ -0,0 +1,12 @@
+use native_api_1c_core::add_in_methods;
+
+struct TraitImpl;
+
+#[add_in_methods]
+impl Default for TraitImpl {
+    fn default() -> Self {
+        Self
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use native_api_1c_core::add_in_methods;
+
+struct UnknownArgument;
+
+#[add_in_methods]
+impl UnknownArgument {
+    #[add_in_method(name = "Run", hidden)]
+    fn run(&self) {}
+}
+
+fn main() {}
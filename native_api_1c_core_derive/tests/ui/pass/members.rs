@@ -0,0 +1,75 @@
+use native_api_1c_core::{
+    add_in_methods,
+    executor::Executor,
+    ffi::{
+        connection::Connection,
+        provided_types::{ParamRef, ParamValue},
+    },
+    interface::{AddInResult, TryAddInWrapper},
+    jobs::JobManager,
+    AddIn,
+};
+
+#[derive(AddIn, Default)]
+#[add_in(name = "Members")]
+struct Members {
+    #[add_in_con]
+    connection: Option<&'static Connection>,
+    #[add_in_prop(name = "Text", name_ru = "Текст", readable, writable)]
+    text: String,
+    #[add_in_prop(name = "Flag", writable)]
+    flag: bool,
+    #[add_in_jobs]
+    jobs: JobManager,
+    #[add_in_executor]
+    executor: Executor,
+}
+
+#[add_in_methods]
+impl Members {
+    #[add_in_method(name = "Length", name_ru = "Длина")]
+    fn length(
+        &self,
+        text: &[u16],
+        #[add_in_arg(default = 0)] start: i32,
+    ) -> i32 {
+        text.len() as i32 - start
+    }
+
+    #[add_in_method(name = "Kind")]
+    fn kind(value: ParamRef) -> &'static str {
+        value.type_name()
+    }
+
+    #[add_in_method(name = "Clear")]
+    fn clear(&mut self, value: &mut ParamValue) -> AddInResult {
+        *value = ParamValue::Empty;
+        self.text.clear();
+        Ok(())
+    }
+
+    #[add_in_method(name = "Wait", timeout = 100)]
+    async fn wait() {}
+
+    #[add_in_method(name = "Later", event = "Ready")]
+    fn later(
+        &self,
+    ) -> impl std::future::Future<Output = String> + Send + 'static {
+        let text = self.text.clone();
+        async move { text }
+    }
+
+    #[add_in_init]
+    fn init(&mut self, _: &'static Connection) -> AddInResult {
+        Ok(())
+    }
+
+    #[add_in_done]
+    fn done(&mut self) {}
+}
+
+fn assert_add_in<T: TryAddInWrapper>() {}
+
+fn main() {
+    assert_add_in::<Members>();
+}
@@ -0,0 +1,9 @@
+use native_api_1c_core::{add_in_methods, AddIn};
+
+#[derive(AddIn)]
+struct Minimal {}
+
+#[add_in_methods]
+impl Minimal {}
+
+fn main() {}
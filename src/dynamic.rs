@@ -0,0 +1,606 @@
+use crate::{
+    ffi::{
+        connection::Connection,
+        conversion::IntoReturn,
+        provided_types::{ParamValue, Params, ReturnValue},
+    },
+    interface::{AddInError, AddInResult, TryAddInWrapper},
+    names::NameRegistry,
+};
+
+type Getter = Box<dyn Fn() -> AddInResult<ParamValue>>;
+type Setter = Box<dyn FnMut(&ParamValue) -> AddInResult>;
+type Procedure = Box<dyn FnMut(&mut Params) -> AddInResult>;
+type Function = Box<dyn FnMut(&mut Params) -> AddInResult<ParamValue>>;
+type InitHook = Box<dyn FnMut(&'static Connection) -> AddInResult>;
+type DoneHook = Box<dyn FnMut()>;
+
+/// Property of `DynamicAddIn`, defined by getter and setter closures.
+/// Property is readable if it has a getter and writable if it has a setter
+pub struct DynamicProperty {
+    get: Option<Getter>,
+    set: Option<Setter>,
+}
+
+impl DynamicProperty {
+    /// Creates read-only property
+    /// # Arguments
+    /// * `get` - closure, that returns value of the property
+    pub fn read_only(
+        get: impl Fn() -> AddInResult<ParamValue> + 'static,
+    ) -> Self {
+        Self {
+            get: Some(Box::new(get)),
+            set: None,
+        }
+    }
+
+    /// Creates write-only property
+    /// # Arguments
+    /// * `set` - closure, that receives new value of the property
+    pub fn write_only(
+        set: impl FnMut(&ParamValue) -> AddInResult + 'static,
+    ) -> Self {
+        Self {
+            get: None,
+            set: Some(Box::new(set)),
+        }
+    }
+
+    /// Creates readable and writable property
+    /// # Arguments
+    /// * `get` - closure, that returns value of the property
+    /// * `set` - closure, that receives new value of the property
+    pub fn read_write(
+        get: impl Fn() -> AddInResult<ParamValue> + 'static,
+        set: impl FnMut(&ParamValue) -> AddInResult + 'static,
+    ) -> Self {
+        Self {
+            get: Some(Box::new(get)),
+            set: Some(Box::new(set)),
+        }
+    }
+}
+
+/// Body of `DynamicMethod`
+enum MethodBody {
+    Procedure(Procedure),
+    Function(Function),
+}
+
+/// Method of `DynamicAddIn`, defined by the number of parameters, their
+/// default values and a closure. The closure reads parameters from the
+/// buffers of 1C platform and writes back only the parameters, set with
+/// `Params::set`
+pub struct DynamicMethod {
+    defaults: Vec<Option<ParamValue>>,
+    body: MethodBody,
+}
+
+impl DynamicMethod {
+    /// Creates method, that does not return a value
+    /// # Arguments
+    /// * `n_params` - number of parameters
+    /// * `body` - closure, that receives parameters of the call
+    pub fn procedure(
+        n_params: usize,
+        body: impl FnMut(&mut Params) -> AddInResult + 'static,
+    ) -> Self {
+        Self {
+            defaults: vec![None; n_params],
+            body: MethodBody::Procedure(Box::new(body)),
+        }
+    }
+
+    /// Creates method, that returns a value
+    /// # Arguments
+    /// * `n_params` - number of parameters
+    /// * `body` - closure, that receives parameters of the call and returns
+    ///   the value
+    pub fn function(
+        n_params: usize,
+        body: impl FnMut(&mut Params) -> AddInResult<ParamValue> + 'static,
+    ) -> Self {
+        Self {
+            defaults: vec![None; n_params],
+            body: MethodBody::Function(Box::new(body)),
+        }
+    }
+
+    /// Sets default value of the parameter, that is used by 1C platform
+    /// when the parameter is omitted
+    /// # Arguments
+    /// * `index` - index of the parameter
+    /// * `value` - default value
+    /// # Returns
+    /// `AddInResult<Self>` - the method or error if `index` is not less
+    /// than the number of parameters
+    pub fn with_default(
+        mut self,
+        index: usize,
+        value: ParamValue,
+    ) -> AddInResult<Self> {
+        let n_params = self.defaults.len();
+        let Some(default) = self.defaults.get_mut(index) else {
+            return Err(AddInError::new(format!(
+                "parameter #{index} is out of range of {n_params} parameters"
+            )));
+        };
+        *default = Some(value);
+        Ok(self)
+    }
+}
+
+/// AddIn, whose properties and methods are registered at runtime, for
+/// components with member set, that depends on configuration or plugins.
+/// Members get indices in the order of registration and are found by
+/// English or Russian name ignoring case, so names of properties, as well
+/// as names of methods, must be unique.
+///
+/// Errors, returned by closures, are reported to 1C platform with
+/// `Connection::add_error` and `MessageCode::Fail`
+pub struct DynamicAddIn {
+    name: Vec<u16>,
+    connection: Option<&'static Connection>,
+    prop_names: NameRegistry,
+    props: Vec<DynamicProperty>,
+    method_names: NameRegistry,
+    methods: Vec<DynamicMethod>,
+    on_init: Option<InitHook>,
+    on_done: Option<DoneHook>,
+}
+
+impl DynamicAddIn {
+    /// Creates AddIn without members
+    /// # Arguments
+    /// * `name` - name of the AddIn, passed to `RegisterExtensionAs`
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.encode_utf16().collect(),
+            connection: None,
+            prop_names: NameRegistry::new(),
+            props: Vec::new(),
+            method_names: NameRegistry::new(),
+            methods: Vec::new(),
+            on_init: None,
+            on_done: None,
+        }
+    }
+
+    /// Registers property, see `add_property`
+    pub fn with_property(
+        mut self,
+        name: &str,
+        name_ru: &str,
+        property: DynamicProperty,
+    ) -> AddInResult<Self> {
+        self.add_property(name, name_ru, property)?;
+        Ok(self)
+    }
+
+    /// Registers method, see `add_method`
+    pub fn with_method(
+        mut self,
+        name: &str,
+        name_ru: &str,
+        method: DynamicMethod,
+    ) -> AddInResult<Self> {
+        self.add_method(name, name_ru, method)?;
+        Ok(self)
+    }
+
+    /// Sets closure, that is called at the end of `init` with the connection
+    /// to 1C platform
+    pub fn on_init(
+        mut self,
+        hook: impl FnMut(&'static Connection) -> AddInResult + 'static,
+    ) -> Self {
+        self.on_init = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets closure, that is called at the start of `done`
+    pub fn on_done(mut self, hook: impl FnMut() + 'static) -> Self {
+        self.on_done = Some(Box::new(hook));
+        self
+    }
+
+    /// Registers property
+    /// # Arguments
+    /// * `name` - English name of the property
+    /// * `name_ru` - Russian name of the property
+    /// * `property` - getter and setter of the property
+    /// # Returns
+    /// `AddInResult<usize>` - index of the property or error if a property
+    /// with one of the names is already registered
+    pub fn add_property(
+        &mut self,
+        name: &str,
+        name_ru: &str,
+        property: DynamicProperty,
+    ) -> AddInResult<usize> {
+        check_unique(&self.prop_names, "property", [name, name_ru])?;
+        self.props.push(property);
+        Ok(self.prop_names.add(name, name_ru))
+    }
+
+    /// Registers method
+    /// # Arguments
+    /// * `name` - English name of the method
+    /// * `name_ru` - Russian name of the method
+    /// * `method` - parameters and body of the method
+    /// # Returns
+    /// `AddInResult<usize>` - index of the method or error if a method
+    /// with one of the names is already registered
+    pub fn add_method(
+        &mut self,
+        name: &str,
+        name_ru: &str,
+        method: DynamicMethod,
+    ) -> AddInResult<usize> {
+        check_unique(&self.method_names, "method", [name, name_ru])?;
+        self.methods.push(method);
+        Ok(self.method_names.add(name, name_ru))
+    }
+
+    /// Returns connection to 1C platform, available between `init`
+    /// and `done`
+    pub fn connection(&self) -> Option<&'static Connection> {
+        self.connection
+    }
+
+    /// Returns English name of the method, used in error messages
+    fn method_name(&self, num: usize) -> String {
+        let name = self.method_names.get_name(num, 1).unwrap_or_default();
+        String::from_utf16_lossy(name)
+    }
+
+    /// Checks that the method exists and is called with expected number
+    /// of parameters
+    fn check_params(&self, num: usize, params: &Params) -> AddInResult {
+        let Some(method) = self.methods.get(num) else {
+            return Err(AddInError::new(format!("method #{num} not found")));
+        };
+        if params.len() != method.defaults.len() {
+            return Err(AddInError::new(format!(
+                "{}: expected {} parameters, found {}",
+                self.method_name(num),
+                method.defaults.len(),
+                params.len(),
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Checks that none of the names is registered, otherwise the new member
+/// would be unreachable with `find_prop` or `find_method`
+fn check_unique(
+    registry: &NameRegistry,
+    kind: &str,
+    names: [&str; 2],
+) -> AddInResult {
+    for name in names.into_iter().filter(|name| !name.is_empty()) {
+        let utf16 = name.encode_utf16().collect::<Vec<_>>();
+        if registry.find(&utf16).is_some() {
+            return Err(AddInError::new(format!(
+                "{kind} {name} is already registered"
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl TryAddInWrapper for DynamicAddIn {
+    fn init(&mut self, interface: &'static Connection) -> AddInResult {
+        self.connection = Some(interface);
+        match &mut self.on_init {
+            Some(hook) => hook(interface),
+            None => Ok(()),
+        }
+    }
+
+    fn done(&mut self) {
+        if let Some(hook) = &mut self.on_done {
+            hook();
+        }
+        self.connection = None;
+    }
+
+    fn register_extension_as(&mut self) -> &[u16] {
+        &self.name
+    }
+
+    fn get_n_props(&self) -> usize {
+        self.props.len()
+    }
+
+    fn find_prop(&self, name: &[u16]) -> Option<usize> {
+        self.prop_names.find(name)
+    }
+
+    fn get_prop_name(&self, num: usize, alias: usize) -> Option<Vec<u16>> {
+        self.prop_names.get_name(num, alias).map(<[u16]>::to_vec)
+    }
+
+    fn get_prop_val(&self, num: usize, val: ReturnValue) -> AddInResult {
+        let get = self.props.get(num).and_then(|prop| prop.get.as_ref());
+        let Some(get) = get else {
+            let err = format!("property #{num} is not readable");
+            return Err(AddInError::new(err));
+        };
+        get()?.into_return(val);
+        Ok(())
+    }
+
+    fn set_prop_val(&mut self, num: usize, val: &ParamValue) -> AddInResult {
+        let set = self.props.get_mut(num).and_then(|prop| prop.set.as_mut());
+        match set {
+            Some(set) => set(val),
+            None => {
+                Err(AddInError::new(format!("property #{num} is not writable")))
+            }
+        }
+    }
+
+    fn is_prop_readable(&self, num: usize) -> bool {
+        self.props.get(num).is_some_and(|prop| prop.get.is_some())
+    }
+
+    fn is_prop_writable(&self, num: usize) -> bool {
+        self.props.get(num).is_some_and(|prop| prop.set.is_some())
+    }
+
+    fn get_n_methods(&self) -> usize {
+        self.methods.len()
+    }
+
+    fn find_method(&self, name: &[u16]) -> Option<usize> {
+        self.method_names.find(name)
+    }
+
+    fn get_method_name(&self, num: usize, alias: usize) -> Option<Vec<u16>> {
+        self.method_names.get_name(num, alias).map(<[u16]>::to_vec)
+    }
+
+    fn get_n_params(&self, num: usize) -> usize {
+        self.methods
+            .get(num)
+            .map_or(0, |method| method.defaults.len())
+    }
+
+    fn get_param_def_value(
+        &self,
+        method_num: usize,
+        param_num: usize,
+        value: ReturnValue,
+    ) -> bool {
+        let default = self
+            .methods
+            .get(method_num)
+            .and_then(|method| method.defaults.get(param_num))
+            .and_then(Option::as_ref);
+        match default {
+            Some(default) => {
+                default.clone().into_return(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn has_ret_val(&self, method_num: usize) -> bool {
+        matches!(
+            self.methods.get(method_num),
+            Some(DynamicMethod {
+                body: MethodBody::Function(_),
+                ..
+            })
+        )
+    }
+
+    fn call_as_proc(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+    ) -> AddInResult {
+        self.check_params(method_num, params)?;
+        match &mut self.methods[method_num].body {
+            MethodBody::Procedure(body) => body(params),
+            MethodBody::Function(body) => body(params).map(|_| ()),
+        }
+    }
+
+    fn call_as_func(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+        val: ReturnValue,
+    ) -> AddInResult {
+        self.check_params(method_num, params)?;
+        let value = match &mut self.methods[method_num].body {
+            MethodBody::Function(body) => body(params)?,
+            MethodBody::Procedure(_) => {
+                return Err(AddInError::new(format!(
+                    "{}: method does not return a value",
+                    self.method_name(method_num),
+                )))
+            }
+        };
+        value.into_return(val);
+        Ok(())
+    }
+
+    fn set_locale(&mut self, _loc: &[u16]) {}
+
+    fn set_user_interface_language_code(&mut self, _lang: &[u16]) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{DynamicAddIn, DynamicMethod, DynamicProperty};
+    use crate::{
+        ffi::provided_types::{ParamRef, ParamValue},
+        interface::AddInError,
+        testing::TestHost,
+    };
+
+    #[test]
+    fn methods_write_params_and_report_errors() {
+        let swap = DynamicMethod::procedure(2, |params| {
+            let first = params.get(0).map(ParamValue::from);
+            let second = params.get(1).map(ParamValue::from);
+            if let (Some(first), Some(second)) = (first, second) {
+                params.set(0, ParamRef::from(&second))?;
+                params.set(1, ParamRef::from(&first))?;
+            }
+            Ok(())
+        });
+        let fail = DynamicMethod::function(0, |_| {
+            Err(AddInError::new("failed").with_source("Dynamic"))
+        });
+        let mut host = TestHost::new(
+            DynamicAddIn::new("Dynamic")
+                .with_method("Swap", "Обменять", swap)
+                .unwrap()
+                .with_method("Fail", "Ошибка", fail)
+                .unwrap(),
+        );
+        assert!(host.init());
+
+        let mut params = [ParamValue::I32(1), ParamValue::Bool(true)];
+        assert!(host.call_as_proc(0, &mut params));
+        assert!(matches!(
+            params,
+            [ParamValue::Bool(true), ParamValue::I32(1)]
+        ));
+
+        assert!(host.call_as_func(0, &mut params).is_none());
+        assert!(!host.call_as_proc(0, &mut []));
+        assert!(host.call_as_func(1, &mut []).is_none());
+
+        let errors = host.connection().errors();
+        let descriptions = errors
+            .iter()
+            .map(|err| err.description.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            descriptions,
+            [
+                "Swap: method does not return a value",
+                "Swap: expected 2 parameters, found 0",
+                "failed",
+            ]
+        );
+        assert_eq!(errors[2].source, "Dynamic");
+
+        host.done();
+        assert_eq!(host.memory().live_allocations(), 0);
+    }
+
+    fn str(s: &str) -> ParamValue {
+        ParamValue::Str(s.encode_utf16().collect())
+    }
+
+    #[test]
+    fn properties_defaults_and_names() {
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let read_only = DynamicProperty::read_only(|| Ok(str("1.0")));
+        let write_only = DynamicProperty::write_only({
+            let written = written.clone();
+            move |value| {
+                written.borrow_mut().push(value.clone());
+                Ok(())
+            }
+        });
+        let format = DynamicMethod::function(3, |_| Ok(ParamValue::Empty))
+            .with_default(1, ParamValue::I32(2))
+            .unwrap()
+            .with_default(2, str("utf-8"))
+            .unwrap();
+        let mut host = TestHost::new(
+            DynamicAddIn::new("Dynamic")
+                .with_property("Version", "Версия", read_only)
+                .unwrap()
+                .with_property("Output", "Вывод", write_only)
+                .unwrap()
+                .with_method("Format", "Форматировать", format)
+                .unwrap(),
+        );
+        assert!(host.init());
+
+        assert_eq!(host.find_prop("версия"), Some(0));
+        assert_eq!(host.find_prop("OUTPUT"), Some(1));
+        assert!(host.is_prop_readable(0) && !host.is_prop_writable(0));
+        assert!(!host.is_prop_readable(1) && host.is_prop_writable(1));
+        assert!(host.get_prop_val(0) == Some(str("1.0")));
+        assert!(!host.set_prop_val(0, &ParamValue::I32(1)));
+        assert!(host.get_prop_val(1).is_none());
+        assert!(host.set_prop_val(1, &ParamValue::I32(1)));
+        assert!(*written.borrow() == [ParamValue::I32(1)]);
+        assert!(host.get_prop_val(2).is_none());
+
+        let format = host.find_method("фОрМаТиРоВаТь").unwrap();
+        assert_eq!(format, 0);
+        assert_eq!(host.find_method("Форматировать1"), None);
+        assert_eq!(
+            host.get_method_name(format, 0).as_deref(),
+            Some("Форматировать")
+        );
+        assert_eq!(host.get_method_name(format, 1).as_deref(), Some("Format"));
+        assert_eq!(host.get_n_params(format), 3);
+        assert!(host.get_param_def_value(format, 0).is_none());
+        assert!(
+            host.get_param_def_value(format, 1) == Some(ParamValue::I32(2))
+        );
+        assert!(host.get_param_def_value(format, 2) == Some(str("utf-8")));
+        assert!(host.get_param_def_value(format, 3).is_none());
+
+        let descriptions = host
+            .connection()
+            .errors()
+            .into_iter()
+            .map(|err| err.description)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            descriptions,
+            [
+                "property #0 is not writable",
+                "property #1 is not readable",
+                "property #2 is not readable",
+            ]
+        );
+        host.done();
+        assert_eq!(host.memory().live_allocations(), 0);
+    }
+
+    #[test]
+    fn invalid_members_are_rejected() {
+        let method = || DynamicMethod::procedure(1, |_| Ok(()));
+        let err = method().with_default(1, ParamValue::Null).err().unwrap();
+        assert_eq!(
+            err.description,
+            "parameter #1 is out of range of 1 parameters"
+        );
+
+        let mut addin = DynamicAddIn::new("Dynamic");
+        assert_eq!(addin.add_method("Run", "Выполнить", method()), Ok(0));
+        assert_eq!(addin.add_method("Stop", "", method()), Ok(1));
+        assert_eq!(addin.add_method("Pause", "", method()), Ok(2));
+        let err = addin.add_method("RUN", "Запустить", method()).unwrap_err();
+        assert_eq!(err.description, "method RUN is already registered");
+        let err = addin
+            .add_method("Start", "выполнить", method())
+            .unwrap_err();
+        assert_eq!(err.description, "method выполнить is already registered");
+        // Names of properties and methods do not clash
+        let property = DynamicProperty::read_only(|| Ok(ParamValue::Null));
+        assert_eq!(addin.add_property("Run", "Выполнить", property), Ok(0));
+
+        let mut host = TestHost::new(addin);
+        assert_eq!(host.get_n_methods(), 3);
+        assert_eq!(host.find_method("run"), Some(0));
+    }
+}
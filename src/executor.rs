@@ -0,0 +1,520 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ffi::{connection::Connection, event_sender::EventSender, panic_guard},
+    interface::{AddInError, AddInResult},
+};
+
+/// Timeout of async methods, that deliver result synchronously, if it is
+/// not set with `timeout` argument of `#[add_in_method(...)]`
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Spawned future, that is polled by the executor thread
+/// # Fields
+/// * `id` - key of the task in `Shared::tasks`
+/// * `future` - future, None when it is finished or cancelled
+/// * `shared` - executor state, used to schedule the task when it is woken
+struct Task {
+    id: u64,
+    future: Mutex<Option<BoxFuture>>,
+    shared: Arc<Shared>,
+}
+
+impl Task {
+    /// Drops the future and forgets the task. Waits for the poll in
+    /// progress, so the future is not used afterwards
+    fn cancel(&self) {
+        let future = lock(&self.future).take();
+        lock(&self.shared.state).tasks.remove(&self.id);
+        drop(future);
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        let shared = self.shared.clone();
+        let mut state = lock(&shared.state);
+        if !state.stopped {
+            state.ready.push_back(self);
+            shared.ready.notify_one();
+        }
+    }
+}
+
+/// State of the executor, shared with its thread and tasks
+/// # Fields
+/// * `ready` - tasks, that should be polled
+/// * `tasks` - all unfinished tasks, so they can be dropped on shutdown
+/// * `next_id` - id of the next spawned task
+/// * `stopped` - if the executor is shut down
+#[derive(Default)]
+struct State {
+    ready: VecDeque<Arc<Task>>,
+    tasks: HashMap<u64, Arc<Task>>,
+    next_id: u64,
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+impl Shared {
+    /// Loop of the executor thread, polls woken tasks until shutdown
+    fn run(self: Arc<Self>) {
+        loop {
+            let task = {
+                let mut state = lock(&self.state);
+                loop {
+                    if state.stopped {
+                        return;
+                    }
+                    if let Some(task) = state.ready.pop_front() {
+                        break task;
+                    }
+                    state = self
+                        .ready
+                        .wait(state)
+                        .unwrap_or_else(|err| err.into_inner());
+                }
+            };
+            let waker = Waker::from(task.clone());
+            let mut future = lock(&task.future);
+            let Some(pinned) = future.as_mut() else {
+                continue;
+            };
+            let mut cx = Context::from_waker(&waker);
+            let finished =
+                match panic_guard::catch(|| pinned.as_mut().poll(&mut cx)) {
+                    Ok(Poll::Pending) => false,
+                    Ok(Poll::Ready(())) | Err(_) => true,
+                };
+            if finished {
+                let future = future.take();
+                drop(future);
+                lock(&self.state).tasks.remove(&task.id);
+            }
+        }
+    }
+}
+
+/// Value of a task, that is waited by `Executor::block_on`
+enum Slot<T> {
+    Pending,
+    Ready(T),
+    Dropped,
+}
+
+/// Receiver side of the value of a task
+struct Completion<T> {
+    slot: Mutex<Slot<T>>,
+    done: Condvar,
+}
+
+/// Sender side of the value of a task. Marks the value as dropped, if
+/// the future is dropped before it completes
+struct Completer<T>(Arc<Completion<T>>);
+
+impl<T> Completer<T> {
+    fn complete(self, value: T) {
+        *lock(&self.0.slot) = Slot::Ready(value);
+        self.0.done.notify_all();
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        let mut slot = lock(&self.0.slot);
+        if matches!(*slot, Slot::Pending) {
+            *slot = Slot::Dropped;
+            self.0.done.notify_all();
+        }
+    }
+}
+
+/// Running executor thread and the connection, used to send events
+struct Runtime {
+    shared: Arc<Shared>,
+    thread: JoinHandle<()>,
+    events: EventSender,
+    source: String,
+}
+
+/// Executor of async methods, owned by the AddIn. Futures are polled on
+/// a dedicated thread, started in `init` and stopped in `done` of the AddIn.
+/// `#[derive(AddIn)]` does it for a field marked with `#[add_in_executor]`.
+///
+/// Executor does not provide I/O reactor or timers, so futures, that use
+/// I/O or timers of tokio, panic when polled by it. With `tokio` feature,
+/// pass the handle of a tokio runtime to `with_tokio`, then futures are
+/// polled inside of the runtime context, and the runtime drives their I/O
+/// and timers. Otherwise spawn such futures on your runtime and await their
+/// `JoinHandle`
+#[derive(Default)]
+pub struct Executor {
+    runtime: Option<Runtime>,
+    #[cfg(feature = "tokio")]
+    tokio: Option<tokio::runtime::Handle>,
+}
+
+impl Executor {
+    /// Creates stopped executor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Polls futures inside of the context of the tokio runtime, so they can
+    /// use its I/O and timers. The runtime must be multi-threaded, or be
+    /// driven by another thread, as the executor does not drive it
+    /// # Arguments
+    /// * `handle` - handle of the runtime
+    #[cfg(feature = "tokio")]
+    pub fn with_tokio(mut self, handle: tokio::runtime::Handle) -> Self {
+        self.tokio = Some(handle);
+        self
+    }
+
+    /// Starts executor thread, does nothing if it is already started
+    /// # Arguments
+    /// * `connection` - connection to 1C platform, used to send events
+    /// * `source` - source of the events, usually the name of the AddIn
+    pub fn start(&mut self, connection: &'static Connection, source: &str) {
+        if self.runtime.is_some() {
+            return;
+        }
+        let shared = Arc::new(Shared::default());
+        #[cfg(feature = "tokio")]
+        let tokio = self.tokio.clone();
+        let thread = thread::spawn({
+            let shared = shared.clone();
+            move || {
+                #[cfg(feature = "tokio")]
+                let _context = tokio.as_ref().map(|handle| handle.enter());
+                shared.run()
+            }
+        });
+        self.runtime = Some(Runtime {
+            shared,
+            thread,
+            events: connection.event_sender(),
+            source: source.to_owned(),
+        });
+    }
+
+    /// Stops executor thread and drops unfinished futures
+    pub fn shutdown(&mut self) {
+        let Some(runtime) = self.runtime.take() else {
+            return;
+        };
+        let tasks = {
+            let mut state = lock(&runtime.shared.state);
+            state.stopped = true;
+            state.ready.clear();
+            std::mem::take(&mut state.tasks)
+        };
+        runtime.shared.ready.notify_all();
+        let _ = runtime.thread.join();
+        for task in tasks.into_values() {
+            task.cancel();
+        }
+    }
+
+    /// Checks if executor thread is running
+    pub fn is_running(&self) -> bool {
+        self.runtime.is_some()
+    }
+
+    fn runtime(&self) -> AddInResult<&Runtime> {
+        self.runtime
+            .as_ref()
+            .ok_or_else(|| AddInError::new("executor is not started"))
+    }
+
+    /// Spawns the future and schedules its first poll
+    fn spawn(
+        &self,
+        future: impl Future<Output = ()> + Send + 'static,
+    ) -> AddInResult<Arc<Task>> {
+        let shared = &self.runtime()?.shared;
+        let mut state = lock(&shared.state);
+        let task = Arc::new(Task {
+            id: state.next_id,
+            future: Mutex::new(Some(Box::pin(future))),
+            shared: shared.clone(),
+        });
+        state.next_id += 1;
+        state.tasks.insert(task.id, task.clone());
+        state.ready.push_back(task.clone());
+        shared.ready.notify_one();
+        Ok(task)
+    }
+
+    /// Runs the future on the executor and waits for its output
+    /// # Arguments
+    /// * `future` - future to run
+    /// * `timeout` - maximal time to wait, the future is dropped after it
+    /// # Returns
+    /// `AddInResult<F::Output>` - output of the future, or error if it timed
+    /// out, panicked or executor is not started
+    pub fn block_on<F>(
+        &self,
+        future: F,
+        timeout: Duration,
+    ) -> AddInResult<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let completion = Arc::new(Completion {
+            slot: Mutex::new(Slot::Pending),
+            done: Condvar::new(),
+        });
+        let completer = Completer(completion.clone());
+        let task = self.spawn(async move {
+            let output = future.await;
+            completer.complete(output);
+        })?;
+
+        let deadline = Instant::now() + timeout;
+        let mut slot = lock(&completion.slot);
+        loop {
+            match std::mem::replace(&mut *slot, Slot::Pending) {
+                Slot::Ready(output) => return Ok(output),
+                Slot::Dropped => {
+                    return Err(AddInError::new("async method was aborted"))
+                }
+                Slot::Pending => {}
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                drop(slot);
+                task.cancel();
+                return Err(AddInError::new(format!(
+                    "async method timed out after {} ms",
+                    timeout.as_millis(),
+                )));
+            }
+            slot = completion
+                .done
+                .wait_timeout(slot, deadline - now)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+    }
+
+    /// Runs the future on the executor without waiting. Its output is sent
+    /// to 1C platform as external event `event`, and its error as external
+    /// event `event` with `Error` suffix, with the description as data
+    /// # Arguments
+    /// * `event` - name of the event
+    /// * `future` - future, that returns data of the event
+    /// # Returns
+    /// `AddInResult` - error if executor is not started
+    pub fn notify<F>(&self, event: &str, future: F) -> AddInResult
+    where
+        F: Future<Output = AddInResult<String>> + Send + 'static,
+    {
+        let runtime = self.runtime()?;
+        let events = runtime.events.clone();
+        let source = runtime.source.clone();
+        let event = event.to_owned();
+        self.spawn(async move {
+            let _ = match future.await {
+                Ok(data) => events.send(&source, &event, &data),
+                Err(err) => events.send(
+                    &source,
+                    &format!("{event}Error"),
+                    &err.to_string(),
+                ),
+            };
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        future,
+        rc::Rc,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::Executor;
+    use crate::{
+        dynamic::DynamicAddIn,
+        interface::AddInError,
+        testing::{ExternalEvent, TestHost},
+    };
+
+    /// Starts the executor with `init` of a test AddIn and stops it with
+    /// `done`
+    fn host(executor: &Rc<RefCell<Executor>>) -> TestHost {
+        let addin = DynamicAddIn::new("Async")
+            .on_init({
+                let executor = executor.clone();
+                move |connection| {
+                    executor.borrow_mut().start(connection, "Async");
+                    Ok(())
+                }
+            })
+            .on_done({
+                let executor = executor.clone();
+                move || executor.borrow_mut().shutdown()
+            });
+        let mut host = TestHost::new(addin);
+        assert!(host.init());
+        host
+    }
+
+    /// Sets the flag, when the future, that owns it, is dropped
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Future, that never completes and sets the flag when dropped
+    async fn forever(flag: DropFlag) {
+        let _flag = flag;
+        future::pending::<()>().await
+    }
+
+    fn wait_for_events(host: &TestHost, n: usize) -> Vec<ExternalEvent> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let events = host.connection().events();
+            if events.len() >= n {
+                return events;
+            }
+            assert!(Instant::now() < deadline, "events: {events:?}");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn block_on_returns_output_or_times_out() {
+        let executor = Rc::new(RefCell::new(Executor::new()));
+        let timeout = Duration::from_secs(5);
+        let err = executor
+            .borrow()
+            .block_on(async { 1 }, timeout)
+            .unwrap_err();
+        assert_eq!(err.description, "executor is not started");
+
+        let mut host = host(&executor);
+        {
+            let executor = executor.borrow();
+            assert_eq!(executor.block_on(async { 1 }, timeout).unwrap(), 1);
+
+            let dropped = Arc::new(AtomicBool::new(false));
+            let future = forever(DropFlag(dropped.clone()));
+            let err = executor
+                .block_on(future, Duration::from_millis(20))
+                .unwrap_err();
+            assert_eq!(err.description, "async method timed out after 20 ms");
+            assert!(dropped.load(Ordering::SeqCst));
+
+            let future = async { panic!("async panic") };
+            let err = executor.block_on(future, timeout).unwrap_err();
+            assert_eq!(err.description, "async method was aborted");
+            // Executor survives the panic
+            assert_eq!(executor.block_on(async { 2 }, timeout).unwrap(), 2);
+        }
+        host.done();
+        assert!(!executor.borrow().is_running());
+    }
+
+    #[test]
+    fn notify_sends_events_and_shutdown_drops_futures() {
+        let executor = Rc::new(RefCell::new(Executor::new()));
+        let mut host = host(&executor);
+        let dropped = Arc::new(AtomicBool::new(false));
+        {
+            let executor = executor.borrow();
+            executor
+                .notify("Done", async { Ok("data".to_owned()) })
+                .unwrap();
+            executor
+                .notify("Done", async { Err(AddInError::new("failed")) })
+                .unwrap();
+            let flag = DropFlag(dropped.clone());
+            executor
+                .notify("Never", async move {
+                    forever(flag).await;
+                    Ok(String::new())
+                })
+                .unwrap();
+        }
+
+        let mut events = wait_for_events(&host, 2)
+            .into_iter()
+            .map(|event| (event.source, event.name, event.data))
+            .collect::<Vec<_>>();
+        events.sort();
+        assert_eq!(
+            events,
+            [
+                ("Async".into(), "Done".into(), "data".into()),
+                ("Async".into(), "DoneError".into(), "failed".into()),
+            ]
+        );
+        assert!(!dropped.load(Ordering::SeqCst));
+        host.done();
+        assert!(dropped.load(Ordering::SeqCst));
+        assert_eq!(host.connection().events().len(), 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn futures_use_tokio_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_time()
+            .build()
+            .unwrap();
+        let executor = Executor::new().with_tokio(runtime.handle().clone());
+        let executor = Rc::new(RefCell::new(executor));
+        let mut host = host(&executor);
+        let future = async {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            3
+        };
+        let output = executor
+            .borrow()
+            .block_on(future, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(output, 3);
+        host.done();
+    }
+}
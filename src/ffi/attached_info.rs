@@ -0,0 +1,37 @@
+/// Scheme, by which the AddIn was actually attached to 1C platform
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachedType {
+    /// AddIn runs in a separate process
+    Isolated = 0,
+    /// AddIn runs in 1C platform process
+    NotIsolated,
+}
+
+/// VTable for AttachedInfo object, derived from Native API interface. See original
+/// C++ implementation in [example project](https://its.1c.ru/db/files/1CITS/EXE/VNCOMPS/VNCOMPS.zip)
+/// from 1C documentation
+#[repr(C)]
+struct AttachedInfoVTable {
+    get_attached_info:
+        unsafe extern "system" fn(&AttachedInfo) -> std::ffi::c_int,
+}
+
+/// AttachedInfo object, used to get the scheme, by which the AddIn was
+/// attached to 1C platform. Can be obtained with `Connection::attached_info`
+#[repr(C)]
+pub struct AttachedInfo {
+    vptr: &'static AttachedInfoVTable,
+}
+
+impl AttachedInfo {
+    /// Equivalent to `GetAttachedInfo` from Native API interface
+    /// # Returns
+    /// `AttachedType` - scheme, by which the AddIn was attached
+    pub fn get_attached_info(&self) -> AttachedType {
+        match unsafe { (self.vptr.get_attached_info)(self) } {
+            0 => AttachedType::Isolated,
+            _ => AttachedType::NotIsolated,
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    cell::RefCell,
+    ffi::c_long,
+    fmt,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{
+    panic_guard,
+    provided_types::{ParamRef, TVariant},
+    string_utils::WStr,
+};
+
+/// Environment variable, that enables tracing of calls from 1C platform,
+/// when it is set to a non-empty value other than `0`. It is read once,
+/// on the first call, unless tracing is switched with `set_enabled`
+pub const TRACE_ENV: &str = "NATIVE_API_1C_TRACE";
+
+/// Maximal length of a traced parameter value, longer values are truncated
+const MAX_VALUE_LEN: usize = 64;
+
+const UNRESOLVED: u8 = 0;
+const DISABLED: u8 = 1;
+const ENABLED: u8 = 2;
+
+static STATE: AtomicU8 = AtomicU8::new(UNRESOLVED);
+
+type CallTracer = Box<dyn Fn(&CallSpan) + Send + Sync>;
+
+static CALL_TRACER: RwLock<Option<CallTracer>> = RwLock::new(None);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Call from 1C platform to `ILanguageExtenderBase` method of the AddIn
+/// # Fields
+/// * `entry` - name of Native API method, like `CallAsFunc`
+/// * `member` - index of the property or the method, for `FindProp` and
+///   `FindMethod` it is the found index
+/// * `name` - English name of the member or the name, that was searched for
+/// * `params` - types and values of the parameters, passed by 1C platform
+/// * `return_type` - type of the value, returned to 1C platform
+/// * `result` - value, returned by Native API method
+/// * `success` - if the call succeeded
+/// * `error` - error or panic, reported to 1C platform during the call
+/// * `duration` - duration of the call
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSpan {
+    pub entry: &'static str,
+    pub member: Option<usize>,
+    pub name: Option<String>,
+    pub params: Vec<(&'static str, String)>,
+    pub return_type: Option<&'static str>,
+    pub result: String,
+    pub success: bool,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+impl fmt::Display for CallSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.entry)?;
+        if let Some(member) = self.member {
+            write!(f, " #{member}")?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, " {name}")?;
+        }
+        if !self.params.is_empty() {
+            let params = self
+                .params
+                .iter()
+                .map(|(ty, value)| format!("{ty} {value}"))
+                .collect::<Vec<_>>();
+            write!(f, "({})", params.join(", "))?;
+        }
+        write!(f, " = {}", self.result)?;
+        if let Some(return_type) = self.return_type {
+            write!(f, ", returned {return_type}")?;
+        }
+        write!(f, ", {:?}", self.duration)?;
+        match (&self.error, self.success) {
+            (Some(error), _) => write!(f, ", failed: {error}"),
+            (None, true) => Ok(()),
+            (None, false) => write!(f, ", failed"),
+        }
+    }
+}
+
+/// Checks if calls from 1C platform are traced
+#[inline]
+pub fn is_enabled() -> bool {
+    match STATE.load(Ordering::Relaxed) {
+        ENABLED => true,
+        DISABLED => false,
+        _ => resolve(),
+    }
+}
+
+#[cold]
+fn resolve() -> bool {
+    let enabled = std::env::var_os(TRACE_ENV)
+        .is_some_and(|value| !value.is_empty() && value != "0");
+    let state = match enabled {
+        true => ENABLED,
+        false => DISABLED,
+    };
+    let _ = STATE.compare_exchange(
+        UNRESOLVED,
+        state,
+        Ordering::Relaxed,
+        Ordering::Relaxed,
+    );
+    STATE.load(Ordering::Relaxed) == ENABLED
+}
+
+/// Enables or disables tracing of calls from 1C platform, overriding
+/// `TRACE_ENV` variable
+pub fn set_enabled(enabled: bool) {
+    let state = match enabled {
+        true => ENABLED,
+        false => DISABLED,
+    };
+    STATE.store(state, Ordering::Relaxed);
+}
+
+/// Sets the function, that receives spans of traced calls. Without it
+/// spans are written to `log` with `Debug` level, if `log` feature is
+/// enabled, or discarded otherwise. Panics of the function are caught
+/// # Arguments
+/// * `tracer` - function, that receives finished span
+pub fn set_call_tracer(tracer: impl Fn(&CallSpan) + Send + Sync + 'static) {
+    let mut guard = CALL_TRACER.write().unwrap_or_else(|e| e.into_inner());
+    *guard = Some(Box::new(tracer));
+}
+
+/// Removes the function, set with `set_call_tracer`
+pub fn clear_call_tracer() {
+    let mut guard = CALL_TRACER.write().unwrap_or_else(|e| e.into_inner());
+    *guard = None;
+}
+
+/// Records the error, reported to 1C platform during the traced call
+pub(crate) fn note_error(error: &dyn fmt::Display) {
+    if is_enabled() {
+        LAST_ERROR.with(|last| *last.borrow_mut() = Some(error.to_string()));
+    }
+}
+
+/// Value, returned by Native API method
+pub(crate) trait TracedResult {
+    /// Returns the value for `CallSpan::result` and if it means success
+    fn describe(&self) -> (String, bool);
+}
+
+impl TracedResult for bool {
+    fn describe(&self) -> (String, bool) {
+        (self.to_string(), *self)
+    }
+}
+
+impl TracedResult for c_long {
+    fn describe(&self) -> (String, bool) {
+        (self.to_string(), *self >= 0)
+    }
+}
+
+/// Name, allocated for 1C platform, it is still owned by the AddIn when
+/// the span ends
+impl TracedResult for *const u16 {
+    fn describe(&self) -> (String, bool) {
+        match unsafe { WStr::from_ptr_opt(*self) } {
+            Some(name) => (quote_utf16(name.as_slice()), true),
+            None => ("null".to_string(), false),
+        }
+    }
+}
+
+/// Span of the call in progress
+pub(crate) struct Span {
+    span: CallSpan,
+    start: Instant,
+}
+
+/// Starts the span, if tracing is enabled
+/// # Arguments
+/// * `entry` - name of Native API method
+#[inline]
+pub(crate) fn begin(entry: &'static str) -> Option<Span> {
+    if !is_enabled() {
+        return None;
+    }
+    LAST_ERROR.with(|last| last.borrow_mut().take());
+    Some(Span {
+        span: CallSpan {
+            entry,
+            member: None,
+            name: None,
+            params: Vec::new(),
+            return_type: None,
+            result: String::new(),
+            success: false,
+            error: None,
+            duration: Duration::ZERO,
+        },
+        start: Instant::now(),
+    })
+}
+
+impl Span {
+    /// Sets index and name of the member
+    pub fn member(mut self, num: c_long, name: Option<&[u16]>) -> Self {
+        self.span.member = usize::try_from(num).ok();
+        self.span.name = name.map(String::from_utf16_lossy);
+        self
+    }
+
+    /// Sets the name, that is searched for
+    pub fn name(mut self, name: &[u16]) -> Self {
+        self.span.name = Some(String::from_utf16_lossy(name));
+        self
+    }
+
+    /// Sets index of the member, found by the name
+    pub fn found(mut self, num: c_long) -> Self {
+        self.span.member = usize::try_from(num).ok();
+        self
+    }
+
+    /// Adds parameters of the call
+    pub fn params<'a>(
+        mut self,
+        params: impl IntoIterator<Item = ParamRef<'a>>,
+    ) -> Self {
+        self.span.params.extend(
+            params
+                .into_iter()
+                .map(|param| (param.type_name(), describe(param))),
+        );
+        self
+    }
+}
+
+/// Finishes the span and passes it to the tracer
+/// # Arguments
+/// * `span` - span, started by `begin`
+/// * `result` - value, returned by Native API method
+/// * `ret` - value, returned to 1C platform through `tVariant`
+pub(crate) fn end(
+    span: Option<Span>,
+    result: &impl TracedResult,
+    ret: Option<&TVariant>,
+) {
+    let Some(Span { mut span, start }) = span else {
+        return;
+    };
+    span.duration = start.elapsed();
+    (span.result, span.success) = result.describe();
+    if let Some(ret) = ret.filter(|_| span.success) {
+        span.return_type = Some(ParamRef::from(ret).type_name());
+    }
+    span.error = LAST_ERROR.with(|last| last.borrow_mut().take());
+
+    // Tracer is called outside of the guard of the entry, so its panics
+    // must not unwind into 1C platform
+    let _ = panic_guard::catch(|| {
+        let tracer = CALL_TRACER.read().unwrap_or_else(|e| e.into_inner());
+        match tracer.as_ref() {
+            Some(tracer) => tracer(&span),
+            #[cfg(feature = "log")]
+            None => {
+                log::debug!(target: "native_api_1c_core::call_trace", "{span}")
+            }
+            #[cfg(not(feature = "log"))]
+            None => {}
+        }
+    });
+}
+
+/// Formats the value of a parameter, truncating long strings and blobs
+fn describe(param: ParamRef) -> String {
+    let value = match param {
+        ParamRef::Empty => return String::new(),
+        ParamRef::Null => return "NULL".to_string(),
+        ParamRef::Bool(v) => v.to_string(),
+        ParamRef::I8(v) => v.to_string(),
+        ParamRef::I16(v) => v.to_string(),
+        ParamRef::I32(v) => v.to_string(),
+        ParamRef::I64(v) => v.to_string(),
+        ParamRef::U8(v) => v.to_string(),
+        ParamRef::U16(v) => v.to_string(),
+        ParamRef::U32(v) => v.to_string(),
+        ParamRef::U64(v) => v.to_string(),
+        ParamRef::F32(v) => v.to_string(),
+        ParamRef::F64(v) | ParamRef::OleDate(v) => v.to_string(),
+        ParamRef::Date(v) => chrono::NaiveDateTime::from(v).to_string(),
+        ParamRef::Str(v) => return quote_utf16(v),
+        ParamRef::PStr(v) => {
+            // Every character takes at most 4 bytes
+            let head = &v[..v.len().min((MAX_VALUE_LEN + 1) * 4)];
+            return quote(String::from_utf8_lossy(head).chars());
+        }
+        ParamRef::Blob(v) => return format!("<{} bytes>", v.len()),
+        ParamRef::Error(v) => v.to_string(),
+        ParamRef::HResult(v) => format!("{v:#x}"),
+        ParamRef::ClsId(v) => format!("{v:02x?}"),
+    };
+    match value.char_indices().nth(MAX_VALUE_LEN) {
+        Some((end, _)) => format!("{}…", &value[..end]),
+        None => value,
+    }
+}
+
+/// Formats UTF-16 string quoted, decoding at most `MAX_VALUE_LEN` characters
+fn quote_utf16(s: &[u16]) -> String {
+    let chars = char::decode_utf16(s.iter().copied())
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER));
+    quote(chars)
+}
+
+/// Formats the string quoted, taking at most `MAX_VALUE_LEN` characters, so
+/// long strings are not decoded completely
+fn quote(mut chars: impl Iterator<Item = char>) -> String {
+    let value = chars.by_ref().take(MAX_VALUE_LEN).collect::<String>();
+    match chars.next() {
+        Some(_) => format!("{value:?}…"),
+        None => format!("{value:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::{
+        clear_call_tracer, describe, set_call_tracer, set_enabled, CallSpan,
+        MAX_VALUE_LEN,
+    };
+    use crate::{
+        dynamic::{DynamicAddIn, DynamicMethod},
+        ffi::provided_types::{ParamRef, ParamValue},
+        testing::TestHost,
+    };
+
+    #[test]
+    fn long_values_are_truncated() {
+        let long = "я".repeat(1_000_000);
+        let utf16 = long.encode_utf16().collect::<Vec<_>>();
+        let expected = format!("{:?}…", "я".repeat(MAX_VALUE_LEN));
+        assert_eq!(describe(ParamRef::Str(&utf16)), expected);
+        assert_eq!(describe(ParamRef::PStr(long.as_bytes())), expected);
+        assert_eq!(describe(ParamRef::Str(&utf16[..2])), "\"яя\"");
+        assert_eq!(describe(ParamRef::Blob(&[0; 3])), "<3 bytes>");
+    }
+
+    #[test]
+    fn calls_are_traced() {
+        let thread = thread::current().id();
+        let spans = Arc::new(Mutex::new(Vec::<CallSpan>::new()));
+        set_call_tracer({
+            let spans = spans.clone();
+            move |span| {
+                if thread::current().id() == thread {
+                    spans.lock().unwrap().push(span.clone());
+                }
+            }
+        });
+        set_enabled(true);
+
+        let method = DynamicMethod::function(1, |_| Ok(ParamValue::I32(1)));
+        let mut host = TestHost::new(
+            DynamicAddIn::new("Traced")
+                .with_method("Echo", "Эхо", method)
+                .unwrap(),
+        );
+        assert!(host.init());
+        let echo = host.find_method("echo").unwrap();
+        assert_eq!(host.get_method_name(echo, 1).as_deref(), Some("Echo"));
+        let mut params =
+            [ParamValue::Str("x".repeat(100).encode_utf16().collect())];
+        assert!(host.call_as_func(echo, &mut params).is_some());
+
+        set_call_tracer(|_| panic!("tracer failed"));
+        assert_eq!(host.get_n_methods(), 1);
+        clear_call_tracer();
+        set_enabled(false);
+
+        let spans = spans.lock().unwrap();
+        let find = spans.iter().find(|s| s.entry == "FindMethod").unwrap();
+        assert_eq!(find.member, Some(0));
+        assert_eq!(find.name.as_deref(), Some("echo"));
+        let name = spans.iter().find(|s| s.entry == "GetMethodName").unwrap();
+        assert_eq!(name.result, "\"Echo\"");
+        let call = spans.iter().find(|s| s.entry == "CallAsFunc").unwrap();
+        assert!(call.success);
+        assert_eq!(call.name.as_deref(), Some("Echo"));
+        assert_eq!(call.params[0].1, format!("{:?}…", "x".repeat(64)));
+        assert!(spans.iter().all(|s| s.entry != "GetNMethods"));
+    }
+}
@@ -1,10 +1,28 @@
-use std::ffi::{c_long, c_ushort};
+use std::{
+    ffi::{c_int, c_long, c_ushort, c_void},
+    fmt,
+    ptr::{self, NonNull},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
-use super::{provided_types::TVariant, string_utils::os_string_nil};
+use super::{
+    attached_info::AttachedInfo,
+    conversion::{FromParam, IntoReturn},
+    event_sender::EventSender,
+    exports,
+    memory_manager::MemoryManager,
+    msg_box::MsgBox,
+    platform_info::PlatformInfo,
+    provided_types::{ParamRef, ReturnValue, TVariant},
+    string_utils::{WStr, WString},
+    AppCapabilities,
+};
 
 /// Message codes that can be used in `Connection::add_error` method
 /// to specify message type.
 /// See [1C documentation](https://its.1c.ru/db/content/metod8dev/src/developers/platform/i8103221.htm#_com_infomessage)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MessageCode {
     /// Error without icon
     None = 1000,
@@ -28,6 +46,45 @@ pub enum MessageCode {
     DialogFail = 1009,
 }
 
+/// Errors, returned by `Connection` methods working with profile storage
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileError {
+    /// 1C platform failed to register the profile
+    RegisterFailed,
+    /// 1C platform failed to read the value
+    /// # Fields
+    /// * `code` - error code, provided by 1C platform
+    /// * `description` - error description, provided by 1C platform
+    ReadFailed { code: c_long, description: String },
+    /// Stored value can not be converted to the requested type
+    /// # Fields
+    /// * `expected` - name of the requested type
+    /// * `actual` - name of the type of the stored value
+    UnexpectedType {
+        expected: &'static str,
+        actual: &'static str,
+    },
+    /// 1C platform failed to write the value
+    WriteFailed,
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RegisterFailed => write!(f, "failed to register profile"),
+            Self::ReadFailed { code, description } => {
+                write!(f, "failed to read value ({code}): {description}")
+            }
+            Self::UnexpectedType { expected, actual } => {
+                write!(f, "expected value of type {expected}, got {actual}")
+            }
+            Self::WriteFailed => write!(f, "failed to write value"),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
 /// VTable for Connection object, derived from Native API interface. See original
 /// C++ implementation in [example project](https://its.1c.ru/db/files/1CITS/EXE/VNCOMPS/VNCOMPS.zip)
 /// from 1C documentation
@@ -47,7 +104,7 @@ struct ConnectionVTable {
         &Connection,
         *mut u16,
         &mut TVariant,
-        c_long,
+        *mut c_long,
         *mut *mut u16,
     ) -> bool,
     write:
@@ -68,6 +125,27 @@ struct ConnectionVTable {
     reset_status_line: unsafe extern "system" fn(&Connection),
 }
 
+/// Interfaces, that can be requested from 1C platform with
+/// `Connection::get_interface`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interfaces {
+    /// `IMsgBox`, see `MsgBox`
+    MsgBox = 0,
+    /// `IPlatformInfo`, see `PlatformInfo`
+    PlatformInfo,
+    /// `IAttachedInfo`, see `AttachedInfo`
+    AttachedInfo,
+}
+
+/// VTable for extended Connection object, passed by 1C platform if it supports
+/// `AppCapabilities` of at least version 1, derived from `IAddInDefBaseEx`
+#[repr(C)]
+struct ConnectionExVTable {
+    base: ConnectionVTable,
+    get_interface: unsafe extern "system" fn(&Connection, c_int) -> *mut c_void,
+}
+
 /// Connection object, used to communicate with 1C platform after the AddIn is loaded
 #[repr(C)]
 pub struct Connection {
@@ -89,8 +167,8 @@ impl Connection {
         description: &str,
     ) -> bool {
         unsafe {
-            let source_wstr = os_string_nil(source);
-            let description_wstr = os_string_nil(description);
+            let source_wstr = WString::from_str_truncate(source);
+            let description_wstr = WString::from_str_truncate(description);
             (self.vptr1.add_error)(
                 self,
                 code as u16,
@@ -110,9 +188,9 @@ impl Connection {
     /// `bool` - operation success status
     pub fn external_event(&self, caller: &str, name: &str, data: &str) -> bool {
         unsafe {
-            let mut caller_wstr = os_string_nil(caller);
-            let mut name_wstr = os_string_nil(name);
-            let mut data_wstr = os_string_nil(data);
+            let mut caller_wstr = WString::from_str_truncate(caller);
+            let mut name_wstr = WString::from_str_truncate(name);
+            let mut data_wstr = WString::from_str_truncate(data);
             (self.vptr1.external_event)(
                 self,
                 caller_wstr.as_mut_ptr(),
@@ -122,6 +200,13 @@ impl Connection {
         }
     }
 
+    /// Returns cloneable sender of external events, that can be moved to
+    /// other threads. All senders of the connection share the queue and
+    /// settings and become inert, when the AddIn is done
+    pub fn event_sender(&'static self) -> EventSender {
+        EventSender::new(self)
+    }
+
     /// Equivalent to `SetEventBufferDepth` from Native API interface
     /// # Arguments
     /// * `depth` - new event buffer depth
@@ -135,4 +220,434 @@ impl Connection {
     pub fn get_event_buffer_depth(&self) -> c_long {
         unsafe { (self.vptr1.get_event_buffer_depth)(self) }
     }
+
+    /// Equivalent to `CleanEventBuffer` from Native API interface and is used to remove
+    /// events, that were sent with `external_event` but not yet processed by 1C platform
+    pub fn clean_event_buffer(&self) {
+        unsafe { (self.vptr1.clean_event_buffer)(self) }
+    }
+
+    /// Equivalent to `SetStatusLine` from Native API interface and is used to show
+    /// text in the status line of 1C platform
+    /// # Arguments
+    /// * `status_line` - text to show
+    /// # Returns
+    /// `bool` - operation success status
+    pub fn set_status_line(&self, status_line: &str) -> bool {
+        unsafe {
+            let mut status_line_wstr = WString::from_str_truncate(status_line);
+            (self.vptr1.set_status_line)(self, status_line_wstr.as_mut_ptr())
+        }
+    }
+
+    /// Equivalent to `ResetStatusLine` from Native API interface and is used to
+    /// restore the status line of 1C platform
+    pub fn reset_status_line(&self) {
+        unsafe { (self.vptr1.reset_status_line)(self) }
+    }
+
+    /// Returns `AppCapabilities`, negotiated between 1C platform and the library
+    /// in `SetPlatformCapabilities`. Can be used in `AddInWrapper::init` to enable
+    /// features, that need newer capabilities
+    ///
+    /// **`exports::set_platform_capabilities` is mandatory.** Exports,
+    /// generated by `export_add_ins!`, call it. A hand-written
+    /// `SetPlatformCapabilities` export must call it too, otherwise
+    /// capabilities stay `Invalid`, and `get_interface`, `msg_box`,
+    /// `platform_info` and `attached_info` always return None
+    /// # Returns
+    /// `AppCapabilities` - negotiated capabilities
+    pub fn app_capabilities(&self) -> AppCapabilities {
+        exports::app_capabilities()
+    }
+
+    /// Equivalent to `GetInterface` from Native API interface and is used to get
+    /// additional interfaces, provided by 1C platform. Only available if
+    /// negotiated `AppCapabilities` are of at least version 1, see
+    /// `app_capabilities`
+    /// # Arguments
+    /// * `interface` - requested interface
+    /// # Returns
+    /// `Option<NonNull<c_void>>` - pointer to the interface object or None
+    /// if it is not available
+    pub fn get_interface(
+        &self,
+        interface: Interfaces,
+    ) -> Option<NonNull<c_void>> {
+        if self.app_capabilities() < AppCapabilities::Capabilities1 {
+            return None;
+        }
+        unsafe {
+            let vtable = &*(self.vptr1 as *const ConnectionVTable
+                as *const ConnectionExVTable);
+            NonNull::new((vtable.get_interface)(self, interface as c_int))
+        }
+    }
+
+    /// Returns MsgBox object, used to show dialogs to the user
+    /// # Returns
+    /// `Option<&MsgBox>` - MsgBox object or None if it is not available
+    pub fn msg_box(&self) -> Option<&MsgBox> {
+        let ptr = self.get_interface(Interfaces::MsgBox)?;
+        Some(unsafe { ptr.cast::<MsgBox>().as_ref() })
+    }
+
+    /// Returns PlatformInfo object, used to get version and type of 1C platform
+    /// # Returns
+    /// `Option<&PlatformInfo>` - PlatformInfo object or None if it is not available
+    pub fn platform_info(&self) -> Option<&PlatformInfo> {
+        let ptr = self.get_interface(Interfaces::PlatformInfo)?;
+        Some(unsafe { ptr.cast::<PlatformInfo>().as_ref() })
+    }
+
+    /// Returns AttachedInfo object, used to check if the AddIn runs isolated
+    /// # Returns
+    /// `Option<&AttachedInfo>` - AttachedInfo object or None if it is not available
+    pub fn attached_info(&self) -> Option<&AttachedInfo> {
+        let ptr = self.get_interface(Interfaces::AttachedInfo)?;
+        Some(unsafe { ptr.cast::<AttachedInfo>().as_ref() })
+    }
+
+    /// Equivalent to `RegisterProfileAs` from Native API interface and is used to set the name
+    /// of the profile, in which values are stored by `read` and `write` methods
+    /// # Arguments
+    /// * `profile_name` - name of the profile
+    /// # Returns
+    /// `Result<(), ProfileError>` - operation result
+    pub fn register_profile_as(
+        &self,
+        profile_name: &str,
+    ) -> Result<(), ProfileError> {
+        let mut profile_name_wstr = WString::from_str_truncate(profile_name);
+        let registered = unsafe {
+            (self.vptr1.register_profile_as)(
+                self,
+                profile_name_wstr.as_mut_ptr(),
+            )
+        };
+        match registered {
+            true => Ok(()),
+            false => Err(ProfileError::RegisterFailed),
+        }
+    }
+
+    /// Equivalent to `Read` from Native API interface and is used to read a value
+    /// from the platform's per-user settings storage. Memory, allocated by 1C
+    /// platform for the value or the error description, is released with
+    /// MemoryManager of the AddIn
+    /// # Arguments
+    /// * `name` - name of the value
+    /// # Returns
+    /// `Result<T, ProfileError>` - stored value, converted to `T`. Use
+    /// `ParamValue` to read the value of any type
+    pub fn read<T>(&self, name: &str) -> Result<T, ProfileError>
+    where
+        T: for<'a> FromParam<'a>,
+    {
+        let mut name_wstr = WString::from_str_truncate(name);
+        let mut value = TVariant::default();
+        let mut code: c_long = 0;
+        let mut description: *mut u16 = ptr::null_mut();
+        let memory = self.memory_manager();
+        unsafe {
+            if (self.vptr1.read)(
+                self,
+                name_wstr.as_mut_ptr(),
+                &mut value,
+                &mut code,
+                &mut description,
+            ) {
+                let param = ParamRef::from(&value);
+                let result = T::from_param(param).ok_or_else(|| {
+                    ProfileError::UnexpectedType {
+                        expected: T::EXPECTED,
+                        actual: param.type_name(),
+                    }
+                });
+                if let Some(memory) = memory {
+                    value.clear(memory);
+                }
+                return result;
+            }
+
+            let text = match WStr::from_ptr_opt(description) {
+                Some(text) => text.to_utf8_lossy(),
+                None => String::new(),
+            };
+            if let Some(memory) = memory {
+                memory.free_memory(&mut (description as *mut c_void));
+            }
+            Err(ProfileError::ReadFailed {
+                code,
+                description: text,
+            })
+        }
+    }
+
+    /// Equivalent to `Write` from Native API interface and is used to write a value
+    /// to the platform's per-user settings storage. Strings and blobs are
+    /// allocated with MemoryManager of the AddIn and released after the call
+    /// # Arguments
+    /// * `name` - name of the value
+    /// * `value` - value to store, `ParamValue` can be used for any type
+    /// # Returns
+    /// `Result<(), ProfileError>` - operation result
+    pub fn write(
+        &self,
+        name: &str,
+        value: impl IntoReturn,
+    ) -> Result<(), ProfileError> {
+        let memory = self.memory_manager().ok_or(ProfileError::WriteFailed)?;
+        let mut name_wstr = WString::from_str_truncate(name);
+        let mut variant = TVariant::default();
+        let mut converted = true;
+        value.into_return(ReturnValue {
+            mem: memory,
+            variant: &mut variant,
+            result: &mut converted,
+        });
+        let written = converted
+            && unsafe {
+                (self.vptr1.write)(self, name_wstr.as_mut_ptr(), &mut variant)
+            };
+        unsafe { variant.clear(memory) };
+        match written {
+            true => Ok(()),
+            false => Err(ProfileError::WriteFailed),
+        }
+    }
+
+    /// Returns MemoryManager of the AddIn, that uses the connection
+    fn memory_manager(&self) -> Option<&'static MemoryManager> {
+        let memory = MEMORY_MANAGERS.lock().unwrap_or_else(|e| e.into_inner());
+        memory
+            .iter()
+            .find(|(connection, _)| ptr::eq(*connection, self))
+            .map(|(_, memory)| *memory)
+    }
+}
+
+/// MemoryManager objects of initialized AddIn objects by their connections,
+/// used to release and allocate memory of profile values
+static MEMORY_MANAGERS: Mutex<
+    Vec<(&'static Connection, &'static MemoryManager)>,
+> = Mutex::new(Vec::new());
+
+/// Associates MemoryManager of the AddIn with its connection, called before
+/// `init` of the AddIn
+pub(crate) fn register_memory_manager(
+    connection: &'static Connection,
+    memory: &'static MemoryManager,
+) {
+    let mut managers =
+        MEMORY_MANAGERS.lock().unwrap_or_else(|e| e.into_inner());
+    managers.retain(|(registered, _)| !ptr::eq(*registered, connection));
+    managers.push((connection, memory));
+}
+
+/// Forgets MemoryManager, associated with the connection, called when the
+/// AddIn stops using the connection
+pub(crate) fn unregister_memory_manager(connection: &Connection) {
+    let mut managers =
+        MEMORY_MANAGERS.lock().unwrap_or_else(|e| e.into_inner());
+    managers.retain(|(registered, _)| !ptr::eq(*registered, connection));
+}
+
+/// Helper for showing progress of long operations in the status line of
+/// 1C platform. Updates are throttled to at most one per `interval`, and
+/// the status line is reset when the reporter is dropped, including
+/// during unwinding
+pub struct ProgressReporter<'a> {
+    connection: &'a Connection,
+    interval: Duration,
+    last_update: Option<Instant>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    /// Creates a new ProgressReporter
+    /// # Arguments
+    /// * `connection` - Connection object, used to set the status line
+    /// * `interval` - minimal interval between status line updates
+    pub fn new(connection: &'a Connection, interval: Duration) -> Self {
+        Self {
+            connection,
+            interval,
+            last_update: None,
+        }
+    }
+
+    /// Shows text in the status line, unless the previous update was
+    /// less than `interval` ago
+    /// # Arguments
+    /// * `text` - text to show
+    /// # Returns
+    /// `bool` - if the status line was updated
+    pub fn report(&mut self, text: &str) -> bool {
+        let now = Instant::now();
+        if let Some(last_update) = self.last_update {
+            if now.duration_since(last_update) < self.interval {
+                return false;
+            }
+        }
+        self.force_report(text)
+    }
+
+    /// Shows progress in the status line as `text: done/total (percent%)`.
+    /// Update is throttled the same way as in `report`, except for the
+    /// final one, when `done` reaches `total`
+    /// # Arguments
+    /// * `text` - description of the operation
+    /// * `done` - number of processed items
+    /// * `total` - total number of items
+    /// # Returns
+    /// `bool` - if the status line was updated
+    pub fn report_progress(
+        &mut self,
+        text: &str,
+        done: usize,
+        total: usize,
+    ) -> bool {
+        let percent = match total {
+            0 => 100,
+            _ => done.min(total) * 100 / total,
+        };
+        let status_line = format!("{text}: {done}/{total} ({percent}%)");
+        match done >= total {
+            true => self.force_report(&status_line),
+            false => self.report(&status_line),
+        }
+    }
+
+    /// Shows text in the status line without throttling
+    /// # Arguments
+    /// * `text` - text to show
+    /// # Returns
+    /// `bool` - operation success status
+    pub fn force_report(&mut self, text: &str) -> bool {
+        self.last_update = Some(Instant::now());
+        self.connection.set_status_line(text)
+    }
+}
+
+impl Drop for ProgressReporter<'_> {
+    fn drop(&mut self) {
+        self.connection.reset_status_line();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+        time::Duration,
+    };
+
+    use super::{Connection, ProfileError, ProgressReporter};
+    use crate::{
+        dynamic::DynamicAddIn, ffi::provided_types::ParamValue,
+        testing::TestHost,
+    };
+
+    #[test]
+    fn profile_values_are_read_without_leaks() {
+        let connection: Rc<Cell<Option<&'static Connection>>> =
+            Default::default();
+        let addin = DynamicAddIn::new("Profile").on_init({
+            let connection = connection.clone();
+            move |interface| {
+                connection.set(Some(interface));
+                Ok(())
+            }
+        });
+        let mut host = TestHost::new(addin);
+        assert!(host.init());
+        let connection = connection.get().unwrap();
+
+        assert_eq!(
+            connection.write("name", "value"),
+            Err(ProfileError::WriteFailed)
+        );
+        connection.register_profile_as("settings").unwrap();
+        connection.write("name", "value").unwrap();
+        connection.write("count", 3).unwrap();
+        connection.write("blob", vec![1u8, 2]).unwrap();
+        assert_eq!(host.memory().live_allocations(), 0);
+        assert!(
+            host.connection().profile_value("settings", "name")
+                == Some(ParamValue::Str("value".encode_utf16().collect()))
+        );
+
+        assert_eq!(connection.read::<String>("name").unwrap(), "value");
+        assert_eq!(connection.read::<i32>("count").unwrap(), 3);
+        assert_eq!(connection.read::<Vec<u8>>("blob").unwrap(), [1, 2]);
+        assert!(
+            connection.read::<ParamValue>("count").unwrap()
+                == ParamValue::I32(3)
+        );
+        assert_eq!(
+            connection.read::<bool>("name"),
+            Err(ProfileError::UnexpectedType {
+                expected: "Boolean",
+                actual: "String",
+            })
+        );
+        assert_eq!(
+            connection.read::<String>("missing"),
+            Err(ProfileError::ReadFailed {
+                code: 1,
+                description: "missing not found".to_string(),
+            })
+        );
+        assert_eq!(host.memory().live_allocations(), 0);
+        assert_eq!(host.memory().invalid_frees(), 0);
+    }
+
+    #[test]
+    fn progress_is_throttled_and_reset() {
+        let connection: Rc<Cell<Option<&'static Connection>>> =
+            Default::default();
+        let addin = DynamicAddIn::new("Progress").on_init({
+            let connection = connection.clone();
+            move |interface| {
+                connection.set(Some(interface));
+                Ok(())
+            }
+        });
+        let mut host = TestHost::new(addin);
+        assert!(host.init());
+        let connection = connection.get().unwrap();
+        let status_line = || host.connection().status_line();
+
+        let hour = Duration::from_secs(3600);
+        let mut progress = ProgressReporter::new(connection, hour);
+        assert!(progress.report("first"));
+        assert!(!progress.report("second"));
+        assert!(!progress.report_progress("Loading", 1, 3));
+        assert_eq!(status_line().as_deref(), Some("first"));
+        assert!(progress.force_report("forced"));
+        assert_eq!(status_line().as_deref(), Some("forced"));
+        assert!(progress.report_progress("Loading", 5, 3));
+        assert_eq!(status_line().as_deref(), Some("Loading: 5/3 (100%)"));
+        assert!(progress.report_progress("Empty", 0, 0));
+        assert_eq!(status_line().as_deref(), Some("Empty: 0/0 (100%)"));
+        drop(progress);
+        assert_eq!(status_line(), None);
+
+        let mut progress = ProgressReporter::new(connection, Duration::ZERO);
+        assert!(progress.report_progress("Loading", 1, 3));
+        assert!(progress.report_progress("Loading", 2, 3));
+        assert_eq!(status_line().as_deref(), Some("Loading: 2/3 (66%)"));
+        drop(progress);
+
+        let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut progress = ProgressReporter::new(connection, hour);
+            progress.report("unwinding");
+            assert_eq!(status_line().as_deref(), Some("unwinding"));
+            panic::resume_unwind(Box::new(()));
+        }));
+        assert!(unwound.is_err());
+        assert_eq!(status_line(), None);
+    }
 }
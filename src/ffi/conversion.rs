@@ -0,0 +1,499 @@
+use std::fmt;
+
+use super::{
+    provided_types::{ParamRef, ParamValue, Params, ReturnValue, Tm},
+    string_utils::from_utf16_lossy,
+};
+
+/// Error of converting `ParamValue` to a Rust type. The message names the
+/// parameter by its index, starting from 0, as Native API and other messages
+/// of this crate number parameters, properties and methods
+/// # Fields
+/// * `index` - index of the parameter, starting from 0
+/// * `expected` - name of the expected type
+/// * `found` - name of the type, passed by 1C platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamError {
+    pub index: usize,
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "parameter #{}: expected {}, found {}",
+            self.index, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+/// Conversion from the parameter, passed by 1C platform, to a Rust type.
+/// Values can borrow strings and blobs from the parameter for lifetime `'a`
+pub trait FromParam<'a>: Sized {
+    /// Name of the expected type, used in `ParamError`
+    const EXPECTED: &'static str;
+
+    /// Converts the parameter to the value of this type
+    /// # Arguments
+    /// * `param` - value passed by 1C platform
+    /// # Returns
+    /// `Option<Self>` - converted value or None if the value has other type
+    /// or does not fit into this type
+    fn from_param(param: ParamRef<'a>) -> Option<Self>;
+}
+
+/// Conversion from a Rust type to the value, returned to 1C platform
+pub trait IntoReturn {
+    /// Sets the value to `ReturnValue`
+    /// # Arguments
+    /// * `val` - ReturnValue object that will be used to return the value
+    fn into_return(self, val: ReturnValue);
+}
+
+/// Converts parameter with the given index to a Rust type. Missing parameter
+/// is treated as `ParamRef::Empty`
+/// # Arguments
+/// * `params` - parameters passed by 1C platform
+/// * `index` - index of the parameter
+/// # Returns
+/// `Result<T, ParamError>` - converted value or error, naming the parameter
+/// and the expected type
+pub fn param<'a, T: FromParam<'a>>(
+    params: &'a Params,
+    index: usize,
+) -> Result<T, ParamError> {
+    let param = params.get(index).unwrap_or(ParamRef::Empty);
+    T::from_param(param).ok_or_else(|| ParamError {
+        index,
+        expected: T::EXPECTED,
+        found: param.type_name(),
+    })
+}
+
+impl FromParam<'_> for ParamValue {
+    const EXPECTED: &'static str = "any value";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        Some(param.into())
+    }
+}
+
+impl<'a> FromParam<'a> for ParamRef<'a> {
+    const EXPECTED: &'static str = "any value";
+
+    fn from_param(param: ParamRef<'a>) -> Option<Self> {
+        Some(param)
+    }
+}
+
+impl IntoReturn for ParamValue {
+    fn into_return(self, val: ReturnValue) {
+        ParamRef::from(&self).into_return(val)
+    }
+}
+
+impl IntoReturn for ParamRef<'_> {
+    fn into_return(self, val: ReturnValue) {
+        match self {
+            ParamRef::Empty => val.set_empty(),
+            ParamRef::Bool(v) => val.set_bool(v),
+            ParamRef::I32(v) => val.set_i32(v),
+            ParamRef::F64(v) => val.set_f64(v),
+            ParamRef::Date(v) => val.set_date(v),
+            ParamRef::Str(v) => val.set_str(v),
+            ParamRef::Blob(v) => val.set_blob(v),
+            ParamRef::Null => val.set_null(),
+            ParamRef::I8(v) => val.set_i8(v),
+            ParamRef::I16(v) => val.set_i16(v),
+            ParamRef::I64(v) => val.set_i64(v),
+            ParamRef::U8(v) => val.set_u8(v),
+            ParamRef::U16(v) => val.set_u16(v),
+            ParamRef::U32(v) => val.set_u32(v),
+            ParamRef::U64(v) => val.set_u64(v),
+            ParamRef::F32(v) => val.set_f32(v),
+            ParamRef::OleDate(v) => val.set_ole_date(v),
+            ParamRef::PStr(v) => val.set_pstr(v),
+            ParamRef::Error(v) => val.set_error(v),
+            ParamRef::HResult(v) => val.set_hresult(v),
+            ParamRef::ClsId(v) => val.set_clsid(v),
+        }
+    }
+}
+
+impl FromParam<'_> for bool {
+    const EXPECTED: &'static str = "Boolean";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        match param {
+            ParamRef::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl IntoReturn for bool {
+    fn into_return(self, val: ReturnValue) {
+        val.set_bool(self)
+    }
+}
+
+impl FromParam<'_> for f64 {
+    const EXPECTED: &'static str = "Number";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        match param {
+            ParamRef::F64(v) => Some(v),
+            ParamRef::F32(v) => Some(v as f64),
+            param => integer(param).map(|v| v as f64),
+        }
+    }
+}
+
+impl IntoReturn for f64 {
+    fn into_return(self, val: ReturnValue) {
+        val.set_f64(self)
+    }
+}
+
+impl FromParam<'_> for f32 {
+    const EXPECTED: &'static str = "Number";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        f64::from_param(param).map(|v| v as f32)
+    }
+}
+
+impl IntoReturn for f32 {
+    fn into_return(self, val: ReturnValue) {
+        val.set_f32(self)
+    }
+}
+
+/// Extracts integer value from any integer parameter. 1C platform passes
+/// integer numbers, that do not fit into integer types, as floats without
+/// fractional part, so such floats are accepted too
+fn integer(param: ParamRef<'_>) -> Option<i128> {
+    match param {
+        ParamRef::I8(v) => Some(v as i128),
+        ParamRef::I16(v) => Some(v as i128),
+        ParamRef::I32(v) => Some(v as i128),
+        ParamRef::I64(v) => Some(v as i128),
+        ParamRef::U8(v) => Some(v as i128),
+        ParamRef::U16(v) => Some(v as i128),
+        ParamRef::U32(v) => Some(v as i128),
+        ParamRef::U64(v) => Some(v as i128),
+        ParamRef::F32(v) if v.fract() == 0.0 => Some(v as i128),
+        ParamRef::F64(v) if v.fract() == 0.0 => Some(v as i128),
+        _ => None,
+    }
+}
+
+/// Implements conversions for integer types, values are returned to
+/// 1C platform as `ReturnValue` of the same size
+macro_rules! impl_integer {
+    ($($ty:ty => $setter:ident($target:ty), $expected:literal;)+) => {$(
+        impl FromParam<'_> for $ty {
+            const EXPECTED: &'static str = $expected;
+
+            fn from_param(param: ParamRef<'_>) -> Option<Self> {
+                integer(param).and_then(|v| <$ty>::try_from(v).ok())
+            }
+        }
+
+        impl IntoReturn for $ty {
+            fn into_return(self, val: ReturnValue) {
+                val.$setter(self as $target)
+            }
+        }
+    )+};
+}
+
+impl_integer! {
+    i8 => set_i8(i8), "integer Number in range of i8";
+    i16 => set_i16(i16), "integer Number in range of i16";
+    i32 => set_i32(i32), "integer Number in range of i32";
+    i64 => set_i64(i64), "integer Number in range of i64";
+    isize => set_i64(i64), "integer Number in range of isize";
+    u8 => set_u8(u8), "integer Number in range of u8";
+    u16 => set_u16(u16), "integer Number in range of u16";
+    u32 => set_u32(u32), "integer Number in range of u32";
+    u64 => set_u64(u64), "integer Number in range of u64";
+    usize => set_u64(u64), "integer Number in range of usize";
+}
+
+impl FromParam<'_> for String {
+    const EXPECTED: &'static str = "String";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        match param {
+            ParamRef::Str(v) => Some(from_utf16_lossy(v)),
+            ParamRef::PStr(v) => Some(String::from_utf8_lossy(v).into()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoReturn for String {
+    fn into_return(self, val: ReturnValue) {
+        self.as_str().into_return(val)
+    }
+}
+
+impl IntoReturn for &str {
+    fn into_return(self, val: ReturnValue) {
+        val.set_str(&self.encode_utf16().collect::<Vec<_>>())
+    }
+}
+
+impl<'a> FromParam<'a> for &'a [u16] {
+    const EXPECTED: &'static str = "String";
+
+    fn from_param(param: ParamRef<'a>) -> Option<Self> {
+        match param {
+            ParamRef::Str(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl FromParam<'_> for Vec<u8> {
+    const EXPECTED: &'static str = "BinaryData";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        match param {
+            ParamRef::Blob(v) => Some(v.to_vec()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoReturn for Vec<u8> {
+    fn into_return(self, val: ReturnValue) {
+        self.as_slice().into_return(val)
+    }
+}
+
+impl<'a> FromParam<'a> for &'a [u8] {
+    const EXPECTED: &'static str = "BinaryData";
+
+    fn from_param(param: ParamRef<'a>) -> Option<Self> {
+        match param {
+            ParamRef::Blob(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl IntoReturn for &[u8] {
+    fn into_return(self, val: ReturnValue) {
+        val.set_blob(self)
+    }
+}
+
+impl FromParam<'_> for Tm {
+    const EXPECTED: &'static str = "Date";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        match param {
+            ParamRef::Date(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl IntoReturn for Tm {
+    fn into_return(self, val: ReturnValue) {
+        val.set_date(self)
+    }
+}
+
+impl FromParam<'_> for chrono::DateTime<chrono::FixedOffset> {
+    const EXPECTED: &'static str = "Date";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        Tm::from_param(param).map(Self::from)
+    }
+}
+
+impl IntoReturn for chrono::DateTime<chrono::FixedOffset> {
+    fn into_return(self, val: ReturnValue) {
+        val.set_date(self.into())
+    }
+}
+
+impl FromParam<'_> for chrono::NaiveDateTime {
+    const EXPECTED: &'static str = "Date";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        Tm::from_param(param).map(Self::from)
+    }
+}
+
+impl IntoReturn for chrono::NaiveDateTime {
+    fn into_return(self, val: ReturnValue) {
+        val.set_date(self.into())
+    }
+}
+
+impl<'a, T: FromParam<'a>> FromParam<'a> for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_param(param: ParamRef<'a>) -> Option<Self> {
+        match param {
+            ParamRef::Empty => Some(None),
+            param => T::from_param(param).map(Some),
+        }
+    }
+}
+
+impl<T: IntoReturn> IntoReturn for Option<T> {
+    fn into_return(self, val: ReturnValue) {
+        match self {
+            Some(v) => v.into_return(val),
+            None => val.set_empty(),
+        }
+    }
+}
+
+impl IntoReturn for () {
+    fn into_return(self, val: ReturnValue) {
+        val.set_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, NaiveDate, NaiveDateTime};
+
+    use super::{param, FromParam, IntoReturn, ParamError};
+    use crate::{
+        ffi::provided_types::{
+            ParamRef, ParamValue, Params, ReturnValue, TVariant, Tm,
+        },
+        testing::TestMemoryManager,
+    };
+
+    fn str(s: &str) -> ParamValue {
+        ParamValue::Str(s.encode_utf16().collect())
+    }
+
+    /// Returns the value to a variant and reads it back
+    fn returned(value: impl IntoReturn) -> ParamValue {
+        let memory = TestMemoryManager::new();
+        let mem = memory.as_memory_manager();
+        let mut variant = TVariant::default();
+        let mut result = true;
+        value.into_return(ReturnValue {
+            mem,
+            variant: &mut variant,
+            result: &mut result,
+        });
+        assert!(result);
+        let value = ParamValue::from(&variant);
+        unsafe { variant.clear(mem) };
+        assert_eq!(memory.live_allocations(), 0);
+        value
+    }
+
+    #[test]
+    fn integers_are_range_checked() {
+        assert_eq!(i8::from_param(ParamRef::I32(300)), None);
+        assert_eq!(i8::from_param(ParamRef::I32(-128)), Some(-128));
+        assert_eq!(u8::from_param(ParamRef::I8(-1)), None);
+        assert_eq!(u64::from_param(ParamRef::I64(-1)), None);
+        assert_eq!(i64::from_param(ParamRef::U64(u64::MAX)), None);
+        assert_eq!(u64::from_param(ParamRef::U64(u64::MAX)), Some(u64::MAX));
+        assert_eq!(i32::from_param(ParamRef::Bool(true)), None);
+        assert_eq!(f64::from_param(ParamRef::I64(-5)), Some(-5.0));
+        assert!(returned(7u8) == ParamValue::U8(7));
+        assert!(returned(-7isize) == ParamValue::I64(-7));
+    }
+
+    #[test]
+    fn integral_floats_are_integers() {
+        assert_eq!(i32::from_param(ParamRef::F64(3.0)), Some(3));
+        assert_eq!(i32::from_param(ParamRef::F32(-3.0)), Some(-3));
+        assert_eq!(i64::from_param(ParamRef::F64(1e15)), Some(1e15 as i64));
+        assert_eq!(i32::from_param(ParamRef::F64(3.5)), None);
+        assert_eq!(u32::from_param(ParamRef::F32(0.25)), None);
+        assert_eq!(i8::from_param(ParamRef::F64(300.0)), None);
+        assert_eq!(i32::from_param(ParamRef::F64(f64::NAN)), None);
+    }
+
+    #[test]
+    fn options_accept_empty() {
+        assert_eq!(Option::<i32>::from_param(ParamRef::Empty), Some(None));
+        assert_eq!(Option::<i32>::from_param(ParamRef::I32(1)), Some(Some(1)));
+        assert_eq!(Option::<i32>::from_param(ParamRef::Bool(true)), None);
+        assert_eq!(i32::from_param(ParamRef::Empty), None);
+        assert!(returned(None::<i32>) == ParamValue::Empty);
+        assert!(returned(Some(true)) == ParamValue::Bool(true));
+    }
+
+    #[test]
+    fn strings() {
+        let utf16 = "Строка".encode_utf16().collect::<Vec<_>>();
+        let param = ParamRef::Str(&utf16);
+        assert_eq!(String::from_param(param).as_deref(), Some("Строка"));
+        assert_eq!(<&[u16]>::from_param(param), Some(utf16.as_slice()));
+
+        let pstr = ParamRef::PStr(b"text");
+        assert_eq!(String::from_param(pstr).as_deref(), Some("text"));
+        assert_eq!(<&[u16]>::from_param(pstr), None);
+        assert_eq!(String::from_param(ParamRef::Blob(b"text")), None);
+
+        assert!(returned("Строка") == ParamValue::Str(utf16.clone()));
+        assert!(returned("Строка".to_string()) == ParamValue::Str(utf16));
+        assert!(returned(vec![1u8, 2]) == ParamValue::Blob(vec![1, 2]));
+    }
+
+    #[test]
+    fn dates() {
+        let naive = NaiveDate::from_ymd_opt(2024, 2, 29)
+            .unwrap()
+            .and_hms_opt(23, 59, 58)
+            .unwrap();
+        let tm = Tm::from(naive);
+        let param = ParamRef::Date(tm);
+        assert_eq!(Tm::from_param(param), Some(tm));
+        assert_eq!(NaiveDateTime::from_param(param), Some(naive));
+        let offset = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(
+            chrono::DateTime::<FixedOffset>::from_param(param),
+            Some(naive.and_local_timezone(offset).unwrap())
+        );
+        assert_eq!(Tm::from_param(ParamRef::OleDate(45_000.0)), None);
+        assert!(returned(naive) == ParamValue::Date(tm));
+    }
+
+    #[test]
+    fn param_errors() {
+        let mut values = [ParamValue::I32(1), str("two")];
+        let params = Params::from(&mut values[..]);
+        assert_eq!(param::<i32>(&params, 0), Ok(1));
+        let err = param::<i32>(&params, 1).unwrap_err();
+        assert_eq!(
+            err,
+            ParamError {
+                index: 1,
+                expected: "integer Number in range of i32",
+                found: "String",
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "parameter #1: expected integer Number in range of i32, \
+             found String"
+        );
+        // Missing parameters are read as Undefined
+        assert_eq!(param::<Option<bool>>(&params, 2), Ok(None));
+        assert_eq!(
+            param::<bool>(&params, 2).unwrap_err().to_string(),
+            "parameter #2: expected Boolean, found Undefined"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::c_long,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::connection::Connection;
+
+/// Interval between attempts to deliver an event with `OverflowPolicy::Block`
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Default number of events, kept by `EventSender` while 1C platform event
+/// buffer is full
+const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+/// What `EventSender` does, when 1C platform rejects an event, because its
+/// event buffer is full
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Event is queued and delivered with later sends or `flush`. If the
+    /// queue is full, the oldest queued event is dropped
+    #[default]
+    DropOldest,
+    /// Sending thread waits until 1C platform accepts the event or
+    /// `timeout` expires. `None` waits until the event is accepted or
+    /// `done` of the AddIn is called, then the send fails with
+    /// `SendError::Closed`, so `done` can join the sending thread
+    Block { timeout: Option<Duration> },
+    /// Same as `DropOldest`, but a queued event with the same source and
+    /// name is replaced by the new one, so only the latest data is delivered
+    Coalesce,
+}
+
+/// Error of sending an event with `EventSender`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// AddIn is done and the connection to 1C platform is closed
+    Closed,
+    /// 1C platform did not accept the event before timeout
+    /// of `OverflowPolicy::Block`
+    Timeout,
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "connection to 1C platform is closed"),
+            Self::Timeout => write!(f, "event buffer of 1C platform is full"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// External event, waiting for delivery to 1C platform
+struct Event {
+    source: String,
+    name: String,
+    data: String,
+}
+
+/// State of the connection, shared by all `EventSender`s obtained from it
+/// # Fields
+/// * `connection` - connection to 1C platform, None after `done`
+/// * `closing` - if `done` of the AddIn is in progress
+/// * `queue` - events, rejected by 1C platform and waiting for delivery
+/// * `policy` - overflow policy
+/// * `capacity` - maximum number of queued events
+/// * `dropped` - number of events, dropped because the queue was full
+struct State {
+    connection: Option<&'static Connection>,
+    closing: bool,
+    queue: VecDeque<Event>,
+    policy: OverflowPolicy,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl State {
+    /// Passes queued events to 1C platform, until it rejects one
+    /// # Returns
+    /// `Result<bool, SendError>` - if the queue is empty
+    fn flush(&mut self) -> Result<bool, SendError> {
+        let connection = self.connection.ok_or(SendError::Closed)?;
+        while let Some(event) = self.queue.front() {
+            if !connection.external_event(
+                &event.source,
+                &event.name,
+                &event.data,
+            ) {
+                return Ok(false);
+            }
+            self.queue.pop_front();
+        }
+        Ok(true)
+    }
+
+    /// Queues the event according to the overflow policy
+    fn enqueue(&mut self, event: Event) {
+        if self.policy == OverflowPolicy::Coalesce {
+            let queued = self.queue.iter_mut().find(|queued| {
+                queued.source == event.source && queued.name == event.name
+            });
+            if let Some(queued) = queued {
+                *queued = event;
+                return;
+            }
+        }
+        self.queue.push_back(event);
+        while self.queue.len() > self.capacity {
+            self.queue.pop_front();
+            self.dropped += 1;
+        }
+    }
+}
+
+/// Registry of shared states by address of the connection, so senders
+/// obtained from the same connection share queue and settings
+fn registry() -> MutexGuard<'static, HashMap<usize, Arc<Mutex<State>>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<usize, Arc<Mutex<State>>>>> =
+        OnceLock::new();
+    REGISTRY
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// Cloneable handle for sending external events to 1C platform from any
+/// thread. Obtained with `Connection::event_sender`.
+///
+/// Calls to 1C platform from all senders of the connection are serialized.
+/// When 1C platform event buffer is full, events are handled according to
+/// `OverflowPolicy`. After `done` of the AddIn senders become inert: queued
+/// events are discarded and sends return `SendError::Closed`
+#[derive(Clone)]
+pub struct EventSender {
+    state: Arc<Mutex<State>>,
+}
+
+impl EventSender {
+    /// Returns sender, sharing state with other senders of the connection
+    pub(crate) fn new(connection: &'static Connection) -> Self {
+        let key = connection as *const Connection as usize;
+        let state = registry()
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(Mutex::new(State {
+                    connection: Some(connection),
+                    closing: false,
+                    queue: VecDeque::new(),
+                    policy: OverflowPolicy::default(),
+                    capacity: DEFAULT_QUEUE_CAPACITY,
+                    dropped: 0,
+                }))
+            })
+            .clone();
+        Self { state }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sends external event to 1C platform, see `Connection::external_event`.
+    /// Queued events are delivered first, to keep the order of events
+    /// # Arguments
+    /// * `source` - name of the event source
+    /// * `name` - name of the event
+    /// * `data` - data of the event
+    /// # Returns
+    /// `Result<(), SendError>` - Ok if the event was accepted by 1C platform
+    /// or queued
+    pub fn send(
+        &self,
+        source: &str,
+        name: &str,
+        data: &str,
+    ) -> Result<(), SendError> {
+        let started = Instant::now();
+        loop {
+            let mut state = self.lock();
+            if state.flush()? {
+                let connection = state.connection.ok_or(SendError::Closed)?;
+                if connection.external_event(source, name, data) {
+                    return Ok(());
+                }
+            }
+            let timeout = match state.policy {
+                OverflowPolicy::Block { .. } if state.closing => {
+                    return Err(SendError::Closed)
+                }
+                OverflowPolicy::Block { timeout } => timeout,
+                OverflowPolicy::DropOldest | OverflowPolicy::Coalesce => {
+                    state.enqueue(Event {
+                        source: source.to_owned(),
+                        name: name.to_owned(),
+                        data: data.to_owned(),
+                    });
+                    return Ok(());
+                }
+            };
+            drop(state);
+            if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                return Err(SendError::Timeout);
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+
+    /// Passes queued events to 1C platform
+    /// # Returns
+    /// `Result<bool, SendError>` - if all queued events were delivered
+    pub fn flush(&self) -> Result<bool, SendError> {
+        self.lock().flush()
+    }
+
+    /// Sets overflow policy for all senders of the connection
+    pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.lock().policy = policy;
+    }
+
+    /// Returns current overflow policy
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.lock().policy
+    }
+
+    /// Sets maximum number of events, queued while 1C platform event buffer
+    /// is full. Excess events are dropped, oldest first
+    pub fn set_queue_capacity(&self, capacity: usize) {
+        let mut state = self.lock();
+        state.capacity = capacity;
+        while state.queue.len() > capacity {
+            state.queue.pop_front();
+            state.dropped += 1;
+        }
+    }
+
+    /// Returns number of queued events
+    pub fn queued(&self) -> usize {
+        self.lock().queue.len()
+    }
+
+    /// Returns number of events, dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    /// Sets depth of 1C platform event buffer,
+    /// see `Connection::set_event_buffer_depth`
+    /// # Returns
+    /// `Result<bool, SendError>` - operation success status
+    pub fn set_buffer_depth(&self, depth: c_long) -> Result<bool, SendError> {
+        let state = self.lock();
+        let connection = state.connection.ok_or(SendError::Closed)?;
+        Ok(connection.set_event_buffer_depth(depth))
+    }
+
+    /// Returns depth of 1C platform event buffer,
+    /// see `Connection::get_event_buffer_depth`
+    pub fn buffer_depth(&self) -> Result<c_long, SendError> {
+        let state = self.lock();
+        let connection = state.connection.ok_or(SendError::Closed)?;
+        Ok(connection.get_event_buffer_depth())
+    }
+
+    /// Checks if the AddIn is done and the sender is inert
+    pub fn is_closed(&self) -> bool {
+        self.lock().connection.is_none()
+    }
+}
+
+impl fmt::Debug for EventSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("EventSender")
+            .field("closed", &state.connection.is_none())
+            .field("policy", &state.policy)
+            .field("queued", &state.queue.len())
+            .field("dropped", &state.dropped)
+            .finish()
+    }
+}
+
+/// Stops waiting of senders with `OverflowPolicy::Block`, called before
+/// `done` of the AddIn, so threads, that are joined in `done`, are not
+/// blocked in `EventSender::send`
+pub(crate) fn begin_close(connection: &Connection) {
+    let key = connection as *const Connection as usize;
+    let Some(state) = registry().get(&key).cloned() else {
+        return;
+    };
+    state.lock().unwrap_or_else(|err| err.into_inner()).closing = true;
+}
+
+/// Makes senders of the connection inert, called when the AddIn is done
+/// or its object is destroyed.
+/// Waits for sends in progress, so no calls to 1C platform are made through
+/// the connection afterwards
+pub(crate) fn close(connection: &Connection) {
+    let key = connection as *const Connection as usize;
+    let Some(state) = registry().remove(&key) else {
+        return;
+    };
+    let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+    state.connection = None;
+    state.queue.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, thread, time::Duration};
+
+    use super::{EventSender, OverflowPolicy, SendError};
+    use crate::{
+        dynamic::DynamicAddIn, interface::AddInError, testing::TestHost,
+    };
+
+    #[test]
+    fn blocked_send_fails_when_done_is_called() {
+        let worker = Rc::new(RefCell::new(None));
+        let result = Rc::new(RefCell::new(None));
+        let addin = DynamicAddIn::new("Sender")
+            .on_init({
+                let worker = worker.clone();
+                move |connection| {
+                    let sender = connection.event_sender();
+                    sender.set_overflow_policy(OverflowPolicy::Block {
+                        timeout: None,
+                    });
+                    *worker.borrow_mut() = Some(thread::spawn(move || {
+                        sender.send("Sender", "Event", "data")
+                    }));
+                    Ok(())
+                }
+            })
+            .on_done({
+                let result = result.clone();
+                move || {
+                    let worker = worker.borrow_mut().take().unwrap();
+                    *result.borrow_mut() = worker.join().ok();
+                }
+            });
+        let mut host = TestHost::new(addin);
+        host.connection().set_reject_events(true);
+        assert!(host.init());
+        thread::sleep(Duration::from_millis(50));
+        host.done();
+        assert_eq!(*result.borrow(), Some(Err(SendError::Closed)));
+        assert!(host.connection().events().is_empty());
+    }
+
+    #[test]
+    fn sender_is_closed_when_destroyed_after_failed_init() {
+        let sender: Rc<RefCell<Option<EventSender>>> = Default::default();
+        let addin = DynamicAddIn::new("Sender").on_init({
+            let sender = sender.clone();
+            move |connection| {
+                *sender.borrow_mut() = Some(connection.event_sender());
+                Err(AddInError::new("init failed"))
+            }
+        });
+        let mut host = TestHost::new(addin);
+        assert!(!host.init());
+        let sender = sender.borrow_mut().take().unwrap();
+        assert!(!sender.is_closed());
+        drop(host);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send("Sender", "Event", ""), Err(SendError::Closed));
+    }
+
+    #[test]
+    fn events_are_queued_and_flushed() {
+        let sender: Rc<RefCell<Option<EventSender>>> = Default::default();
+        let addin = DynamicAddIn::new("Sender").on_init({
+            let sender = sender.clone();
+            move |connection| {
+                *sender.borrow_mut() = Some(connection.event_sender());
+                Ok(())
+            }
+        });
+        let mut host = TestHost::new(addin);
+        assert!(host.init());
+        let sender = sender.borrow_mut().take().unwrap();
+        host.connection().set_reject_events(true);
+        sender.set_queue_capacity(2);
+        for data in ["1", "2", "3"] {
+            assert_eq!(sender.send("Sender", "Event", data), Ok(()));
+        }
+        assert_eq!((sender.queued(), sender.dropped()), (2, 1));
+        host.connection().set_reject_events(false);
+        assert_eq!(sender.flush(), Ok(true));
+        let data = host
+            .connection()
+            .events()
+            .into_iter()
+            .map(|event| event.data)
+            .collect::<Vec<_>>();
+        assert_eq!(data, ["2", "3"]);
+    }
+}
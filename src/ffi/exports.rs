@@ -0,0 +1,259 @@
+use std::{
+    ffi::{c_int, c_long, c_void},
+    iter,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use super::{
+    create_component, panic_guard, string_utils::WStr, AppCapabilities,
+    AttachType,
+};
+use crate::interface::TryAddInWrapper;
+
+static APP_CAPABILITIES: AtomicI32 =
+    AtomicI32::new(AppCapabilities::Invalid as c_int);
+
+/// Returns `AppCapabilities`, negotiated with 1C platform in
+/// `SetPlatformCapabilities`, or `Invalid` if it was not called yet
+pub fn app_capabilities() -> AppCapabilities {
+    AppCapabilities::from(APP_CAPABILITIES.load(Ordering::Relaxed))
+}
+
+/// Body of `SetPlatformCapabilities` export, negotiates capabilities
+/// between 1C platform and exported classes. Negotiated value is the
+/// lowest of the levels and can be read with `app_capabilities`
+///
+/// **Hand-written `SetPlatformCapabilities` export must call this
+/// function.** Otherwise negotiated capabilities stay `Invalid` and
+/// `Connection::get_interface` never returns platform interfaces
+/// # Arguments
+/// * `capabilities` - `AppCapabilities` level, provided by 1C platform
+/// * `supported` - capabilities, supported by exported classes
+/// # Returns
+/// `AppCapabilities` - highest level, supported by all exported classes
+pub fn set_platform_capabilities(
+    capabilities: c_int,
+    supported: &[AppCapabilities],
+) -> AppCapabilities {
+    let supported = supported
+        .iter()
+        .copied()
+        .min()
+        .unwrap_or(AppCapabilities::LAST);
+    let negotiated = AppCapabilities::from(capabilities).min(supported);
+    APP_CAPABILITIES.store(negotiated as c_int, Ordering::Relaxed);
+    supported
+}
+
+/// Body of `GetAttachType` export, combines attach types, supported
+/// by all exported classes. If classes require different schemes, `Any`
+/// is returned, and each object refuses to initialize, when attached by
+/// unsupported scheme
+/// # Arguments
+/// * `attach_types` - attach types, supported by exported classes
+/// # Returns
+/// `AttachType` - scheme of attaching supported by the library
+pub fn attach_type(attach_types: &[AttachType]) -> AttachType {
+    let mut combined = AttachType::Any;
+    for &attach_type in attach_types {
+        combined = match (combined, attach_type) {
+            (AttachType::Any, attach_type) => attach_type,
+            (combined, AttachType::Any) => combined,
+            (combined, attach_type) if combined == attach_type => combined,
+            _ => return AttachType::Any,
+        };
+    }
+    combined
+}
+
+/// Builds value returned by `GetClassNames` export
+/// # Arguments
+/// * `class_names` - names of classes exported by the library
+/// # Returns
+/// `Vec<u16>` - `|`-separated class names in UTF-16 with null terminator
+pub fn class_names(class_names: &[&str]) -> Vec<u16> {
+    class_names
+        .join("|")
+        .encode_utf16()
+        .chain(iter::once(0))
+        .collect()
+}
+
+/// Checks if name, requested by 1C platform in `GetClassObject`, matches
+/// the name of the exported class
+/// # Arguments
+/// * `name` - pointer to null terminated UTF-16 string
+/// * `class_name` - name of the exported class
+/// # Returns
+/// `bool` - if names are equal
+/// # Safety
+/// `name` must be either null or a valid null terminated UTF-16 string
+pub unsafe fn is_class_name(name: *const u16, class_name: &str) -> bool {
+    if name.is_null() {
+        return false;
+    }
+    let name = WStr::from_ptr(name);
+    name.as_slice()
+        .iter()
+        .copied()
+        .eq(class_name.encode_utf16())
+}
+
+/// Body of `GetClassObject` export for a single class
+/// # Arguments
+/// * `component` - pointer to the place where pointer to the component
+///   object should be written
+/// # Returns
+/// `c_long` - non-zero value if the component object was created
+/// # Safety
+/// `component` must be either null or a valid pointer, provided by 1C platform
+pub unsafe fn get_class_object<T: TryAddInWrapper + Default>(
+    component: *mut *mut c_void,
+) -> c_long {
+    if component.is_null() || !(*component).is_null() {
+        return 0;
+    }
+    panic_guard::guard(None, 0, || create_component(component, T::default()))
+}
+
+/// Generates functions, exported from the library and called by 1C platform:
+/// `GetClassObject`, `DestroyObject`, `GetClassNames`, `GetAttachType` and
+/// `SetPlatformCapabilities`
+///
+/// Takes a list of class names and `AddInWrapper` or `TryAddInWrapper` types,
+/// that implement `Default`. `GetClassNames` returns class names separated
+/// by `|` and `GetClassObject` creates an object of the type, whose class
+/// name was requested by 1C platform.
+///
+/// ```ignore
+/// native_api_1c_core::export_add_ins! {
+///     "Printer" => PrinterAddIn,
+///     "Scanner" => ScannerAddIn,
+/// }
+/// ```
+#[macro_export]
+macro_rules! export_add_ins {
+    ($($class_name:literal => $add_in:ty),+ $(,)?) => {
+        #[no_mangle]
+        #[allow(non_snake_case, clippy::missing_safety_doc)]
+        pub unsafe extern "C" fn GetClassObject(
+            name: *const u16,
+            component: *mut *mut ::std::ffi::c_void,
+        ) -> ::std::ffi::c_long {
+            $(
+                if $crate::ffi::exports::is_class_name(name, $class_name) {
+                    return $crate::ffi::exports::get_class_object::<$add_in>(
+                        component,
+                    );
+                }
+            )+
+            0
+        }
+
+        #[no_mangle]
+        #[allow(non_snake_case, clippy::missing_safety_doc)]
+        pub unsafe extern "C" fn DestroyObject(
+            component: *mut *mut ::std::ffi::c_void,
+        ) -> ::std::ffi::c_long {
+            if component.is_null() || (*component).is_null() {
+                return -1;
+            }
+            $crate::ffi::destroy_component(component)
+        }
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        pub extern "C" fn GetClassNames() -> *const u16 {
+            static CLASS_NAMES: ::std::sync::OnceLock<::std::vec::Vec<u16>> =
+                ::std::sync::OnceLock::new();
+            CLASS_NAMES
+                .get_or_init(|| {
+                    $crate::ffi::exports::class_names(&[$($class_name),+])
+                })
+                .as_ptr()
+        }
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        pub extern "C" fn GetAttachType() -> $crate::ffi::AttachType {
+            $crate::ffi::exports::attach_type(&[$(
+                <$add_in as $crate::interface::TryAddInWrapper>::attach_type()
+            ),+])
+        }
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        pub extern "C" fn SetPlatformCapabilities(
+            capabilities: ::std::ffi::c_int,
+        ) -> $crate::ffi::AppCapabilities {
+            $crate::ffi::exports::set_platform_capabilities(capabilities, &[$(
+                <$add_in as $crate::interface::TryAddInWrapper>::supported_capabilities()
+            ),+])
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::c_int, ptr};
+
+    use super::{
+        app_capabilities, attach_type, class_names, is_class_name,
+        set_platform_capabilities,
+    };
+    use crate::ffi::{AppCapabilities, AttachType};
+
+    #[test]
+    fn attach_types_are_combined() {
+        use AttachType::{Any, Isolated, NotIsolated};
+
+        assert_eq!(attach_type(&[]), Any);
+        assert_eq!(attach_type(&[Any, Any]), Any);
+        assert_eq!(attach_type(&[Any, Isolated]), Isolated);
+        assert_eq!(attach_type(&[NotIsolated, Any]), NotIsolated);
+        assert_eq!(attach_type(&[Isolated, Isolated, Any]), Isolated);
+        assert_eq!(attach_type(&[NotIsolated, NotIsolated]), NotIsolated);
+        assert_eq!(attach_type(&[Isolated, NotIsolated]), Any);
+        assert_eq!(attach_type(&[NotIsolated, Any, Isolated]), Any);
+    }
+
+    #[test]
+    fn class_names_are_matched() {
+        let name = |s: &str| s.encode_utf16().chain([0]).collect::<Vec<_>>();
+        unsafe {
+            assert!(is_class_name(name("Printer").as_ptr(), "Printer"));
+            assert!(is_class_name(name("Принтер").as_ptr(), "Принтер"));
+            assert!(is_class_name(name("").as_ptr(), ""));
+            assert!(!is_class_name(name("printer").as_ptr(), "Printer"));
+            assert!(!is_class_name(name("Print").as_ptr(), "Printer"));
+            assert!(!is_class_name(name("Printers").as_ptr(), "Printer"));
+            assert!(!is_class_name(ptr::null(), "Printer"));
+        }
+        assert_eq!(class_names(&["A", "Б"]), name("A|Б"));
+    }
+
+    #[test]
+    fn capabilities_are_negotiated() {
+        assert_eq!(AppCapabilities::from(0), AppCapabilities::Invalid);
+        assert_eq!(AppCapabilities::from(-1), AppCapabilities::Invalid);
+        assert_eq!(AppCapabilities::from(7), AppCapabilities::LAST);
+
+        // Levels below 1 would break interfaces in tests, running in parallel
+        let supported = set_platform_capabilities(
+            AppCapabilities::LAST as c_int,
+            &[AppCapabilities::LAST, AppCapabilities::Capabilities1],
+        );
+        assert_eq!(supported, AppCapabilities::Capabilities1);
+        assert_eq!(app_capabilities(), AppCapabilities::Capabilities1);
+
+        let supported = set_platform_capabilities(
+            AppCapabilities::Capabilities2 as c_int,
+            &[],
+        );
+        assert_eq!(supported, AppCapabilities::LAST);
+        assert_eq!(app_capabilities(), AppCapabilities::Capabilities2);
+
+        set_platform_capabilities(AppCapabilities::LAST as c_int, &[]);
+        assert_eq!(app_capabilities(), AppCapabilities::LAST);
+    }
+}
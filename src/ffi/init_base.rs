@@ -1,10 +1,15 @@
 use std::ffi::c_long;
 
-use super::{connection::Connection, memory_manager::MemoryManager, This};
-use crate::interface::AddInWrapper;
+use super::{
+    connection::{self, Connection, MessageCode},
+    event_sender,
+    memory_manager::MemoryManager,
+    This,
+};
+use crate::interface::TryAddInWrapper;
 
 #[repr(C)]
-pub struct InitDoneBaseVTable<T: AddInWrapper> {
+pub struct InitDoneBaseVTable<T: TryAddInWrapper> {
     dtor: usize,
     #[cfg(target_family = "unix")]
     dtor2: usize,
@@ -18,36 +23,69 @@ pub struct InitDoneBaseVTable<T: AddInWrapper> {
     done: unsafe extern "system" fn(&mut This<0, T>),
 }
 
-unsafe extern "system" fn init<T: AddInWrapper>(
+unsafe extern "system" fn init<T: TryAddInWrapper>(
     this: &mut This<0, T>,
     interface: &'static Connection,
 ) -> bool {
     let component = this.get_component();
-    component.addin.init(interface)
+    component.connection = Some(interface);
+    component.guard(false, |component| {
+        if let Some(attached_info) = interface.attached_info() {
+            let attached = attached_info.get_attached_info();
+            if !T::attach_type().allows(attached) {
+                interface.add_error(
+                    MessageCode::Fail,
+                    "",
+                    &format!("AddIn can not be attached as {attached:?}"),
+                );
+                return false;
+            }
+        }
+        if let Some(memory) = component.memory {
+            connection::register_memory_manager(interface, memory);
+        }
+        match component.addin.init(interface) {
+            Ok(()) => {
+                #[cfg(feature = "log")]
+                crate::logger::attach(interface);
+                true
+            }
+            Err(err) => {
+                component.report(&err);
+                false
+            }
+        }
+    })
 }
 
-unsafe extern "system" fn set_mem_manager<T: AddInWrapper>(
+unsafe extern "system" fn set_mem_manager<T: TryAddInWrapper>(
     this: &mut This<0, T>,
     mem: &'static MemoryManager,
 ) -> bool {
     let component = this.get_component();
-    component.memory = Some(mem);
-    true
+    component.guard(false, |component| {
+        component.memory = Some(mem);
+        true
+    })
 }
 
-unsafe extern "system" fn get_info<T: AddInWrapper>(
+unsafe extern "system" fn get_info<T: TryAddInWrapper>(
     this: &mut This<0, T>,
 ) -> c_long {
     let component = this.get_component();
-    component.addin.get_info() as c_long
+    component.guard(0, |component| component.addin.get_info() as c_long)
 }
 
-unsafe extern "system" fn done<T: AddInWrapper>(this: &mut This<0, T>) {
+unsafe extern "system" fn done<T: TryAddInWrapper>(this: &mut This<0, T>) {
     let component = this.get_component();
-    component.addin.done()
+    if let Some(connection) = component.connection {
+        event_sender::begin_close(connection);
+    }
+    component.guard((), |component| component.addin.done());
+    component.release_connection();
 }
 
-impl<T: AddInWrapper> Default for InitDoneBaseVTable<T> {
+impl<T: TryAddInWrapper> Default for InitDoneBaseVTable<T> {
     fn default() -> Self {
         Self {
             dtor: 0,
@@ -60,3 +98,141 @@ impl<T: AddInWrapper> Default for InitDoneBaseVTable<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{
+        ffi::{
+            attached_info::AttachedType,
+            connection::{Connection, MessageCode},
+            provided_types::{ParamValue, Params, ReturnValue},
+            AttachType,
+        },
+        interface::{AddInResult, TryAddInWrapper},
+        testing::TestHost,
+    };
+
+    /// AddIn, that can only be loaded into 1C platform process
+    struct InProcess {
+        initialized: Rc<Cell<bool>>,
+    }
+
+    impl TryAddInWrapper for InProcess {
+        fn init(&mut self, _: &'static Connection) -> AddInResult {
+            self.initialized.set(true);
+            Ok(())
+        }
+
+        fn attach_type() -> AttachType {
+            AttachType::NotIsolated
+        }
+
+        fn done(&mut self) {}
+
+        fn register_extension_as(&mut self) -> &[u16] {
+            &utf16_lit::utf16_null!("InProcess")
+        }
+
+        fn get_n_props(&self) -> usize {
+            0
+        }
+
+        fn find_prop(&self, _: &[u16]) -> Option<usize> {
+            None
+        }
+
+        fn get_prop_name(&self, _: usize, _: usize) -> Option<Vec<u16>> {
+            None
+        }
+
+        fn get_prop_val(&self, _: usize, _: ReturnValue) -> AddInResult {
+            Ok(())
+        }
+
+        fn set_prop_val(&mut self, _: usize, _: &ParamValue) -> AddInResult {
+            Ok(())
+        }
+
+        fn is_prop_readable(&self, _: usize) -> bool {
+            false
+        }
+
+        fn is_prop_writable(&self, _: usize) -> bool {
+            false
+        }
+
+        fn get_n_methods(&self) -> usize {
+            0
+        }
+
+        fn find_method(&self, _: &[u16]) -> Option<usize> {
+            None
+        }
+
+        fn get_method_name(&self, _: usize, _: usize) -> Option<Vec<u16>> {
+            None
+        }
+
+        fn get_n_params(&self, _: usize) -> usize {
+            0
+        }
+
+        fn get_param_def_value(
+            &self,
+            _: usize,
+            _: usize,
+            _: ReturnValue,
+        ) -> bool {
+            false
+        }
+
+        fn has_ret_val(&self, _: usize) -> bool {
+            false
+        }
+
+        fn call_as_proc(&mut self, _: usize, _: &mut Params) -> AddInResult {
+            Ok(())
+        }
+
+        fn call_as_func(
+            &mut self,
+            _: usize,
+            _: &mut Params,
+            _: ReturnValue,
+        ) -> AddInResult {
+            Ok(())
+        }
+
+        fn set_locale(&mut self, _: &[u16]) {}
+
+        fn set_user_interface_language_code(&mut self, _: &[u16]) {}
+    }
+
+    #[test]
+    fn unsupported_attach_type_is_refused() {
+        let initialized = Rc::new(Cell::new(false));
+        let mut host = TestHost::new(InProcess {
+            initialized: initialized.clone(),
+        });
+        host.connection().set_attached_type(AttachedType::Isolated);
+        assert!(!host.init());
+        assert!(!initialized.get());
+
+        let errors = host.connection().errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, MessageCode::Fail as u16);
+        assert_eq!(
+            errors[0].description,
+            "AddIn can not be attached as Isolated"
+        );
+
+        let mut host = TestHost::new(InProcess {
+            initialized: initialized.clone(),
+        });
+        assert!(host.init());
+        assert!(initialized.get());
+        assert!(host.connection().errors().is_empty());
+    }
+}
@@ -4,16 +4,19 @@ use std::{
     slice::from_raw_parts_mut,
 };
 
-use crate::interface::AddInWrapper;
+use crate::interface::TryAddInWrapper;
 
 use super::{
-    get_str,
-    provided_types::{ParamValue, ReturnValue, TVariant},
+    call_trace::{self, Span},
+    memory_manager::PlatformStr,
+    panic_guard,
+    provided_types::{ParamRef, ParamValue, Params, ReturnValue, TVariant},
+    string_utils::WStr,
     This,
 };
 
 #[repr(C)]
-pub struct LanguageExtenderBaseVTable<T: AddInWrapper> {
+pub struct LanguageExtenderBaseVTable<T: TryAddInWrapper> {
     dtor: usize,
     #[cfg(target_family = "unix")]
     dtor2: usize,
@@ -68,249 +71,358 @@ pub struct LanguageExtenderBaseVTable<T: AddInWrapper> {
     ) -> bool,
 }
 
-unsafe extern "system" fn register_extension_as<T: AddInWrapper>(
+/// Names, returned by the AddIn, may already end with NUL terminator, which
+/// is added again by `PlatformStr`
+fn trim_nul(name: &[u16]) -> &[u16] {
+    name.strip_suffix(&[0]).unwrap_or(name)
+}
+
+/// Starts span of a call, that refers to the property with the given index
+fn prop_span<T: TryAddInWrapper>(
+    entry: &'static str,
+    addin: &T,
+    num: c_long,
+) -> Option<Span> {
+    call_trace::begin(entry).map(|span| {
+        let name = panic_guard::catch(|| addin.get_prop_name(num as usize, 1));
+        span.member(num, name.ok().flatten().as_deref().map(trim_nul))
+    })
+}
+
+/// Starts span of a call, that refers to the method with the given index
+fn method_span<T: TryAddInWrapper>(
+    entry: &'static str,
+    addin: &T,
+    num: c_long,
+) -> Option<Span> {
+    call_trace::begin(entry).map(|span| {
+        let name =
+            panic_guard::catch(|| addin.get_method_name(num as usize, 1));
+        span.member(num, name.ok().flatten().as_deref().map(trim_nul))
+    })
+}
+
+unsafe extern "system" fn register_extension_as<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     name: *mut *mut u16,
 ) -> bool {
     let component = this.get_component();
-    let Some(allocator) = component.memory else {
-        return false;
-    };
-
-    let extension_name = component.addin.register_extension_as();
-
-    let Ok(ptr) = allocator.alloc_str(extension_name.len()) else {
-        return false;
-    };
-    ptr::copy_nonoverlapping(
-        extension_name.as_ptr(),
-        ptr.as_ptr(),
-        extension_name.len(),
-    );
-    *name = ptr.as_ptr();
-
-    true
+    let span = call_trace::begin("RegisterExtensionAs");
+    let result = component.guard(false, |component| {
+        let Some(allocator) = component.memory else {
+            return false;
+        };
+
+        let extension_name = component.addin.register_extension_as();
+
+        let Ok(extension_name) =
+            PlatformStr::new(allocator, trim_nul(extension_name))
+        else {
+            return false;
+        };
+        *name = extension_name.into_raw();
+
+        true
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn get_n_props<T: AddInWrapper>(
+unsafe extern "system" fn get_n_props<T: TryAddInWrapper>(
     this: &mut This<1, T>,
 ) -> c_long {
     let component = this.get_component();
-    component.addin.get_n_props() as c_long
+    let span = call_trace::begin("GetNProps");
+    let result =
+        component.guard(0, |component| component.addin.get_n_props() as c_long);
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn find_prop<T: AddInWrapper>(
+unsafe extern "system" fn find_prop<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     name: *const u16,
 ) -> c_long {
     let component = this.get_component();
-    let name = get_str(name);
-    match component.addin.find_prop(name) {
-        Some(i) => i as c_long,
-        None => -1,
-    }
+    let span = call_trace::begin("FindProp")
+        .map(|span| span.name(WStr::from_ptr(name).as_slice()));
+    let result = component.guard(-1, |component| {
+        let name = WStr::from_ptr(name).as_slice();
+        match component.addin.find_prop(name) {
+            Some(i) => i as c_long,
+            None => -1,
+        }
+    });
+    call_trace::end(span.map(|span| span.found(result)), &result, None);
+    result
 }
 
-unsafe extern "system" fn get_prop_name<T: AddInWrapper>(
+unsafe extern "system" fn get_prop_name<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     num: c_long,
     alias: c_long,
 ) -> *const u16 {
     let component = this.get_component();
-    let Some(allocator) = component.memory else {
-        return ptr::null();
-    };
-    let Some(prop_name) = component.addin.get_prop_name(num as usize, alias as usize) else {
-        return ptr::null();
-    };
-    let Ok(ptr) = allocator.alloc_str(prop_name.len()) else {
-        return ptr::null();
-    };
-    ptr::copy_nonoverlapping(prop_name.as_ptr(), ptr.as_ptr(), prop_name.len());
-
-    ptr.as_ptr()
+    let span = prop_span("GetPropName", &component.addin, num);
+    let result = component.guard(ptr::null(), |component| {
+        let Some(allocator) = component.memory else {
+            return ptr::null();
+        };
+        let Some(prop_name) =
+            component.addin.get_prop_name(num as usize, alias as usize)
+        else {
+            return ptr::null();
+        };
+        let Ok(prop_name) = PlatformStr::new(allocator, trim_nul(&prop_name))
+        else {
+            return ptr::null();
+        };
+
+        prop_name.into_raw()
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn get_prop_val<T: AddInWrapper>(
+unsafe extern "system" fn get_prop_val<T: TryAddInWrapper>(
     component: &mut This<1, T>,
     num: c_long,
     val: &mut TVariant,
 ) -> bool {
     let component = component.get_component();
-    let Some(mem) = component.memory else {
-        return false;
-    };
-
-    let mut result = true;
-    let return_value = ReturnValue {
-        mem,
-        variant: val,
-        result: &mut result,
-    };
-    component.addin.get_prop_val(num as usize, return_value) && result
+    let span = prop_span("GetPropVal", &component.addin, num);
+    let result = component.guard(false, |component| {
+        let Some(mem) = component.memory else {
+            return false;
+        };
+
+        let mut result = true;
+        let return_value = ReturnValue {
+            mem,
+            variant: val,
+            result: &mut result,
+        };
+        match component.addin.get_prop_val(num as usize, return_value) {
+            Ok(()) => result,
+            Err(err) => {
+                component.report(&err);
+                false
+            }
+        }
+    });
+    call_trace::end(span, &result, Some(val));
+    result
 }
 
-unsafe extern "system" fn set_prop_val<T: AddInWrapper>(
+unsafe extern "system" fn set_prop_val<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     num: c_long,
     val: &TVariant,
 ) -> bool {
     let component = this.get_component();
-    let param = ParamValue::from(val);
-    component.addin.set_prop_val(num as usize, &param)
+    let span = prop_span("SetPropVal", &component.addin, num)
+        .map(|span| span.params([ParamRef::from(val)]));
+    let result = component.guard(false, |component| {
+        let param = ParamValue::from(val);
+        match component.addin.set_prop_val(num as usize, &param) {
+            Ok(()) => true,
+            Err(err) => {
+                component.report(&err);
+                false
+            }
+        }
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn is_prop_readable<T: AddInWrapper>(
+unsafe extern "system" fn is_prop_readable<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     num: c_long,
 ) -> bool {
     let component = this.get_component();
-    component.addin.is_prop_readable(num as usize)
+    let span = prop_span("IsPropReadable", &component.addin, num);
+    let result = component.guard(false, |component| {
+        component.addin.is_prop_readable(num as usize)
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn is_prop_writable<T: AddInWrapper>(
+unsafe extern "system" fn is_prop_writable<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     num: c_long,
 ) -> bool {
     let component = this.get_component();
-    component.addin.is_prop_writable(num as usize)
+    let span = prop_span("IsPropWritable", &component.addin, num);
+    let result = component.guard(false, |component| {
+        component.addin.is_prop_writable(num as usize)
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn get_n_methods<T: AddInWrapper>(
+unsafe extern "system" fn get_n_methods<T: TryAddInWrapper>(
     this: &mut This<1, T>,
 ) -> c_long {
     let component = this.get_component();
-    component.addin.get_n_methods() as c_long
+    let span = call_trace::begin("GetNMethods");
+    let result = component
+        .guard(0, |component| component.addin.get_n_methods() as c_long);
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn find_method<T: AddInWrapper>(
+unsafe extern "system" fn find_method<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     name: *const u16,
 ) -> c_long {
     let component = this.get_component();
-    let name = get_str(name);
-    match component.addin.find_method(name) {
-        Some(i) => i as c_long,
-        None => -1,
-    }
+    let span = call_trace::begin("FindMethod")
+        .map(|span| span.name(WStr::from_ptr(name).as_slice()));
+    let result = component.guard(-1, |component| {
+        let name = WStr::from_ptr(name).as_slice();
+        match component.addin.find_method(name) {
+            Some(i) => i as c_long,
+            None => -1,
+        }
+    });
+    call_trace::end(span.map(|span| span.found(result)), &result, None);
+    result
 }
 
-unsafe extern "system" fn get_method_name<T: AddInWrapper>(
+unsafe extern "system" fn get_method_name<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     num: c_long,
     alias: c_long,
 ) -> *const u16 {
     let component = this.get_component();
-    let Some(allocator) = component.memory else {
-        return ptr::null();
-    };
-    let Some(method_name) = component.addin.get_method_name(num as usize, alias as usize) else {
-        return ptr::null();
-    };
-    let Ok(ptr) = allocator.alloc_str(method_name.len()) else {
-        return ptr::null();
-    };
-
-    ptr::copy_nonoverlapping(
-        method_name.as_ptr(),
-        ptr.as_ptr(),
-        method_name.len(),
-    );
-
-    ptr.as_ptr()
+    let span = method_span("GetMethodName", &component.addin, num);
+    let result = component.guard(ptr::null(), |component| {
+        let Some(allocator) = component.memory else {
+            return ptr::null();
+        };
+        let Some(method_name) = component
+            .addin
+            .get_method_name(num as usize, alias as usize)
+        else {
+            return ptr::null();
+        };
+        let Ok(method_name) =
+            PlatformStr::new(allocator, trim_nul(&method_name))
+        else {
+            return ptr::null();
+        };
+
+        method_name.into_raw()
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn get_n_params<T: AddInWrapper>(
+unsafe extern "system" fn get_n_params<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     num: c_long,
 ) -> c_long {
     let component = this.get_component();
-    component.addin.get_n_params(num as usize) as c_long
+    let span = method_span("GetNParams", &component.addin, num);
+    let result = component.guard(0, |component| {
+        component.addin.get_n_params(num as usize) as c_long
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn get_param_def_value<T: AddInWrapper>(
+unsafe extern "system" fn get_param_def_value<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     method_num: c_long,
     param_num: c_long,
     val: &mut TVariant,
 ) -> bool {
     let component = this.get_component();
-    let Some(mem) = component.memory else {
-        return false;
-    };
-
-    let mut result = true;
-    let return_value = ReturnValue {
-        mem,
-        variant: val,
-        result: &mut result,
-    };
-
-    component.addin.get_param_def_value(
-        method_num as usize,
-        param_num as usize,
-        return_value,
-    ) && result
+    let span = method_span("GetParamDefValue", &component.addin, method_num);
+    let result = component.guard(false, |component| {
+        let Some(mem) = component.memory else {
+            return false;
+        };
+
+        let mut result = true;
+        let return_value = ReturnValue {
+            mem,
+            variant: val,
+            result: &mut result,
+        };
+
+        component.addin.get_param_def_value(
+            method_num as usize,
+            param_num as usize,
+            return_value,
+        ) && result
+    });
+    call_trace::end(span, &result, Some(val));
+    result
 }
 
-unsafe extern "system" fn has_ret_val<T: AddInWrapper>(
+unsafe extern "system" fn has_ret_val<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     method_num: c_long,
 ) -> bool {
     let component = this.get_component();
-    component.addin.has_ret_val(method_num as usize)
+    let span = method_span("HasRetVal", &component.addin, method_num);
+    let result = component.guard(false, |component| {
+        component.addin.has_ret_val(method_num as usize)
+    });
+    call_trace::end(span, &result, None);
+    result
+}
+
+/// Parameters array, passed by 1C platform, which may be null if there
+/// are no parameters
+unsafe fn platform_params<'a>(
+    params: *mut TVariant,
+    size_array: c_long,
+) -> &'a mut [TVariant] {
+    match params.is_null() || size_array <= 0 {
+        true => &mut [],
+        false => from_raw_parts_mut(params, size_array as usize),
+    }
 }
 
-unsafe extern "system" fn call_as_proc<T: AddInWrapper>(
+unsafe extern "system" fn call_as_proc<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     method_num: c_long,
     params: *mut TVariant,
     size_array: c_long,
 ) -> bool {
     let component = this.get_component();
-    let Some(mem_mngr) = component.memory else { return false; };
-
-    let parameters_raw = from_raw_parts_mut(params, size_array as usize);
-    let mut parameters_values = parameters_raw
-        .iter()
-        .map(ParamValue::from)
-        .collect::<Vec<ParamValue>>();
-    let parameters_values_buf = parameters_values.clone();
-
-    let call_result = component
-        .addin
-        .call_as_proc(method_num as usize, &mut parameters_values);
-    if !call_result {
-        return false;
-    }
-    if parameters_values.len() != parameters_values_buf.len() {
-        return false;
-    }
-
-    for i in 0..parameters_values.len() {
-        let raw_param = &mut parameters_raw[i];
-        if parameters_values_buf[i] == parameters_values[i] {
-            continue;
-        }
-        match &parameters_values[i] {
-            ParamValue::Str(v) => {
-                let Ok(_) = raw_param.update_to_str(mem_mngr, v) else { return false; };
-            }
-            ParamValue::Blob(v) => {
-                let Ok(_) = raw_param.update_to_blob(mem_mngr, v) else { return false; };
-            }
-            ParamValue::Bool(v) => raw_param.update_to_bool(*v),
-            ParamValue::I32(v) => raw_param.update_to_i32(*v),
-            ParamValue::F64(v) => raw_param.update_to_f64(*v),
-            ParamValue::Date(v) => raw_param.update_to_date(*v),
-            ParamValue::Empty => {}
+    let span =
+        method_span("CallAsProc", &component.addin, method_num).map(|span| {
+            let variants = platform_params(params, size_array);
+            span.params(variants.iter().map(ParamRef::from))
+        });
+    let result = component.guard(false, |component| {
+        let Some(mem_mngr) = component.memory else {
+            return false;
+        };
+
+        let variants = platform_params(params, size_array);
+        let mut params = Params::from_platform(mem_mngr, variants);
+
+        let call_result = component
+            .addin
+            .call_as_proc(method_num as usize, &mut params);
+        if let Err(err) = call_result {
+            component.report(&err);
+            return false;
         }
-    }
 
-    true
+        true
+    });
+    call_trace::end(span, &result, None);
+    result
 }
 
-unsafe extern "system" fn call_as_func<T: AddInWrapper>(
+unsafe extern "system" fn call_as_func<T: TryAddInWrapper>(
     this: &mut This<1, T>,
     method_num: c_long,
     ret_value: &mut TVariant,
@@ -318,61 +430,43 @@ unsafe extern "system" fn call_as_func<T: AddInWrapper>(
     size_array: c_long,
 ) -> bool {
     let component = this.get_component();
-    let Some(mem_mngr) = component.memory else { return false; };
-
-    let mut result = true;
-    let return_value = ReturnValue {
-        mem: mem_mngr,
-        variant: ret_value,
-        result: &mut result,
-    };
-
-    let parameters_raw = from_raw_parts_mut(params, size_array as usize);
-    let mut parameters_values = parameters_raw
-        .iter()
-        .map(ParamValue::from)
-        .collect::<Vec<ParamValue>>();
-    let parameters_values_buf = parameters_values.clone();
-
-    let call_result = component.addin.call_as_func(
-        method_num as usize,
-        &mut parameters_values,
-        return_value,
-    );
-    if !call_result {
-        return false;
-    }
-    if !result {
-        return false;
-    }
-    if parameters_values.len() != parameters_values_buf.len() {
-        return false;
-    }
-
-    for i in 0..parameters_values.len() {
-        let raw_param = &mut parameters_raw[i];
-        if parameters_values_buf[i] == parameters_values[i] {
-            continue;
-        }
-        match &parameters_values[i] {
-            ParamValue::Str(v) => {
-                let Ok(_) = raw_param.update_to_str(mem_mngr, v) else { return false; };
-            }
-            ParamValue::Blob(v) => {
-                let Ok(_) = raw_param.update_to_blob(mem_mngr, v) else { return false; };
-            }
-            ParamValue::Bool(v) => raw_param.update_to_bool(*v),
-            ParamValue::I32(v) => raw_param.update_to_i32(*v),
-            ParamValue::F64(v) => raw_param.update_to_f64(*v),
-            ParamValue::Date(v) => raw_param.update_to_date(*v),
-            ParamValue::Empty => {}
+    let span =
+        method_span("CallAsFunc", &component.addin, method_num).map(|span| {
+            let variants = platform_params(params, size_array);
+            span.params(variants.iter().map(ParamRef::from))
+        });
+    let result = component.guard(false, |component| {
+        let Some(mem_mngr) = component.memory else {
+            return false;
+        };
+
+        let mut result = true;
+        let return_value = ReturnValue {
+            mem: mem_mngr,
+            variant: ret_value,
+            result: &mut result,
+        };
+
+        let variants = platform_params(params, size_array);
+        let mut params = Params::from_platform(mem_mngr, variants);
+
+        let call_result = component.addin.call_as_func(
+            method_num as usize,
+            &mut params,
+            return_value,
+        );
+        if let Err(err) = call_result {
+            component.report(&err);
+            return false;
         }
-    }
 
-    true
+        result
+    });
+    call_trace::end(span, &result, Some(ret_value));
+    result
 }
 
-impl<T: AddInWrapper> Default for LanguageExtenderBaseVTable<T> {
+impl<T: TryAddInWrapper> Default for LanguageExtenderBaseVTable<T> {
     fn default() -> Self {
         Self {
             dtor: 0,
@@ -397,3 +491,22 @@ impl<T: AddInWrapper> Default for LanguageExtenderBaseVTable<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::platform_params;
+    use crate::ffi::provided_types::TVariant;
+
+    #[test]
+    fn null_or_empty_params_array() {
+        let mut variant = TVariant::default();
+        unsafe {
+            assert!(platform_params(ptr::null_mut(), 2).is_empty());
+            assert!(platform_params(&mut variant, 0).is_empty());
+            assert!(platform_params(&mut variant, -1).is_empty());
+            assert_eq!(platform_params(&mut variant, 1).len(), 1);
+        }
+    }
+}
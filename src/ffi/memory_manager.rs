@@ -1,5 +1,6 @@
 use std::{
     ffi::{c_ulong, c_void},
+    fmt,
     ptr::{self, NonNull},
 };
 
@@ -25,8 +26,18 @@ pub struct MemoryManager {
     vptr: &'static MemoryManagerVTable,
 }
 
+/// Error of allocating memory with MemoryManager
+#[derive(Debug)]
 pub struct AllocationError;
 
+impl fmt::Display for AllocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to allocate memory with MemoryManager")
+    }
+}
+
+impl std::error::Error for AllocationError {}
+
 impl MemoryManager {
     /// Safe wrapper around `alloc_memory` method of the MemoryManager object
     /// to allocate memory for byte array
@@ -40,7 +51,7 @@ impl MemoryManager {
     ) -> Result<NonNull<u8>, AllocationError> {
         let mut ptr = ptr::null_mut::<c_void>();
         unsafe {
-            if (self.vptr.alloc_memory)(self, &mut ptr, size as c_ulong * 2) {
+            if (self.vptr.alloc_memory)(self, &mut ptr, size as c_ulong) {
                 match NonNull::new(ptr as *mut u8) {
                     Some(ptr) => Ok(ptr),
                     None => Err(AllocationError),
@@ -80,3 +91,145 @@ impl MemoryManager {
         }
     }
 }
+
+/// UTF-16 string in memory, allocated by 1C platform `MemoryManager`. Memory
+/// is freed on drop, unless it is handed over to 1C platform with `into_raw`
+pub struct PlatformStr<'a> {
+    mem: &'a MemoryManager,
+    ptr: NonNull<u16>,
+    len: usize,
+}
+
+impl<'a> PlatformStr<'a> {
+    /// Allocates memory and copies the string into it. Null terminator is
+    /// always appended after the copied data
+    /// # Arguments
+    /// * `mem` - memory manager provided by 1C platform
+    /// * `s` - UTF-16 string to copy
+    /// # Returns
+    /// `Result<PlatformStr, AllocationError>` - owned copy of the string
+    pub fn new(
+        mem: &'a MemoryManager,
+        s: &[u16],
+    ) -> Result<Self, AllocationError> {
+        let ptr = mem.alloc_str(s.len() + 1)?;
+        unsafe {
+            ptr::copy_nonoverlapping(s.as_ptr(), ptr.as_ptr(), s.len());
+            *ptr.as_ptr().add(s.len()) = 0;
+        }
+        Ok(Self {
+            mem,
+            ptr,
+            len: s.len(),
+        })
+    }
+
+    /// Returns the length of the string without null terminator
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Hands the memory over to 1C platform, which becomes responsible
+    /// for freeing it
+    /// # Returns
+    /// `*mut u16` - pointer to null terminated string
+    pub fn into_raw(self) -> *mut u16 {
+        let ptr = self.ptr.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+}
+
+impl Drop for PlatformStr<'_> {
+    fn drop(&mut self) {
+        self.mem
+            .free_memory(&mut self.ptr.as_ptr().cast::<c_void>());
+    }
+}
+
+/// Byte array in memory, allocated by 1C platform `MemoryManager`. Memory
+/// is freed on drop, unless it is handed over to 1C platform with `into_raw`
+pub struct PlatformBlob<'a> {
+    mem: &'a MemoryManager,
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl<'a> PlatformBlob<'a> {
+    /// Allocates memory and copies the bytes into it
+    /// # Arguments
+    /// * `mem` - memory manager provided by 1C platform
+    /// * `bytes` - data to copy
+    /// # Returns
+    /// `Result<PlatformBlob, AllocationError>` - owned copy of the data
+    pub fn new(
+        mem: &'a MemoryManager,
+        bytes: &[u8],
+    ) -> Result<Self, AllocationError> {
+        // Zero-sized allocations are not guaranteed to succeed
+        let ptr = mem.alloc_blob(bytes.len().max(1))?;
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.as_ptr(), bytes.len())
+        };
+        Ok(Self {
+            mem,
+            ptr,
+            len: bytes.len(),
+        })
+    }
+
+    /// Allocates memory and copies the bytes into it, appending null
+    /// terminator, that is not included into the length
+    /// # Arguments
+    /// * `mem` - memory manager provided by 1C platform
+    /// * `bytes` - narrow string to copy
+    /// # Returns
+    /// `Result<PlatformBlob, AllocationError>` - owned copy of the string
+    pub fn new_nil(
+        mem: &'a MemoryManager,
+        bytes: &[u8],
+    ) -> Result<Self, AllocationError> {
+        let ptr = mem.alloc_blob(bytes.len() + 1)?;
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.as_ptr(), bytes.len());
+            *ptr.as_ptr().add(bytes.len()) = 0;
+        }
+        Ok(Self {
+            mem,
+            ptr,
+            len: bytes.len(),
+        })
+    }
+
+    /// Returns the length of the data
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the data is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Hands the memory over to 1C platform, which becomes responsible
+    /// for freeing it
+    /// # Returns
+    /// `*mut u8` - pointer to the data
+    pub fn into_raw(self) -> *mut u8 {
+        let ptr = self.ptr.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+}
+
+impl Drop for PlatformBlob<'_> {
+    fn drop(&mut self) {
+        self.mem
+            .free_memory(&mut self.ptr.as_ptr().cast::<c_void>());
+    }
+}
@@ -3,25 +3,47 @@
 //! submodules according to what C++ class they originate from
 //!
 use std::{
-    ffi::{c_long, c_void},
+    ffi::{c_int, c_long, c_void},
     ptr,
 };
 
-use crate::interface::AddInWrapper;
+use crate::interface::{AddInError, TryAddInWrapper};
 
 use self::{
-    init_base::InitDoneBaseVTable, lang_extender::LanguageExtenderBaseVTable,
-    memory_manager::MemoryManager, string_utils::get_str,
+    attached_info::AttachedType,
+    connection::{Connection, MessageCode},
+    init_base::InitDoneBaseVTable,
+    lang_extender::LanguageExtenderBaseVTable,
+    memory_manager::MemoryManager,
+    string_utils::WStr,
 };
 
+/// Implementation of `AttachedInfo` - replacement for `IAttachedInfo`
+pub mod attached_info;
+/// Tracing of calls from 1C platform to the AddIn
+pub mod call_trace;
 /// Implementation of `Connection` - replacement for `IAddInDefBase`
 pub mod connection;
+/// Conversions between Rust types and values, passed to and returned to
+/// 1C platform
+pub mod conversion;
+/// Thread-safe sending of external events to 1C platform
+pub mod event_sender;
+/// Implementation of library exports - replacement for `GetClassObject`,
+/// `DestroyObject` and other functions, exported by the AddIn library
+pub mod exports;
 /// Implementation of `InitDone` - replacement for `IInitDoneBase`
 pub mod init_base;
 /// Implementation of `LanguageExtender` - replacement for `ILanguageExtenderBase`
 pub mod lang_extender;
 /// Implementation of `MemoryManager` - replacement for `IMemoryManager`
 pub mod memory_manager;
+/// Implementation of `MsgBox` - replacement for `IMsgBox`
+pub mod msg_box;
+/// Containment of panics, that would otherwise unwind into 1C platform
+pub mod panic_guard;
+/// Implementation of `PlatformInfo` - replacement for `IPlatformInfo`
+pub mod platform_info;
 /// Implementations of types, provided by Native API for easy of use in Rust
 pub mod provided_types;
 /// Functions to convert between Rust and 1C strings
@@ -29,8 +51,7 @@ pub mod string_utils;
 
 /// Scheme of attaching to 1C platform process
 #[repr(C)]
-#[derive(Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AttachType {
     /// Attach to 1C platform process
     NotIsolated = 1,
@@ -40,13 +61,61 @@ pub enum AttachType {
     Any,
 }
 
+impl AttachType {
+    /// Checks if the scheme, by which the AddIn was actually attached,
+    /// is allowed by this attach type
+    pub fn allows(self, attached: AttachedType) -> bool {
+        matches!(
+            (self, attached),
+            (Self::Any, _)
+                | (Self::NotIsolated, AttachedType::NotIsolated)
+                | (Self::Isolated, AttachedType::Isolated)
+        )
+    }
+}
+
+/// Level of Native API capabilities, supported by 1C platform or the AddIn.
+/// Negotiated by `SetPlatformCapabilities` export before any AddIn object
+/// is created. Capabilities of at least version 1 are needed to use
+/// `Connection::get_interface`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AppCapabilities {
+    /// Capabilities were not negotiated, because `SetPlatformCapabilities`
+    /// did not call `exports::set_platform_capabilities`
+    Invalid = -1,
+    /// Version 1 capabilities
+    Capabilities1 = 1,
+    /// Version 2 capabilities
+    Capabilities2 = 2,
+    /// Version 3 capabilities
+    Capabilities3 = 3,
+}
+
+impl AppCapabilities {
+    /// Highest capabilities level, supported by this crate
+    pub const LAST: Self = Self::Capabilities3;
+}
+
+impl From<c_int> for AppCapabilities {
+    fn from(value: c_int) -> Self {
+        match value {
+            1 => Self::Capabilities1,
+            2 => Self::Capabilities2,
+            // Newer platforms are backward compatible with known levels
+            3.. => Self::LAST,
+            _ => Self::Invalid,
+        }
+    }
+}
+
 #[repr(C)]
-struct This<const OFFSET: usize, T: AddInWrapper> {
+struct This<const OFFSET: usize, T: TryAddInWrapper> {
     ptr: *mut Component<T>,
 }
 
-impl<'a, const OFFSET: usize, T: AddInWrapper> This<OFFSET, T> {
-    unsafe fn get_component(&mut self) -> &'a mut Component<T> {
+impl<const OFFSET: usize, T: TryAddInWrapper> This<OFFSET, T> {
+    unsafe fn get_component(&mut self) -> &mut Component<T> {
         let new_ptr = (self as *mut This<OFFSET, T> as *mut c_void)
             .sub(OFFSET * std::mem::size_of::<usize>());
         &mut *(new_ptr as *mut Component<T>)
@@ -54,24 +123,26 @@ impl<'a, const OFFSET: usize, T: AddInWrapper> This<OFFSET, T> {
 }
 
 #[repr(C)]
-struct LocaleBaseVTable<T: AddInWrapper> {
+struct LocaleBaseVTable<T: TryAddInWrapper> {
     dtor: usize,
     #[cfg(target_family = "unix")]
     dtor2: usize,
     set_locale: unsafe extern "system" fn(&mut This<2, T>, *const u16),
 }
 
-unsafe extern "system" fn set_locale<T: AddInWrapper>(
+unsafe extern "system" fn set_locale<T: TryAddInWrapper>(
     this: &mut This<2, T>,
     loc: *const u16,
 ) {
     let component = this.get_component();
-    let loc = get_str(loc);
-    component.addin.set_locale(loc)
+    component.guard((), |component| {
+        let loc = WStr::from_ptr(loc).as_slice();
+        component.addin.set_locale(loc)
+    })
 }
 
 #[repr(C)]
-struct UserLanguageBaseVTable<T: AddInWrapper> {
+struct UserLanguageBaseVTable<T: TryAddInWrapper> {
     dtor: usize,
     #[cfg(target_family = "unix")]
     dtor2: usize,
@@ -79,34 +150,91 @@ struct UserLanguageBaseVTable<T: AddInWrapper> {
         unsafe extern "system" fn(&mut This<3, T>, *const u16),
 }
 
-unsafe extern "system" fn set_user_interface_language_code<T: AddInWrapper>(
+unsafe extern "system" fn set_user_interface_language_code<
+    T: TryAddInWrapper,
+>(
     this: &mut This<3, T>,
     lang: *const u16,
 ) {
     let component = this.get_component();
-    let lang = get_str(lang);
-    component.addin.set_user_interface_language_code(lang)
+    component.guard((), |component| {
+        let lang = WStr::from_ptr(lang).as_slice();
+        component.addin.set_user_interface_language_code(lang)
+    })
 }
 
 #[repr(C)]
-struct Component<T: AddInWrapper> {
+struct Component<T: TryAddInWrapper> {
     vptr1: Box<InitDoneBaseVTable<T>>,
     vptr2: Box<LanguageExtenderBaseVTable<T>>,
     vptr3: Box<LocaleBaseVTable<T>>,
     vptr4: Box<UserLanguageBaseVTable<T>>,
     destroy: unsafe extern "system" fn(*mut *mut Component<T>),
     memory: Option<&'static MemoryManager>,
+    connection: Option<&'static Connection>,
     addin: T,
 }
 
-unsafe extern "system" fn destroy<T: AddInWrapper>(
+impl<T: TryAddInWrapper> Component<T> {
+    /// Calls `f` with the component, catching panics, so they do not unwind
+    /// into 1C platform. See `panic_guard::guard`
+    fn guard<R>(&mut self, default: R, f: impl FnOnce(&mut Self) -> R) -> R {
+        let connection = self.connection;
+        panic_guard::guard(connection, default, || f(self))
+    }
+
+    /// Reports the error to 1C platform with `MessageCode::Fail`, so it
+    /// becomes the description of the exception, raised by 1C platform
+    fn report(&self, err: &AddInError) {
+        if err.description.is_empty() {
+            return;
+        }
+        call_trace::note_error(err);
+        if let Some(connection) = self.connection {
+            connection.add_error(
+                MessageCode::Fail,
+                &err.source,
+                &err.description,
+            );
+        }
+    }
+
+    /// Stops using the connection to 1C platform, called after `done` and
+    /// when the component is destroyed without `done`, for example after
+    /// failed `init`, so no dangling connection is left in global state
+    fn release_connection(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            event_sender::close(connection);
+            connection::unregister_memory_manager(connection);
+            #[cfg(feature = "log")]
+            crate::logger::detach(connection);
+        }
+    }
+}
+
+impl<T: TryAddInWrapper> Drop for Component<T> {
+    fn drop(&mut self) {
+        self.release_connection();
+    }
+}
+
+unsafe extern "system" fn destroy<T: TryAddInWrapper>(
     component: *mut *mut Component<T>,
 ) {
     let comp = Box::from_raw(*component);
-    drop(comp);
+    panic_guard::guard(None, (), || drop(comp));
 }
 
-pub unsafe fn create_component<T: AddInWrapper>(
+/// Creates the component object with vtables, expected by 1C platform
+/// # Arguments
+/// * `component` - pointer to the place where pointer to the component
+///   object will be written
+/// * `addin` - AddIn object to wrap
+/// # Returns
+/// `c_long` - `1` on success
+/// # Safety
+/// `component` must be a valid pointer, provided by 1C platform
+pub unsafe fn create_component<T: TryAddInWrapper>(
     component: *mut *mut c_void,
     addin: T,
 ) -> c_long {
@@ -133,6 +261,7 @@ pub unsafe fn create_component<T: AddInWrapper>(
         vptr4,
         destroy: destroy::<T>,
         memory: None,
+        connection: None,
         addin,
     });
 
@@ -140,6 +269,13 @@ pub unsafe fn create_component<T: AddInWrapper>(
     1
 }
 
+/// Destroys the component object, created by `create_component`
+/// # Arguments
+/// * `component` - pointer to the pointer to the component object
+/// # Returns
+/// `c_long` - `0` on success
+/// # Safety
+/// `component` must point to the object, created by `create_component`
 pub unsafe fn destroy_component(component: *mut *mut c_void) -> c_long {
     #[repr(C)]
     struct ComponentWrapper {
@@ -0,0 +1,96 @@
+use super::{
+    provided_types::{ParamValue, TVariant},
+    string_utils::WString,
+};
+
+/// VTable for MsgBox object, derived from Native API interface. See original
+/// C++ implementation in [example project](https://its.1c.ru/db/files/1CITS/EXE/VNCOMPS/VNCOMPS.zip)
+/// from 1C documentation
+#[repr(C)]
+struct MsgBoxVTable {
+    confirm:
+        unsafe extern "system" fn(&MsgBox, *const u16, &mut TVariant) -> bool,
+    alert: unsafe extern "system" fn(&MsgBox, *const u16) -> bool,
+}
+
+/// MsgBox object, used to show dialogs to the user of 1C platform.
+/// Can be obtained with `Connection::msg_box`
+#[repr(C)]
+pub struct MsgBox {
+    vptr: &'static MsgBoxVTable,
+}
+
+impl MsgBox {
+    /// Equivalent to `Confirm` from Native API interface and is used to ask
+    /// the user a question, that can be answered with "OK" or "Cancel"
+    /// # Arguments
+    /// * `query_text` - text of the question
+    /// # Returns
+    /// `Option<bool>` - answer of the user or None if the dialog failed
+    pub fn confirm(&self, query_text: &str) -> Option<bool> {
+        let query_text_wstr = WString::from_str_truncate(query_text);
+        let mut answer = TVariant::default();
+        let shown = unsafe {
+            (self.vptr.confirm)(self, query_text_wstr.as_ptr(), &mut answer)
+        };
+        if !shown {
+            return None;
+        }
+        match ParamValue::from(&answer) {
+            ParamValue::Bool(answer) => Some(answer),
+            _ => None,
+        }
+    }
+
+    /// Equivalent to `Alert` from Native API interface and is used to show
+    /// a message to the user
+    /// # Arguments
+    /// * `text` - text of the message
+    /// # Returns
+    /// `bool` - operation success status
+    pub fn alert(&self, text: &str) -> bool {
+        let text_wstr = WString::from_str_truncate(text);
+        unsafe { (self.vptr.alert)(self, text_wstr.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{
+        dynamic::DynamicAddIn, ffi::connection::Connection, testing::TestHost,
+    };
+
+    #[test]
+    fn dialogs_are_shown() {
+        let connection: Rc<Cell<Option<&'static Connection>>> =
+            Default::default();
+        let addin = DynamicAddIn::new("Dialogs").on_init({
+            let connection = connection.clone();
+            move |interface| {
+                connection.set(Some(interface));
+                Ok(())
+            }
+        });
+        let mut host = TestHost::new(addin);
+        assert!(host.init());
+        let msg_box = connection.get().unwrap().msg_box().unwrap();
+
+        assert_eq!(msg_box.confirm("Continue?"), Some(true));
+        host.connection().set_confirm_answer(Some(false));
+        assert_eq!(msg_box.confirm("Delete?"), Some(false));
+        host.connection().set_confirm_answer(None);
+        assert_eq!(msg_box.confirm("Пропустить?"), None);
+        assert_eq!(
+            host.connection().confirmations(),
+            ["Continue?", "Delete?", "Пропустить?"]
+        );
+
+        assert!(host.connection().alerts().is_empty());
+        assert!(msg_box.alert("Done"));
+        assert!(msg_box.alert("Готово"));
+        assert_eq!(host.connection().alerts(), ["Done", "Готово"]);
+        assert_eq!(host.memory().live_allocations(), 0);
+    }
+}
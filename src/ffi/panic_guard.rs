@@ -0,0 +1,219 @@
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{Once, RwLock},
+};
+
+use super::connection::{Connection, MessageCode};
+
+/// Panic, caught at the boundary between the AddIn and 1C platform
+/// # Fields
+/// * `message` - panic message
+/// * `location` - source location of the panic, if known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaughtPanic {
+    pub message: String,
+    pub location: Option<String>,
+}
+
+impl fmt::Display for CaughtPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => {
+                write!(f, "AddIn panicked at {location}: {}", self.message)
+            }
+            None => write!(f, "AddIn panicked: {}", self.message),
+        }
+    }
+}
+
+type PanicHook = Box<dyn Fn(&CaughtPanic) + Send + Sync>;
+
+static PANIC_HOOK: RwLock<Option<PanicHook>> = RwLock::new(None);
+static INSTALL_STD_HOOK: Once = Once::new();
+
+thread_local! {
+    static GUARD_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static LAST_PANIC: RefCell<Option<CaughtPanic>> = const { RefCell::new(None) };
+}
+
+/// Sets the hook, that is called for every panic caught at the boundary
+/// with 1C platform, after the panic is reported with `Connection::add_error`.
+/// Replaces the previously set hook
+/// # Arguments
+/// * `hook` - function, that receives caught panic
+pub fn set_panic_hook(hook: impl Fn(&CaughtPanic) + Send + Sync + 'static) {
+    let mut guard = PANIC_HOOK.write().unwrap_or_else(|e| e.into_inner());
+    *guard = Some(Box::new(hook));
+}
+
+/// Removes the hook, set with `set_panic_hook`
+pub fn clear_panic_hook() {
+    let mut guard = PANIC_HOOK.write().unwrap_or_else(|e| e.into_inner());
+    *guard = None;
+}
+
+/// Installs process-wide panic hook, that records message and location of
+/// panics inside `guard` instead of printing them. Panics outside of `guard`
+/// are passed to the previously installed hook
+fn install_std_hook() {
+    INSTALL_STD_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if GUARD_DEPTH.with(Cell::get) == 0 {
+                return previous(info);
+            }
+            let caught = CaughtPanic {
+                message: payload_message(info.payload()),
+                location: info.location().map(ToString::to_string),
+            };
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(caught));
+        }));
+    });
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    "Box<dyn Any>".to_string()
+}
+
+/// Calls `f`, catching panics, so they do not unwind into 1C platform.
+/// Caught panic is reported to 1C platform with `MessageCode::Fail`, passed
+/// to the hook, set with `set_panic_hook`, and `default` is returned
+/// # Arguments
+/// * `connection` - connection used to report the panic, if available
+/// * `default` - value returned if `f` panics
+/// * `f` - function to call
+/// # Returns
+/// `R` - value returned by `f` or `default`
+pub(crate) fn guard<R>(
+    connection: Option<&Connection>,
+    default: R,
+    f: impl FnOnce() -> R,
+) -> R {
+    let caught = match catch(f) {
+        Ok(value) => return value,
+        Err(caught) => caught,
+    };
+
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        super::call_trace::note_error(&caught);
+        if let Some(connection) = connection {
+            connection.add_error(MessageCode::Fail, "", &caught.to_string());
+        }
+        let hook = PANIC_HOOK.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(hook) = hook.as_ref() {
+            hook(&caught);
+        }
+    }));
+
+    default
+}
+
+/// Calls `f`, catching panics without reporting them, for code that runs
+/// outside of calls from 1C platform, like background jobs
+/// # Arguments
+/// * `f` - function to call
+/// # Returns
+/// `Result<R, CaughtPanic>` - value returned by `f` or the caught panic
+pub(crate) fn catch<R>(f: impl FnOnce() -> R) -> Result<R, CaughtPanic> {
+    install_std_hook();
+    GUARD_DEPTH.with(|depth| depth.set(depth.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    GUARD_DEPTH.with(|depth| depth.set(depth.get() - 1));
+
+    let payload = match result {
+        Ok(value) => return Ok(value),
+        Err(payload) => payload,
+    };
+    let caught = LAST_PANIC
+        .with(|last| last.borrow_mut().take())
+        .unwrap_or_else(|| CaughtPanic {
+            message: payload_message(payload.as_ref()),
+            location: None,
+        });
+    // Panic payload may panic on drop, it must not escape either
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(payload)));
+    Err(caught)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{clear_panic_hook, set_panic_hook};
+    use crate::{
+        dynamic::{DynamicAddIn, DynamicMethod, DynamicProperty},
+        ffi::{connection::MessageCode, provided_types::ParamValue},
+        testing::TestHost,
+    };
+
+    #[test]
+    fn panics_are_reported_and_component_keeps_working() {
+        let caught = Arc::new(Mutex::new(Vec::new()));
+        set_panic_hook({
+            let caught = caught.clone();
+            move |panic| {
+                if panic.message.starts_with("guarded") {
+                    caught.lock().unwrap().push(panic.clone());
+                }
+            }
+        });
+
+        let method = DynamicMethod::function(1, |params| {
+            match params.get(0).map(ParamValue::from) {
+                Some(ParamValue::Bool(true)) => panic!("guarded method"),
+                _ => Ok(ParamValue::I32(1)),
+            }
+        });
+        let property = DynamicProperty::read_only(|| panic!("guarded getter"));
+        let mut host = TestHost::new(
+            DynamicAddIn::new("Panics")
+                .with_method("Call", "Вызвать", method)
+                .unwrap()
+                .with_property("Value", "Значение", property)
+                .unwrap(),
+        );
+        assert!(host.init());
+
+        assert!(host
+            .call_as_func(0, &mut [ParamValue::Bool(true)])
+            .is_none());
+        let errors = host.connection().errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, MessageCode::Fail as u16);
+        let prefix = concat!("AddIn panicked at ", file!(), ":");
+        assert!(errors[0].description.starts_with(prefix));
+        assert!(errors[0].description.ends_with(": guarded method"));
+
+        assert!(host.get_prop_val(0).is_none());
+        let errors = host.connection().errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].code, MessageCode::Fail as u16);
+        assert!(errors[1].description.ends_with(": guarded getter"));
+
+        let value = host.call_as_func(0, &mut [ParamValue::Bool(false)]);
+        assert!(value == Some(ParamValue::I32(1)));
+        assert_eq!(host.connection().errors().len(), 2);
+        clear_panic_hook();
+
+        let caught = caught.lock().unwrap();
+        let messages = caught
+            .iter()
+            .map(|panic| panic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, ["guarded method", "guarded getter"]);
+        assert!(caught[0].location.as_deref().unwrap().starts_with(file!()));
+        assert_eq!(caught[0].to_string(), errors[0].description);
+
+        host.done();
+        assert_eq!(host.memory().live_allocations(), 0);
+    }
+}
@@ -0,0 +1,239 @@
+use std::{ffi::c_int, fmt, str::FromStr};
+
+use super::string_utils::WStr;
+
+/// Type of 1C platform application, that loaded the AddIn
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplicationType {
+    /// Unknown application
+    Unknown = -1,
+    /// Thin client
+    ThinClient = 0,
+    /// Thick client
+    ThickClient,
+    /// Web client
+    WebClient,
+    /// Server
+    Server,
+    /// External connection
+    ExtConn,
+    /// Mobile client
+    MobileClient,
+    /// Mobile server
+    MobileServer,
+}
+
+impl From<c_int> for ApplicationType {
+    fn from(value: c_int) -> Self {
+        match value {
+            0 => Self::ThinClient,
+            1 => Self::ThickClient,
+            2 => Self::WebClient,
+            3 => Self::Server,
+            4 => Self::ExtConn,
+            5 => Self::MobileClient,
+            6 => Self::MobileServer,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Version of 1C platform, e.g. `8.3.22.1709`. Versions are compared
+/// component by component
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlatformVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub release: u32,
+    pub build: u32,
+}
+
+impl PlatformVersion {
+    /// Creates a new PlatformVersion
+    pub const fn new(major: u32, minor: u32, release: u32, build: u32) -> Self {
+        Self {
+            major,
+            minor,
+            release,
+            build,
+        }
+    }
+}
+
+/// Error, returned when platform version string can not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError;
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid platform version")
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+impl FromStr for PlatformVersion {
+    type Err = ParseVersionError;
+
+    /// Parses version in `major.minor.release.build` format, missing
+    /// trailing components are treated as `0`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = [0; 4];
+        let mut parts = s.trim().split('.');
+        for component in components.iter_mut() {
+            let Some(part) = parts.next() else {
+                break;
+            };
+            *component = part.parse().map_err(|_| ParseVersionError)?;
+        }
+        if parts.next().is_some() {
+            return Err(ParseVersionError);
+        }
+        let [major, minor, release, build] = components;
+        Ok(Self::new(major, minor, release, build))
+    }
+}
+
+impl fmt::Display for PlatformVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.release, self.build
+        )
+    }
+}
+
+/// Information about 1C platform application, that loaded the AddIn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppInfo {
+    /// Version of 1C platform or None if it can not be parsed, see
+    /// `raw_version`
+    pub version: Option<PlatformVersion>,
+    /// Version of 1C platform, as it is reported by 1C platform
+    pub raw_version: String,
+    /// Type of the application
+    pub application: ApplicationType,
+    /// User agent of the browser, only available in web client
+    pub user_agent: Option<String>,
+}
+
+/// `AppInfo` structure, as it is passed by 1C platform
+#[repr(C)]
+struct AppInfoRaw {
+    app_version: *const u16,
+    application: c_int,
+    user_agent_information: *const u16,
+}
+
+/// VTable for PlatformInfo object, derived from Native API interface. See original
+/// C++ implementation in [example project](https://its.1c.ru/db/files/1CITS/EXE/VNCOMPS/VNCOMPS.zip)
+/// from 1C documentation
+#[repr(C)]
+struct PlatformInfoVTable {
+    get_platform_info:
+        unsafe extern "system" fn(&PlatformInfo) -> *const AppInfoRaw,
+}
+
+/// PlatformInfo object, used to get information about 1C platform.
+/// Can be obtained with `Connection::platform_info`
+#[repr(C)]
+pub struct PlatformInfo {
+    vptr: &'static PlatformInfoVTable,
+}
+
+impl PlatformInfo {
+    /// Equivalent to `GetPlatformInfo` from Native API interface
+    /// # Returns
+    /// `Option<AppInfo>` - information about 1C platform or None if it is
+    /// not available
+    pub fn get_platform_info(&self) -> Option<AppInfo> {
+        unsafe {
+            let info = (self.vptr.get_platform_info)(self).as_ref()?;
+            if info.app_version.is_null() {
+                return None;
+            }
+            let version = WStr::from_ptr(info.app_version).to_utf8_lossy();
+            let user_agent = match info.user_agent_information.is_null() {
+                true => None,
+                false => Some(
+                    WStr::from_ptr(info.user_agent_information).to_utf8_lossy(),
+                ),
+            };
+            Some(AppInfo {
+                version: version.parse().ok(),
+                raw_version: version,
+                application: ApplicationType::from(info.application),
+                user_agent: user_agent.filter(|s| !s.is_empty()),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::{ApplicationType, PlatformVersion};
+    use crate::{
+        dynamic::DynamicAddIn, ffi::connection::Connection, testing::TestHost,
+    };
+
+    #[test]
+    fn versions_are_parsed() {
+        assert_eq!(
+            "8.3.22.1709".parse(),
+            Ok(PlatformVersion::new(8, 3, 22, 1709))
+        );
+        assert_eq!(" 8.3 ".parse(), Ok(PlatformVersion::new(8, 3, 0, 0)));
+        assert!("8.3.22.1709.1".parse::<PlatformVersion>().is_err());
+        assert!("8.3.x".parse::<PlatformVersion>().is_err());
+        assert!("".parse::<PlatformVersion>().is_err());
+        assert!(
+            PlatformVersion::new(8, 3, 9, 0)
+                < PlatformVersion::new(8, 3, 10, 0)
+        );
+        assert_eq!(
+            PlatformVersion::new(8, 3, 22, 1709).to_string(),
+            "8.3.22.1709"
+        );
+    }
+
+    #[test]
+    fn unparsable_version_keeps_app_info() {
+        let connection: Rc<Cell<Option<&'static Connection>>> =
+            Default::default();
+        let addin = DynamicAddIn::new("Info").on_init({
+            let connection = connection.clone();
+            move |interface| {
+                connection.set(Some(interface));
+                Ok(())
+            }
+        });
+        let mut host = TestHost::new(addin);
+        assert!(host.init());
+        let info = connection.get().unwrap().platform_info().unwrap();
+
+        let app_info = info.get_platform_info().unwrap();
+        assert_eq!(
+            app_info.version,
+            Some(PlatformVersion::new(8, 3, 24, 1000))
+        );
+        assert_eq!(app_info.raw_version, "8.3.24.1000");
+        assert_eq!(app_info.application, ApplicationType::ThinClient);
+        assert_eq!(app_info.user_agent, None);
+
+        host.connection().set_platform_info(
+            "8.5.1.100-beta",
+            ApplicationType::WebClient,
+            Some("Mozilla/5.0"),
+        );
+        let app_info = info.get_platform_info().unwrap();
+        assert_eq!(app_info.version, None);
+        assert_eq!(app_info.raw_version, "8.5.1.100-beta");
+        assert_eq!(app_info.application, ApplicationType::WebClient);
+        assert_eq!(app_info.user_agent.as_deref(), Some("Mozilla/5.0"));
+        assert_eq!(host.memory().live_allocations(), 0);
+    }
+}
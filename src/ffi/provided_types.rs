@@ -1,11 +1,13 @@
-use chrono::{Datelike, Offset, Timelike};
+use chrono::{Datelike, Timelike};
 use std::{
-    ffi::{c_int, c_void},
+    ffi::{c_int, c_long, c_void},
     ptr,
-    slice::{from_raw_parts, from_raw_parts_mut},
+    slice::from_raw_parts,
 };
 
-use super::memory_manager::{AllocationError, MemoryManager};
+use super::memory_manager::{
+    AllocationError, MemoryManager, PlatformBlob, PlatformStr,
+};
 
 /// Type representing 1C date and time values
 /// # Fields
@@ -19,9 +21,9 @@ use super::memory_manager::{AllocationError, MemoryManager};
 /// * `yday` - days since January 1 - [0, 365]
 /// * `isdst` - daylight savings time flag
 /// * `gmtoff` - seconds east of UTC (unix only)
-/// * `zone` - timezone abbreviation (unix only)
+/// * `zone` - pointer to timezone abbreviation, owned by C runtime (unix only)
 #[repr(C)]
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug)]
 pub struct Tm {
     pub sec: c_int,
     pub min: c_int,
@@ -36,95 +38,159 @@ pub struct Tm {
     #[cfg(target_family = "unix")]
     pub gmtoff: std::ffi::c_long,
     #[cfg(target_family = "unix")]
-    pub zone: std::ffi::c_char,
+    pub zone: *const std::ffi::c_char,
 }
 
-impl From<chrono::DateTime<chrono::FixedOffset>> for Tm {
-    fn from(dt: chrono::DateTime<chrono::FixedOffset>) -> Self {
+// `zone` is either null or points to a static string of C runtime
+unsafe impl Send for Tm {}
+unsafe impl Sync for Tm {}
+
+impl Default for Tm {
+    fn default() -> Self {
         Self {
-            sec: dt.second() as c_int,
-            min: dt.minute() as c_int,
-            hour: dt.hour() as c_int,
-            mday: dt.day() as c_int,
-            mon: dt.month() as c_int,
-            year: dt.year() as c_int,
-            wday: dt.weekday().num_days_from_sunday() as c_int,
-            yday: dt.ordinal() as c_int,
-            isdst: dt.timestamp() as c_int,
+            sec: 0,
+            min: 0,
+            hour: 0,
+            mday: 0,
+            mon: 0,
+            year: 0,
+            wday: 0,
+            yday: 0,
+            isdst: 0,
             #[cfg(target_family = "unix")]
-            gmtoff: dt.offset().fix().local_minus_utc() as std::ffi::c_long,
+            gmtoff: 0,
             #[cfg(target_family = "unix")]
-            zone: dt.offset().to_string().into_bytes()[0] as std::ffi::c_char,
+            zone: ptr::null(),
         }
     }
 }
 
-impl From<&Tm> for chrono::DateTime<chrono::FixedOffset> {
-    fn from(tm: &Tm) -> Self {
+impl Tm {
+    /// Returns 1C empty date `0001-01-01 00:00:00`, which is the default
+    /// value of 1C `Date` type
+    pub fn empty_date() -> Self {
+        let date = chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        Self::from(date.and_time(chrono::NaiveTime::MIN))
+    }
+
+    /// Checks if the value is 1C empty date `0001-01-01 00:00:00`
+    pub fn is_empty_date(&self) -> bool {
+        self.year == 1 - 1900
+            && self.mon == 0
+            && self.mday == 1
+            && self.hour == 0
+            && self.min == 0
+            && self.sec == 0
+    }
+
+    /// Converts the value to date and time, ignoring timezone offset
+    /// # Returns
+    /// `Option<chrono::NaiveDateTime>` - date and time or None if fields are
+    /// out of range
+    pub fn to_naive(&self) -> Option<chrono::NaiveDateTime> {
         // The year begins with the year 1900. For example, 2023 == 123
         // The month starts at 0
-        let Some(naive_date) = chrono::NaiveDate::from_ymd_opt(
-            tm.year + 1900,
-            tm.mon as u32 + 1,
-            tm.mday as u32,
-        ) else {
-            return chrono::DateTime::default();
-        };
-        let Some(naive_time) = chrono::NaiveTime::from_hms_opt(
-            tm.hour as u32,
-            tm.min as u32,
-            tm.sec as u32,
-        ) else {
-            return chrono::DateTime::default();
+        let date = chrono::NaiveDate::from_ymd_opt(
+            self.year.checked_add(1900)?,
+            u32::try_from(self.mon).ok()? + 1,
+            u32::try_from(self.mday).ok()?,
+        )?;
+        // Leap second is represented by chrono as 59th second with
+        // nanoseconds exceeding one second
+        let (sec, nano) = match self.sec {
+            60 => (59, 1_000_000_000),
+            sec => (u32::try_from(sec).ok()?, 0),
         };
+        let time = chrono::NaiveTime::from_hms_nano_opt(
+            u32::try_from(self.hour).ok()?,
+            u32::try_from(self.min).ok()?,
+            sec,
+            nano,
+        )?;
+        Some(chrono::NaiveDateTime::new(date, time))
+    }
+
+    /// Returns timezone offset of the value, on Windows it is always UTC
+    pub fn offset(&self) -> chrono::FixedOffset {
         #[cfg(target_family = "unix")]
-        let Some(offset) = chrono::FixedOffset::east_opt(tm.gmtoff as i32) else {
-            return chrono::DateTime::default();
-        };
+        let offset = i32::try_from(self.gmtoff)
+            .ok()
+            .and_then(chrono::FixedOffset::east_opt);
         #[cfg(target_family = "windows")]
-        let Some(offset) = chrono::FixedOffset::east_opt(0) else {
-            return chrono::DateTime::default();
+        let offset = None;
+        offset.unwrap_or(chrono::FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+/// Converts date and time, 1C dates have no timezone, so `gmtoff` is 0.
+/// Fractions of a second are dropped
+impl From<chrono::NaiveDateTime> for Tm {
+    fn from(dt: chrono::NaiveDateTime) -> Self {
+        let sec = match dt.nanosecond() >= 1_000_000_000 {
+            true => 60,
+            false => dt.second() as c_int,
         };
-        chrono::DateTime::from_utc(
-            chrono::NaiveDateTime::new(naive_date, naive_time),
-            offset,
-        )
+        Self {
+            sec,
+            min: dt.minute() as c_int,
+            hour: dt.hour() as c_int,
+            mday: dt.day() as c_int,
+            mon: dt.month0() as c_int,
+            year: dt.year() - 1900,
+            wday: dt.weekday().num_days_from_sunday() as c_int,
+            yday: dt.ordinal0() as c_int,
+            ..Self::default()
+        }
+    }
+}
+
+/// Converts local date and time of the value and stores its offset in
+/// `gmtoff`. Fractions of a second are dropped
+impl From<chrono::DateTime<chrono::FixedOffset>> for Tm {
+    fn from(dt: chrono::DateTime<chrono::FixedOffset>) -> Self {
+        Self {
+            #[cfg(target_family = "unix")]
+            gmtoff: dt.offset().local_minus_utc() as std::ffi::c_long,
+            ..Self::from(dt.naive_local())
+        }
+    }
+}
+
+/// Converts fields of the value, ignoring timezone offset. Values out of
+/// range are converted to 1C empty date `0001-01-01 00:00:00`
+impl From<&Tm> for chrono::NaiveDateTime {
+    fn from(tm: &Tm) -> Self {
+        tm.to_naive()
+            .unwrap_or_else(|| Tm::empty_date().to_naive().unwrap())
+    }
+}
+
+impl From<Tm> for chrono::NaiveDateTime {
+    fn from(tm: Tm) -> Self {
+        Self::from(&tm)
+    }
+}
+
+/// Converts fields of the value as local time with `gmtoff` offset. Values
+/// out of range are converted to 1C empty date `0001-01-01 00:00:00`
+impl From<&Tm> for chrono::DateTime<chrono::FixedOffset> {
+    fn from(tm: &Tm) -> Self {
+        let offset = tm.offset();
+        let local = chrono::NaiveDateTime::from(tm);
+        let utc =
+            local - chrono::Duration::seconds(offset.local_minus_utc() as i64);
+        chrono::DateTime::from_naive_utc_and_offset(utc, offset)
     }
 }
 
 impl From<Tm> for chrono::DateTime<chrono::FixedOffset> {
     fn from(tm: Tm) -> Self {
-        // The year begins with the year 1900. For example, 2023 == 123
-        // The month starts at 0
-        let Some(naive_date) = chrono::NaiveDate::from_ymd_opt(
-            tm.year + 1900,
-            tm.mon as u32 + 1,
-            tm.mday as u32,
-        ) else {
-            return chrono::DateTime::default();
-        };
-        let Some(naive_time) = chrono::NaiveTime::from_hms_opt(
-            tm.hour as u32,
-            tm.min as u32,
-            tm.sec as u32,
-        ) else {
-            return chrono::DateTime::default();
-        };
-        #[cfg(target_family = "unix")]
-        let Some(offset) = chrono::FixedOffset::east_opt(tm.gmtoff as i32) else {
-            return chrono::DateTime::default();
-        };
-        #[cfg(target_family = "windows")]
-        let Some(offset) = chrono::FixedOffset::east_opt(0) else {
-            return chrono::DateTime::default();
-        };
-        chrono::DateTime::from_utc(
-            chrono::NaiveDateTime::new(naive_date, naive_time),
-            offset,
-        )
+        Self::from(&tm)
     }
 }
 
+/// Compares date, time and offset of the values. `zone` is not compared,
+/// because it is only a pointer to the abbreviation of the offset
 #[cfg(target_family = "unix")]
 impl PartialEq for Tm {
     fn eq(&self, other: &Self) -> bool {
@@ -138,7 +204,6 @@ impl PartialEq for Tm {
             && self.yday == other.yday
             && self.isdst == other.isdst
             && self.gmtoff == other.gmtoff
-            && self.zone == other.zone
     }
 }
 
@@ -201,39 +266,108 @@ impl<'a> ReturnValue<'a> {
 
     /// Sets the value of the ReturnValue object to UTF-16 `&[u16]`
     pub fn set_str(self, val: &[u16]) {
-        let Ok(ptr) = self.mem.alloc_str(val.len()) else {
+        let Ok(s) = PlatformStr::new(self.mem, val) else {
             *self.result = false;
             return;
         };
 
-        unsafe {
-            ptr::copy_nonoverlapping(val.as_ptr(), ptr.as_ptr(), val.len())
-        };
-
         self.variant.vt = VariantType::WStr;
-        self.variant.value.data_str.ptr = ptr.as_ptr();
-        self.variant.value.data_str.len = val.len() as u32;
+        self.variant.value.data_str.len = s.len() as u32;
+        self.variant.value.data_str.ptr = s.into_raw();
     }
 
     /// Sets the value of the ReturnValue object to blob `&[u8]`
     pub fn set_blob(self, val: &[u8]) {
-        let Ok(ptr) = self.mem.alloc_blob(val.len()) else {
+        let Ok(blob) = PlatformBlob::new(self.mem, val) else {
             *self.result = false;
             return;
         };
 
-        unsafe {
-            ptr::copy_nonoverlapping(val.as_ptr(), ptr.as_ptr(), val.len())
+        self.variant.vt = VariantType::Blob;
+        self.variant.value.data_blob.len = blob.len() as u32;
+        self.variant.value.data_blob.ptr = blob.into_raw();
+    }
+
+    /// Sets the value of the ReturnValue object to `Null`
+    pub fn set_null(self) {
+        self.variant.vt = VariantType::Null;
+    }
+
+    /// Sets the value of the ReturnValue object to integer `i8`
+    pub fn set_i8(self, val: i8) {
+        self.variant.update_to_i8(val);
+    }
+
+    /// Sets the value of the ReturnValue object to integer `i16`
+    pub fn set_i16(self, val: i16) {
+        self.variant.update_to_i16(val);
+    }
+
+    /// Sets the value of the ReturnValue object to integer `i64`
+    pub fn set_i64(self, val: i64) {
+        self.variant.update_to_i64(val);
+    }
+
+    /// Sets the value of the ReturnValue object to integer `u8`
+    pub fn set_u8(self, val: u8) {
+        self.variant.update_to_u8(val);
+    }
+
+    /// Sets the value of the ReturnValue object to integer `u16`
+    pub fn set_u16(self, val: u16) {
+        self.variant.update_to_u16(val);
+    }
+
+    /// Sets the value of the ReturnValue object to integer `u32`
+    pub fn set_u32(self, val: u32) {
+        self.variant.update_to_u32(val);
+    }
+
+    /// Sets the value of the ReturnValue object to integer `u64`
+    pub fn set_u64(self, val: u64) {
+        self.variant.update_to_u64(val);
+    }
+
+    /// Sets the value of the ReturnValue object to float `f32`
+    pub fn set_f32(self, val: f32) {
+        self.variant.update_to_f32(val);
+    }
+
+    /// Sets the value of the ReturnValue object to OLE Automation date `f64`
+    pub fn set_ole_date(self, val: f64) {
+        self.variant.update_to_ole_date(val);
+    }
+
+    /// Sets the value of the ReturnValue object to error code `i32`
+    pub fn set_error(self, val: i32) {
+        self.variant.update_to_error(val);
+    }
+
+    /// Sets the value of the ReturnValue object to `HRESULT` code
+    pub fn set_hresult(self, val: c_long) {
+        self.variant.update_to_hresult(val);
+    }
+
+    /// Sets the value of the ReturnValue object to UUID `[u8; 16]`
+    pub fn set_clsid(self, val: [u8; 16]) {
+        self.variant.update_to_clsid(val);
+    }
+
+    /// Sets the value of the ReturnValue object to narrow string `&[u8]`
+    pub fn set_pstr(self, val: &[u8]) {
+        let Ok(s) = PlatformBlob::new_nil(self.mem, val) else {
+            *self.result = false;
+            return;
         };
 
-        self.variant.vt = VariantType::Blob;
-        self.variant.value.data_blob.ptr = ptr.as_ptr();
-        self.variant.value.data_blob.len = val.len() as u32;
+        self.variant.vt = VariantType::PStr;
+        self.variant.value.data_blob.len = s.len() as u32;
+        self.variant.value.data_blob.ptr = s.into_raw();
     }
 }
 
 /// Represents 1C variant values for parameters
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum ParamValue {
     /// Empty value
     Empty,
@@ -249,9 +383,44 @@ pub enum ParamValue {
     Str(Vec<u16>),
     /// Blob value
     Blob(Vec<u8>),
+    /// Null value
+    Null,
+    /// 8-bit integer value
+    I8(i8),
+    /// 16-bit integer value
+    I16(i16),
+    /// 64-bit integer value
+    I64(i64),
+    /// 8-bit unsigned integer value
+    U8(u8),
+    /// 16-bit unsigned integer value
+    U16(u16),
+    /// 32-bit unsigned integer value
+    U32(u32),
+    /// 64-bit unsigned integer value
+    U64(u64),
+    /// Single precision float value
+    F32(f32),
+    /// OLE Automation date value, days since 1899-12-30
+    OleDate(f64),
+    /// Narrow string value, without null terminator
+    PStr(Vec<u8>),
+    /// Error code value
+    Error(i32),
+    /// `HRESULT` code value
+    HResult(c_long),
+    /// UUID value
+    ClsId([u8; 16]),
+}
+
+impl ParamValue {
+    /// Returns the name of 1C type of the value, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        ParamRef::from(self).type_name()
+    }
 }
 
-impl<'a> PartialEq for ParamValue {
+impl PartialEq for ParamValue {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Empty, Self::Empty) => true,
@@ -261,6 +430,20 @@ impl<'a> PartialEq for ParamValue {
             (Self::Date(a), Self::Date(b)) => a == b,
             (Self::Str(a), Self::Str(b)) => a == b,
             (Self::Blob(a), Self::Blob(b)) => a == b,
+            (Self::Null, Self::Null) => true,
+            (Self::I8(a), Self::I8(b)) => a == b,
+            (Self::I16(a), Self::I16(b)) => a == b,
+            (Self::I64(a), Self::I64(b)) => a == b,
+            (Self::U8(a), Self::U8(b)) => a == b,
+            (Self::U16(a), Self::U16(b)) => a == b,
+            (Self::U32(a), Self::U32(b)) => a == b,
+            (Self::U64(a), Self::U64(b)) => a == b,
+            (Self::F32(a), Self::F32(b)) => a == b,
+            (Self::OleDate(a), Self::OleDate(b)) => a == b,
+            (Self::PStr(a), Self::PStr(b)) => a == b,
+            (Self::Error(a), Self::Error(b)) => a == b,
+            (Self::HResult(a), Self::HResult(b)) => a == b,
+            (Self::ClsId(a), Self::ClsId(b)) => a == b,
             _ => false,
         }
     }
@@ -268,6 +451,152 @@ impl<'a> PartialEq for ParamValue {
 
 impl<'a> From<&'a TVariant> for ParamValue {
     fn from(param: &'a TVariant) -> ParamValue {
+        ParamRef::from(param).into()
+    }
+}
+
+/// Represents 1C variant values for parameters, borrowed from the buffers
+/// of 1C platform or of `ParamValue`, so strings and blobs are not copied
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamRef<'a> {
+    /// Empty value
+    Empty,
+    /// Boolean value
+    Bool(bool),
+    /// Integer value
+    I32(i32),
+    /// Float value
+    F64(f64),
+    /// Date-time value
+    Date(Tm),
+    /// UTF-16 string value
+    Str(&'a [u16]),
+    /// Blob value
+    Blob(&'a [u8]),
+    /// Null value
+    Null,
+    /// 8-bit integer value
+    I8(i8),
+    /// 16-bit integer value
+    I16(i16),
+    /// 64-bit integer value
+    I64(i64),
+    /// 8-bit unsigned integer value
+    U8(u8),
+    /// 16-bit unsigned integer value
+    U16(u16),
+    /// 32-bit unsigned integer value
+    U32(u32),
+    /// 64-bit unsigned integer value
+    U64(u64),
+    /// Single precision float value
+    F32(f32),
+    /// OLE Automation date value, days since 1899-12-30
+    OleDate(f64),
+    /// Narrow string value, without null terminator
+    PStr(&'a [u8]),
+    /// Error code value
+    Error(i32),
+    /// `HRESULT` code value
+    HResult(c_long),
+    /// UUID value
+    ClsId([u8; 16]),
+}
+
+impl ParamRef<'_> {
+    /// Returns the name of 1C type of the value, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Empty => "Undefined",
+            Self::Bool(_) => "Boolean",
+            Self::I8(_)
+            | Self::I16(_)
+            | Self::I32(_)
+            | Self::I64(_)
+            | Self::U8(_)
+            | Self::U16(_)
+            | Self::U32(_)
+            | Self::U64(_)
+            | Self::F32(_)
+            | Self::F64(_) => "Number",
+            Self::Date(_) | Self::OleDate(_) => "Date",
+            Self::Str(_) | Self::PStr(_) => "String",
+            Self::Blob(_) => "BinaryData",
+            Self::Null => "Null",
+            Self::Error(_) => "Error",
+            Self::HResult(_) => "HRESULT",
+            Self::ClsId(_) => "UUID",
+        }
+    }
+}
+
+impl<'a> From<&'a ParamValue> for ParamRef<'a> {
+    fn from(param: &'a ParamValue) -> Self {
+        match param {
+            ParamValue::Empty => Self::Empty,
+            ParamValue::Bool(v) => Self::Bool(*v),
+            ParamValue::I32(v) => Self::I32(*v),
+            ParamValue::F64(v) => Self::F64(*v),
+            ParamValue::Date(v) => Self::Date(*v),
+            ParamValue::Str(v) => Self::Str(v),
+            ParamValue::Blob(v) => Self::Blob(v),
+            ParamValue::Null => Self::Null,
+            ParamValue::I8(v) => Self::I8(*v),
+            ParamValue::I16(v) => Self::I16(*v),
+            ParamValue::I64(v) => Self::I64(*v),
+            ParamValue::U8(v) => Self::U8(*v),
+            ParamValue::U16(v) => Self::U16(*v),
+            ParamValue::U32(v) => Self::U32(*v),
+            ParamValue::U64(v) => Self::U64(*v),
+            ParamValue::F32(v) => Self::F32(*v),
+            ParamValue::OleDate(v) => Self::OleDate(*v),
+            ParamValue::PStr(v) => Self::PStr(v),
+            ParamValue::Error(v) => Self::Error(*v),
+            ParamValue::HResult(v) => Self::HResult(*v),
+            ParamValue::ClsId(v) => Self::ClsId(*v),
+        }
+    }
+}
+
+impl From<ParamRef<'_>> for ParamValue {
+    fn from(param: ParamRef<'_>) -> Self {
+        match param {
+            ParamRef::Empty => Self::Empty,
+            ParamRef::Bool(v) => Self::Bool(v),
+            ParamRef::I32(v) => Self::I32(v),
+            ParamRef::F64(v) => Self::F64(v),
+            ParamRef::Date(v) => Self::Date(v),
+            ParamRef::Str(v) => Self::Str(v.into()),
+            ParamRef::Blob(v) => Self::Blob(v.into()),
+            ParamRef::Null => Self::Null,
+            ParamRef::I8(v) => Self::I8(v),
+            ParamRef::I16(v) => Self::I16(v),
+            ParamRef::I64(v) => Self::I64(v),
+            ParamRef::U8(v) => Self::U8(v),
+            ParamRef::U16(v) => Self::U16(v),
+            ParamRef::U32(v) => Self::U32(v),
+            ParamRef::U64(v) => Self::U64(v),
+            ParamRef::F32(v) => Self::F32(v),
+            ParamRef::OleDate(v) => Self::OleDate(v),
+            ParamRef::PStr(v) => Self::PStr(v.into()),
+            ParamRef::Error(v) => Self::Error(v),
+            ParamRef::HResult(v) => Self::HResult(v),
+            ParamRef::ClsId(v) => Self::ClsId(v),
+        }
+    }
+}
+
+impl<'a> From<&'a TVariant> for ParamRef<'a> {
+    fn from(param: &'a TVariant) -> Self {
+        /// Slice from the pointer, passed by 1C platform, which may be null
+        /// for empty strings and blobs
+        unsafe fn slice<'a, T>(ptr: *const T, len: u32) -> &'a [T] {
+            match ptr.is_null() {
+                true => &[],
+                false => from_raw_parts(ptr, len as usize),
+            }
+        }
+
         unsafe {
             match param.vt {
                 VariantType::Empty => Self::Empty,
@@ -275,22 +604,182 @@ impl<'a> From<&'a TVariant> for ParamValue {
                 VariantType::Int32 => Self::I32(param.value.i32),
                 VariantType::Double => Self::F64(param.value.f64),
                 VariantType::Time => Self::Date(param.value.tm),
-                VariantType::WStr => Self::Str(
-                    from_raw_parts(
-                        param.value.data_str.ptr,
-                        param.value.data_str.len as usize,
-                    )
-                    .into(),
-                ),
-                VariantType::Blob => Self::Blob(
-                    from_raw_parts(
-                        param.value.data_blob.ptr,
-                        param.value.data_blob.len as usize,
-                    )
-                    .into(),
-                ),
-                _ => Self::Empty,
+                VariantType::WStr => Self::Str(slice(
+                    param.value.data_str.ptr,
+                    param.value.data_str.len,
+                )),
+                VariantType::Blob => Self::Blob(slice(
+                    param.value.data_blob.ptr,
+                    param.value.data_blob.len,
+                )),
+                VariantType::Null => Self::Null,
+                VariantType::Int8 => Self::I8(param.value.i8),
+                VariantType::Int16 => Self::I16(param.value.i16),
+                VariantType::Int64 => Self::I64(param.value.i64),
+                VariantType::UInt8 => Self::U8(param.value.u8),
+                VariantType::UInt16 => Self::U16(param.value.u16),
+                VariantType::UInt32 => Self::U32(param.value.u32),
+                VariantType::UInt64 => Self::U64(param.value.u64),
+                VariantType::Int => Self::I32(param.value.i32),
+                VariantType::UInt => Self::U32(param.value.u32),
+                VariantType::Float => Self::F32(param.value.f32),
+                VariantType::Date => Self::OleDate(param.value.f64),
+                VariantType::PStr => Self::PStr(slice(
+                    param.value.data_blob.ptr,
+                    param.value.data_blob.len,
+                )),
+                VariantType::Error => Self::Error(param.value.err_code),
+                VariantType::HResult => Self::HResult(param.value.h_res),
+                VariantType::ClsID => Self::ClsId(param.value.clsid),
+                // Interfaces, arrays and undefined values can not be
+                // represented in Rust
+                VariantType::Interface
+                | VariantType::Variant
+                | VariantType::Undefined => Self::Empty,
+            }
+        }
+    }
+}
+
+/// Storage of method parameters, see `Params`
+enum ParamsStorage<'a> {
+    Platform {
+        mem: &'a MemoryManager,
+        variants: &'a mut [TVariant],
+    },
+    Owned(&'a mut [ParamValue]),
+}
+
+/// Method parameters, passed to `TryAddInWrapper::call_as_proc` and
+/// `TryAddInWrapper::call_as_func`. Parameters are read as `ParamRef`
+/// directly from the buffers of 1C platform, and only parameters, written
+/// with `set`, are copied back to 1C platform
+pub struct Params<'a> {
+    storage: ParamsStorage<'a>,
+}
+
+impl<'a> Params<'a> {
+    /// Creates parameters over the variants, passed by 1C platform
+    /// # Arguments
+    /// * `mem` - memory manager to allocate written values with
+    /// * `variants` - parameters, passed by 1C platform
+    pub(crate) fn from_platform(
+        mem: &'a MemoryManager,
+        variants: &'a mut [TVariant],
+    ) -> Self {
+        Self {
+            storage: ParamsStorage::Platform { mem, variants },
+        }
+    }
+
+    /// Returns the number of parameters
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            ParamsStorage::Platform { variants, .. } => variants.len(),
+            ParamsStorage::Owned(values) => values.len(),
+        }
+    }
+
+    /// Checks if there are no parameters
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the parameter with the given index
+    /// # Arguments
+    /// * `index` - index of the parameter
+    /// # Returns
+    /// `Option<ParamRef>` - value of the parameter or None if there is no
+    /// parameter with the given index
+    pub fn get(&self, index: usize) -> Option<ParamRef<'_>> {
+        match &self.storage {
+            ParamsStorage::Platform { variants, .. } => {
+                variants.get(index).map(ParamRef::from)
             }
+            ParamsStorage::Owned(values) => {
+                values.get(index).map(ParamRef::from)
+            }
+        }
+    }
+
+    /// Returns iterator over the parameters
+    pub fn iter(&self) -> impl Iterator<Item = ParamRef<'_>> {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    /// Copies all parameters to owned values
+    pub fn to_values(&self) -> Vec<ParamValue> {
+        self.iter().map(ParamValue::from).collect()
+    }
+
+    /// Writes the value of the parameter, passed by reference. For 1C
+    /// platform parameters, memory of the previous value is released and
+    /// the new value is allocated with MemoryManager
+    /// # Arguments
+    /// * `index` - index of the parameter
+    /// * `value` - new value of the parameter
+    /// # Returns
+    /// `Result<(), AllocationError>` - operation result
+    /// # Panics
+    /// If there is no parameter with the given index
+    pub fn set(
+        &mut self,
+        index: usize,
+        value: ParamRef<'_>,
+    ) -> Result<(), AllocationError> {
+        match &mut self.storage {
+            ParamsStorage::Platform { mem, variants } => unsafe {
+                variants[index].set_param(mem, value)
+            },
+            ParamsStorage::Owned(values) => {
+                values[index] = value.into();
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the value of the parameter, like `set`, only if it differs
+    /// from the current value, so unchanged strings and blobs are not
+    /// reallocated
+    /// # Arguments
+    /// * `index` - index of the parameter
+    /// * `value` - new value of the parameter
+    /// # Returns
+    /// `Result<(), AllocationError>` - operation result
+    /// # Panics
+    /// If there is no parameter with the given index
+    pub fn update(
+        &mut self,
+        index: usize,
+        value: ParamRef<'_>,
+    ) -> Result<(), AllocationError> {
+        match self.get(index) == Some(value) {
+            true => Ok(()),
+            false => self.set(index, value),
+        }
+    }
+
+    /// Writes the values, that differ from the current values of the
+    /// parameters, so unchanged parameters are not copied
+    /// # Arguments
+    /// * `values` - new values of the parameters, extra values are ignored
+    /// # Returns
+    /// `Result<(), AllocationError>` - operation result
+    pub fn write_changed(
+        &mut self,
+        values: &[ParamValue],
+    ) -> Result<(), AllocationError> {
+        for (index, value) in values.iter().enumerate().take(self.len()) {
+            self.update(index, value.into())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a mut [ParamValue]> for Params<'a> {
+    fn from(values: &'a mut [ParamValue]) -> Self {
+        Self {
+            storage: ParamsStorage::Owned(values),
         }
     }
 }
@@ -352,16 +841,32 @@ pub struct DataBlob {
 /// Type encapsulating 1C variant values
 /// # Fields
 /// * `bool` - boolean value
-/// * `i32` - integer value
-/// * `f64` - float value
+/// * `i8`, `i16`, `i32`, `i64` - integer values
+/// * `u8`, `u16`, `u32`, `u64` - unsigned integer values
+/// * `f32` - single precision float value
+/// * `f64` - float value, also used for OLE Automation date
+/// * `err_code` - error code value
+/// * `h_res` - `HRESULT` code value
+/// * `clsid` - UUID value
 /// * `tm` - date-time value
 /// * `data_str` - UTF-16 string value
-/// * `data_blob` - blob value
+/// * `data_blob` - blob and narrow string value
 #[repr(C)]
 pub union VariantValue {
     pub bool: bool,
+    pub i8: i8,
+    pub i16: i16,
     pub i32: i32,
+    pub i64: i64,
+    pub u8: u8,
+    pub u16: u16,
+    pub u32: u32,
+    pub u64: u64,
+    pub f32: f32,
     pub f64: f64,
+    pub err_code: i32,
+    pub h_res: c_long,
+    pub clsid: [u8; 16],
     pub tm: Tm,
     pub data_str: DataStr,
     pub data_blob: DataBlob,
@@ -370,12 +875,23 @@ pub union VariantValue {
 /// Type encapsulating 1C variant values for internal use
 #[repr(C)]
 pub struct TVariant {
-    value: VariantValue,
+    pub(crate) value: VariantValue,
     elements: u32, //Dimension for an one-dimensional array in pvarVal
-    vt: VariantType,
+    pub(crate) vt: VariantType,
+}
+
+impl Default for TVariant {
+    fn default() -> Self {
+        Self {
+            value: VariantValue { tm: Tm::default() },
+            elements: 0,
+            vt: VariantType::Empty,
+        }
+    }
 }
 
 impl TVariant {
+    /// Same as `set_str`
     /// # Safety
     /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
     /// Function is safe as long as 1C platform provides valid pointers.
@@ -384,39 +900,19 @@ impl TVariant {
         mem_mngr: &MemoryManager,
         v: &[u16],
     ) -> Result<u32, AllocationError> {
-        let mut old_pointer = self.value.data_str.ptr;
-
-        let ptr = mem_mngr.alloc_str(v.len())?;
-        ptr::copy_nonoverlapping(v.as_ptr(), ptr.as_ptr(), v.len());
-
-        self.value.data_str.ptr = ptr.as_ptr();
-        self.value.data_str.len = v.len() as u32;
-
-        mem_mngr.free_memory(&mut old_pointer.cast::<c_void>());
-
-        self.vt = VariantType::WStr;
-
-        Ok(self.value.data_str.len)
+        self.set_str(mem_mngr, v)
     }
 
+    /// Same as `set_blob`
+    /// # Safety
+    /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
+    /// Function is safe as long as 1C platform provides valid pointers.
     pub unsafe fn update_to_blob(
         &mut self,
         mem_mngr: &MemoryManager,
         v: &[u8],
     ) -> Result<u32, AllocationError> {
-        let mut old_pointer = self.value.data_blob.ptr;
-
-        let ptr = mem_mngr.alloc_blob(v.len())?;
-        ptr::copy_nonoverlapping(v.as_ptr(), ptr.as_ptr(), v.len());
-
-        self.value.data_blob.ptr = ptr.as_ptr();
-        self.value.data_blob.len = v.len() as u32;
-
-        mem_mngr.free_memory(&mut old_pointer.cast::<c_void>());
-
-        self.vt = VariantType::Blob;
-
-        Ok(self.value.data_blob.len)
+        self.set_blob(mem_mngr, v)
     }
 
     pub fn update_to_bool(&mut self, v: bool) {
@@ -438,4 +934,532 @@ impl TVariant {
         self.value.tm = v;
         self.vt = VariantType::Time;
     }
+
+    pub fn update_to_empty(&mut self) {
+        self.vt = VariantType::Empty;
+    }
+
+    pub fn update_to_null(&mut self) {
+        self.vt = VariantType::Null;
+    }
+
+    pub fn update_to_i8(&mut self, v: i8) {
+        self.value.i8 = v;
+        self.vt = VariantType::Int8;
+    }
+
+    pub fn update_to_i16(&mut self, v: i16) {
+        self.value.i16 = v;
+        self.vt = VariantType::Int16;
+    }
+
+    pub fn update_to_i64(&mut self, v: i64) {
+        self.value.i64 = v;
+        self.vt = VariantType::Int64;
+    }
+
+    pub fn update_to_u8(&mut self, v: u8) {
+        self.value.u8 = v;
+        self.vt = VariantType::UInt8;
+    }
+
+    pub fn update_to_u16(&mut self, v: u16) {
+        self.value.u16 = v;
+        self.vt = VariantType::UInt16;
+    }
+
+    pub fn update_to_u32(&mut self, v: u32) {
+        self.value.u32 = v;
+        self.vt = VariantType::UInt32;
+    }
+
+    pub fn update_to_u64(&mut self, v: u64) {
+        self.value.u64 = v;
+        self.vt = VariantType::UInt64;
+    }
+
+    pub fn update_to_f32(&mut self, v: f32) {
+        self.value.f32 = v;
+        self.vt = VariantType::Float;
+    }
+
+    pub fn update_to_ole_date(&mut self, v: f64) {
+        self.value.f64 = v;
+        self.vt = VariantType::Date;
+    }
+
+    pub fn update_to_error(&mut self, v: i32) {
+        self.value.err_code = v;
+        self.vt = VariantType::Error;
+    }
+
+    pub fn update_to_hresult(&mut self, v: c_long) {
+        self.value.h_res = v;
+        self.vt = VariantType::HResult;
+    }
+
+    pub fn update_to_clsid(&mut self, v: [u8; 16]) {
+        self.value.clsid = v;
+        self.vt = VariantType::ClsID;
+    }
+
+    /// Same as `set_pstr`
+    /// # Safety
+    /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
+    /// Function is safe as long as 1C platform provides valid pointers.
+    pub unsafe fn update_to_pstr(
+        &mut self,
+        mem_mngr: &MemoryManager,
+        v: &[u8],
+    ) -> Result<u32, AllocationError> {
+        self.set_pstr(mem_mngr, v)
+    }
+
+    /// Checks if the variant owns memory, allocated with `MemoryManager`
+    fn owns_memory(&self) -> bool {
+        matches!(
+            self.vt,
+            VariantType::WStr | VariantType::PStr | VariantType::Blob
+        )
+    }
+
+    /// Releases the memory, owned by the variant, and sets it to
+    /// `VariantType::Empty`. Memory is released only for WStr, PStr and
+    /// Blob values, other values do not own any memory
+    /// # Arguments
+    /// * `mem_mngr` - memory manager, that allocated the value
+    /// # Safety
+    /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
+    /// Function is safe as long as 1C platform provides valid pointers.
+    pub unsafe fn clear(&mut self, mem_mngr: &MemoryManager) {
+        if self.owns_memory() {
+            let mut ptr = match self.vt {
+                VariantType::WStr => self.value.data_str.ptr.cast::<c_void>(),
+                _ => self.value.data_blob.ptr.cast::<c_void>(),
+            };
+            if !ptr.is_null() {
+                mem_mngr.free_memory(&mut ptr);
+            }
+        }
+        self.value = VariantValue { tm: Tm::default() };
+        self.vt = VariantType::Empty;
+    }
+
+    /// Sets the value to UTF-16 string, allocated with `mem_mngr`.
+    /// Previous value is released with `clear`, after the new one is
+    /// allocated, so the variant is left unchanged on allocation error
+    /// # Arguments
+    /// * `mem_mngr` - memory manager to allocate the string with
+    /// * `v` - string to set
+    /// # Returns
+    /// `Result<u32, AllocationError>` - length of the string
+    /// # Safety
+    /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
+    /// Function is safe as long as 1C platform provides valid pointers.
+    pub unsafe fn set_str(
+        &mut self,
+        mem_mngr: &MemoryManager,
+        v: &[u16],
+    ) -> Result<u32, AllocationError> {
+        let s = PlatformStr::new(mem_mngr, v)?;
+        self.clear(mem_mngr);
+
+        self.value.data_str.len = s.len() as u32;
+        self.value.data_str.ptr = s.into_raw();
+        self.vt = VariantType::WStr;
+
+        Ok(self.value.data_str.len)
+    }
+
+    /// Sets the value to blob, allocated with `mem_mngr`. See `set_str`
+    /// # Safety
+    /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
+    /// Function is safe as long as 1C platform provides valid pointers.
+    pub unsafe fn set_blob(
+        &mut self,
+        mem_mngr: &MemoryManager,
+        v: &[u8],
+    ) -> Result<u32, AllocationError> {
+        let blob = PlatformBlob::new(mem_mngr, v)?;
+        self.clear(mem_mngr);
+
+        self.value.data_blob.len = blob.len() as u32;
+        self.value.data_blob.ptr = blob.into_raw();
+        self.vt = VariantType::Blob;
+
+        Ok(self.value.data_blob.len)
+    }
+
+    /// Sets the value to narrow string, allocated with `mem_mngr`.
+    /// See `set_str`
+    /// # Safety
+    /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
+    /// Function is safe as long as 1C platform provides valid pointers.
+    pub unsafe fn set_pstr(
+        &mut self,
+        mem_mngr: &MemoryManager,
+        v: &[u8],
+    ) -> Result<u32, AllocationError> {
+        let s = PlatformBlob::new_nil(mem_mngr, v)?;
+        self.clear(mem_mngr);
+
+        self.value.data_blob.len = s.len() as u32;
+        self.value.data_blob.ptr = s.into_raw();
+        self.vt = VariantType::PStr;
+
+        Ok(self.value.data_blob.len)
+    }
+
+    /// Sets the value to any parameter value, allocating memory for strings
+    /// and blobs with `mem_mngr`. Memory, owned by the previous value,
+    /// is released with `clear`
+    /// # Arguments
+    /// * `mem_mngr` - memory manager to allocate and release values with
+    /// * `v` - value to set
+    /// # Safety
+    /// This function is unsafe because it manipulates pointers, provided by the 1C platform.
+    /// Function is safe as long as 1C platform provides valid pointers.
+    pub unsafe fn set_param(
+        &mut self,
+        mem_mngr: &MemoryManager,
+        v: ParamRef<'_>,
+    ) -> Result<(), AllocationError> {
+        match v {
+            ParamRef::Str(v) => {
+                self.set_str(mem_mngr, v)?;
+                return Ok(());
+            }
+            ParamRef::Blob(v) => {
+                self.set_blob(mem_mngr, v)?;
+                return Ok(());
+            }
+            ParamRef::PStr(v) => {
+                self.set_pstr(mem_mngr, v)?;
+                return Ok(());
+            }
+            _ => self.clear(mem_mngr),
+        }
+        match v {
+            ParamRef::Empty
+            | ParamRef::Str(_)
+            | ParamRef::Blob(_)
+            | ParamRef::PStr(_) => {}
+            ParamRef::Bool(v) => self.update_to_bool(v),
+            ParamRef::I32(v) => self.update_to_i32(v),
+            ParamRef::F64(v) => self.update_to_f64(v),
+            ParamRef::Date(v) => self.update_to_date(v),
+            ParamRef::Null => self.update_to_null(),
+            ParamRef::I8(v) => self.update_to_i8(v),
+            ParamRef::I16(v) => self.update_to_i16(v),
+            ParamRef::I64(v) => self.update_to_i64(v),
+            ParamRef::U8(v) => self.update_to_u8(v),
+            ParamRef::U16(v) => self.update_to_u16(v),
+            ParamRef::U32(v) => self.update_to_u32(v),
+            ParamRef::U64(v) => self.update_to_u64(v),
+            ParamRef::F32(v) => self.update_to_f32(v),
+            ParamRef::OleDate(v) => self.update_to_ole_date(v),
+            ParamRef::Error(v) => self.update_to_error(v),
+            ParamRef::HResult(v) => self.update_to_hresult(v),
+            ParamRef::ClsId(v) => self.update_to_clsid(v),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime, Timelike};
+    use proptest::prelude::*;
+
+    use super::{ParamRef, ParamValue, Params, ReturnValue, TVariant, Tm};
+    use crate::testing::TestMemoryManager;
+
+    fn empty_naive() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(1, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn fields(tm: &Tm) -> [i32; 6] {
+        [tm.year, tm.mon, tm.mday, tm.hour, tm.min, tm.sec]
+    }
+
+    prop_compose! {
+        fn naive_date_time()(
+            days in 0..3_652_059i64,
+            secs in 0..86_400u32,
+            nano in 0..1_000_000_000u32,
+        ) -> NaiveDateTime {
+            let dt = empty_naive()
+                + chrono::Duration::days(days)
+                + chrono::Duration::seconds(secs.into());
+            dt.with_nanosecond(nano).unwrap()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn naive_to_tm_and_back(dt in naive_date_time()) {
+            let tm = Tm::from(dt);
+            prop_assert_eq!(tm.to_naive(), dt.with_nanosecond(0));
+            prop_assert_eq!(NaiveDateTime::from(&tm), dt.with_nanosecond(0).unwrap());
+        }
+
+        #[test]
+        fn tm_to_naive_and_back(
+            year in (1 - 1900)..(9999 - 1900),
+            mon in 0..12,
+            mday in 1..29,
+            hour in 0..24,
+            min in 0..60,
+            sec in 0..61,
+        ) {
+            let tm = Tm { year, mon, mday, hour, min, sec, ..Tm::default() };
+            let naive = tm.to_naive().unwrap();
+            let back = Tm::from(naive);
+            prop_assert_eq!(fields(&back), fields(&tm));
+            prop_assert_eq!(back, Tm::from(NaiveDateTime::from(back)));
+        }
+
+        #[test]
+        fn out_of_range_is_empty_date(
+            year in any::<i32>(),
+            mon in any::<i32>(),
+            mday in any::<i32>(),
+            hour in any::<i32>(),
+            min in any::<i32>(),
+            sec in any::<i32>(),
+        ) {
+            let tm = Tm { year, mon, mday, hour, min, sec, ..Tm::default() };
+            let naive = NaiveDateTime::from(&tm);
+            match tm.to_naive() {
+                Some(expected) => prop_assert_eq!(naive, expected),
+                None => prop_assert_eq!(naive, empty_naive()),
+            }
+        }
+    }
+
+    #[test]
+    fn empty_date() {
+        let tm = Tm::empty_date();
+        assert!(tm.is_empty_date());
+        assert_eq!(tm.to_naive(), Some(empty_naive()));
+        assert_eq!(Tm::from(empty_naive()), tm);
+        assert!(!Tm::from(empty_naive() + chrono::Duration::seconds(1))
+            .is_empty_date());
+    }
+
+    #[test]
+    fn leap_second() {
+        let dt = NaiveDate::from_ymd_opt(2016, 12, 31)
+            .unwrap()
+            .and_hms_milli_opt(23, 59, 59, 1_000)
+            .unwrap();
+        let tm = Tm::from(dt);
+        assert_eq!(tm.sec, 60);
+        assert_eq!(tm.to_naive(), Some(dt));
+        assert_eq!(Tm::from(NaiveDateTime::from(tm)), tm);
+    }
+
+    #[test]
+    fn out_of_range_fields() {
+        let valid = Tm::from(empty_naive() + chrono::Duration::days(800));
+        let cases = [
+            Tm::default(),
+            Tm { mon: 12, ..valid },
+            Tm { mon: -1, ..valid },
+            Tm { mday: 32, ..valid },
+            Tm { hour: 24, ..valid },
+            Tm { min: 60, ..valid },
+            Tm { sec: 61, ..valid },
+            Tm {
+                year: i32::MAX,
+                ..valid
+            },
+            Tm {
+                year: 1_000_000,
+                ..valid
+            },
+        ];
+        for tm in cases {
+            assert_eq!(tm.to_naive(), None, "{tm:?}");
+            assert_eq!(NaiveDateTime::from(&tm), empty_naive(), "{tm:?}");
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn offset_round_trip() {
+        let offset = chrono::FixedOffset::east_opt(3 * 3600).unwrap();
+        let dt = NaiveDate::from_ymd_opt(2023, 7, 14)
+            .unwrap()
+            .and_hms_opt(12, 30, 15)
+            .unwrap()
+            .and_local_timezone(offset)
+            .unwrap();
+        let tm = Tm::from(dt);
+        assert_eq!(tm.gmtoff, 3 * 3600);
+        assert_eq!(chrono::DateTime::<chrono::FixedOffset>::from(tm), dt);
+    }
+
+    /// Allocates variants with the values, as 1C platform does
+    fn variants(
+        memory: &TestMemoryManager,
+        values: &[ParamValue],
+    ) -> Vec<TVariant> {
+        let mem = memory.as_memory_manager();
+        values
+            .iter()
+            .map(|value| {
+                let mut variant = TVariant::default();
+                unsafe { variant.set_param(mem, value.into()) }.unwrap();
+                variant
+            })
+            .collect()
+    }
+
+    fn blob_ptr(params: &Params, index: usize) -> *const u8 {
+        match params.get(index) {
+            Some(ParamRef::Blob(blob)) => blob.as_ptr(),
+            other => panic!("expected blob, found {other:?}"),
+        }
+    }
+
+    #[test]
+    fn unchanged_params_are_not_reallocated() {
+        let memory = TestMemoryManager::new();
+        let values = [ParamValue::Blob(vec![1, 2, 3]), ParamValue::I32(1)];
+        let mut variants = variants(&memory, &values);
+        let mut params =
+            Params::from_platform(memory.as_memory_manager(), &mut variants);
+
+        let blob = blob_ptr(&params, 0);
+        params.write_changed(&values).unwrap();
+        assert_eq!(blob_ptr(&params, 0), blob);
+        params.update(0, ParamRef::Blob(&[1, 2, 3])).unwrap();
+        assert_eq!(blob_ptr(&params, 0), blob);
+        assert_eq!(memory.live_allocations(), 1);
+
+        for variant in &mut variants {
+            unsafe { variant.clear(memory.as_memory_manager()) };
+        }
+        assert_eq!(memory.live_allocations(), 0);
+        assert_eq!(memory.invalid_frees(), 0);
+    }
+
+    #[test]
+    fn written_params_replace_platform_values() {
+        let memory = TestMemoryManager::new();
+        let values = [ParamValue::Blob(vec![1, 2, 3]), ParamValue::I32(1)];
+        let mut variants = variants(&memory, &values);
+        let mut params =
+            Params::from_platform(memory.as_memory_manager(), &mut variants);
+
+        let written =
+            [ParamValue::Blob(vec![4, 5]), ParamValue::Str(vec![0x44])];
+        params.write_changed(&written).unwrap();
+        assert_eq!(params.get(0), Some(ParamRef::Blob(&[4, 5])));
+        params.set(0, ParamRef::Bool(true)).unwrap();
+
+        assert!(matches!(
+            ParamValue::from(&variants[0]),
+            ParamValue::Bool(true)
+        ));
+        assert!(matches!(
+            ParamValue::from(&variants[1]),
+            ParamValue::Str(s) if s == [0x44]
+        ));
+        // Only the string is left, both blobs are freed
+        assert_eq!(memory.live_allocations(), 1);
+
+        for variant in &mut variants {
+            unsafe { variant.clear(memory.as_memory_manager()) };
+        }
+        assert_eq!(memory.live_allocations(), 0);
+        assert_eq!(memory.invalid_frees(), 0);
+    }
+
+    fn date() -> Tm {
+        Tm::from(empty_naive() + chrono::Duration::days(738_000))
+    }
+
+    #[test]
+    fn return_values_round_trip() {
+        const CLSID: [u8; 16] = *b"0123456789abcdef";
+        let cases: [(fn(ReturnValue), ParamValue); 22] = [
+            (|val| val.set_empty(), ParamValue::Empty),
+            (|val| val.set_null(), ParamValue::Null),
+            (|val| val.set_bool(true), ParamValue::Bool(true)),
+            (|val| val.set_i32(i32::MIN), ParamValue::I32(i32::MIN)),
+            (|val| val.set_f64(-0.5), ParamValue::F64(-0.5)),
+            (|val| val.set_date(date()), ParamValue::Date(date())),
+            (
+                |val| val.set_str(&[0x44, 0x430]),
+                ParamValue::Str(vec![0x44, 0x430]),
+            ),
+            (|val| val.set_str(&[]), ParamValue::Str(vec![])),
+            (
+                |val| val.set_blob(&[0, 255]),
+                ParamValue::Blob(vec![0, 255]),
+            ),
+            (|val| val.set_i8(i8::MIN), ParamValue::I8(i8::MIN)),
+            (|val| val.set_i16(i16::MIN), ParamValue::I16(i16::MIN)),
+            (|val| val.set_i64(i64::MIN), ParamValue::I64(i64::MIN)),
+            (|val| val.set_u8(u8::MAX), ParamValue::U8(u8::MAX)),
+            (|val| val.set_u16(u16::MAX), ParamValue::U16(u16::MAX)),
+            (|val| val.set_u32(u32::MAX), ParamValue::U32(u32::MAX)),
+            (|val| val.set_u64(u64::MAX), ParamValue::U64(u64::MAX)),
+            (|val| val.set_f32(1.5), ParamValue::F32(1.5)),
+            (
+                |val| val.set_ole_date(45_000.25),
+                ParamValue::OleDate(45_000.25),
+            ),
+            (
+                |val| val.set_pstr(b"text"),
+                ParamValue::PStr(b"text".to_vec()),
+            ),
+            (|val| val.set_error(-7), ParamValue::Error(-7)),
+            (|val| val.set_hresult(-1), ParamValue::HResult(-1)),
+            (|val| val.set_clsid(CLSID), ParamValue::ClsId(CLSID)),
+        ];
+        let memory = TestMemoryManager::new();
+        let mem = memory.as_memory_manager();
+        for (set, expected) in cases {
+            let mut variant = TVariant::default();
+            let mut result = true;
+            set(ReturnValue {
+                mem,
+                variant: &mut variant,
+                result: &mut result,
+            });
+            assert!(result, "{expected:?}");
+            assert_eq!(ParamRef::from(&variant), ParamRef::from(&expected));
+            assert!(ParamValue::from(&variant) == expected, "{expected:?}");
+            assert_eq!(
+                ParamValue::from(&variant).type_name(),
+                expected.type_name()
+            );
+            unsafe { variant.clear(mem) };
+            assert_eq!(memory.live_allocations(), 0, "{expected:?}");
+        }
+        assert_eq!(memory.invalid_frees(), 0);
+    }
+
+    #[test]
+    fn empty_params() {
+        let memory = TestMemoryManager::new();
+        let mut params =
+            Params::from_platform(memory.as_memory_manager(), &mut []);
+        assert!(params.is_empty());
+        assert_eq!(params.get(0), None);
+        assert_eq!(params.iter().count(), 0);
+        params.write_changed(&[ParamValue::I32(1)]).unwrap();
+
+        let mut values = [];
+        let mut params = Params::from(&mut values[..]);
+        assert!(params.to_values().is_empty());
+        params.write_changed(&[ParamValue::I32(1)]).unwrap();
+        assert_eq!(memory.live_allocations(), 0);
+    }
 }
@@ -1,33 +1,336 @@
-use std::slice::from_raw_parts;
+use std::{borrow::Borrow, fmt, ops::Deref, slice::from_raw_parts};
 
-/// Helper function to convert pointer to UTF-16 string to Rust slice
+/// Error of creating `WStr` or `WString` from UTF-16 data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NulError {
+    /// Data has NUL character at the given position before its end
+    Interior(usize),
+    /// Data does not end with NUL character
+    NotTerminated,
+}
+
+impl fmt::Display for NulError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Interior(position) => {
+                write!(f, "unexpected NUL character at position {position}")
+            }
+            Self::NotTerminated => write!(f, "string is not NUL-terminated"),
+        }
+    }
+}
+
+impl std::error::Error for NulError {}
+
+/// Error of converting UTF-16 string with unpaired surrogate to UTF-8
+/// # Fields
+/// * `position` - position of the unpaired surrogate
+/// * `unit` - value of the unpaired surrogate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utf16Error {
+    pub position: usize,
+    pub unit: u16,
+}
+
+impl fmt::Display for Utf16Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unpaired surrogate {:#06x} at position {}",
+            self.unit, self.position
+        )
+    }
+}
+
+impl std::error::Error for Utf16Error {}
+
+/// Converts UTF-16 string to Rust string, surrogate pairs are decoded
+/// to single characters
 /// # Arguments
-/// * `s` - pointer to UTF-16 string
+/// * `s` - UTF-16 string without NUL terminator
 /// # Returns
-/// `&[u16]` - slice of UTF-16 characters
-/// # Safety
-/// This function is unsafe because it takes a raw pointer and dereferences it
-pub unsafe fn get_str<'a>(s: *const u16) -> &'a [u16] {
-    unsafe fn strlen(s: *const u16) -> usize {
-        let mut i = 0;
-        while *s.add(i) != 0 {
-            i += 1;
+/// `Result<String, Utf16Error>` - Rust string or error on the first
+/// unpaired surrogate
+pub fn from_utf16(s: &[u16]) -> Result<String, Utf16Error> {
+    let mut result = String::with_capacity(s.len());
+    let mut position = 0;
+    for c in char::decode_utf16(s.iter().copied()) {
+        match c {
+            Ok(c) => {
+                result.push(c);
+                position += c.len_utf16();
+            }
+            Err(err) => {
+                return Err(Utf16Error {
+                    position,
+                    unit: err.unpaired_surrogate(),
+                })
+            }
         }
-        i + 1
     }
+    Ok(result)
+}
 
-    let len = strlen(s);
-    from_raw_parts(s, len)
+/// Converts UTF-16 string to Rust string, replacing unpaired surrogates
+/// with `U+FFFD REPLACEMENT CHARACTER`
+/// # Arguments
+/// * `s` - UTF-16 string without NUL terminator
+/// # Returns
+/// `String` - Rust string
+pub fn from_utf16_lossy(s: &[u16]) -> String {
+    String::from_utf16_lossy(s)
 }
 
-/// Helper function to convert Rust string to UTF-16 string
+/// Borrowed NUL-terminated UTF-16 string, passed to and from 1C platform.
+/// Like `CStr`, it always ends with a single NUL character and has no other
+/// NUL characters
+#[repr(transparent)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct WStr {
+    inner: [u16],
+}
+
+impl WStr {
+    /// Wraps NUL-terminated string, passed by 1C platform
+    /// # Arguments
+    /// * `ptr` - pointer to NUL-terminated UTF-16 string
+    /// # Returns
+    /// `&WStr` - string, borrowed for lifetime `'a`
+    /// # Safety
+    /// `ptr` must be non-null and point to NUL-terminated UTF-16 string,
+    /// that is valid and not modified for lifetime `'a`
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> &'a WStr {
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        Self::from_slice_with_nul_unchecked(from_raw_parts(ptr, len + 1))
+    }
+
+    /// Wraps NUL-terminated string, that may be null
+    /// # Arguments
+    /// * `ptr` - pointer to NUL-terminated UTF-16 string or null
+    /// # Returns
+    /// `Option<&WStr>` - string or None if `ptr` is null
+    /// # Safety
+    /// Same as `from_ptr`, if `ptr` is non-null
+    pub unsafe fn from_ptr_opt<'a>(ptr: *const u16) -> Option<&'a WStr> {
+        match ptr.is_null() {
+            true => None,
+            false => Some(Self::from_ptr(ptr)),
+        }
+    }
+
+    /// Wraps slice, that ends with NUL character and has no other NULs
+    /// # Arguments
+    /// * `s` - UTF-16 string with NUL terminator
+    /// # Returns
+    /// `Result<&WStr, NulError>` - string or error, if `s` violates
+    /// the invariants
+    pub fn from_slice_with_nul(s: &[u16]) -> Result<&WStr, NulError> {
+        match s.iter().position(|&c| c == 0) {
+            Some(position) if position + 1 == s.len() => {
+                Ok(unsafe { Self::from_slice_with_nul_unchecked(s) })
+            }
+            Some(position) => Err(NulError::Interior(position)),
+            None => Err(NulError::NotTerminated),
+        }
+    }
+
+    /// Wraps slice without checking the invariants
+    /// # Safety
+    /// `s` must end with NUL character and have no other NULs
+    pub unsafe fn from_slice_with_nul_unchecked(s: &[u16]) -> &WStr {
+        &*(s as *const [u16] as *const WStr)
+    }
+
+    /// Returns pointer to the string, that can be passed to 1C platform
+    pub fn as_ptr(&self) -> *const u16 {
+        self.inner.as_ptr()
+    }
+
+    /// Returns the string without NUL terminator
+    pub fn as_slice(&self) -> &[u16] {
+        &self.inner[..self.inner.len() - 1]
+    }
+
+    /// Returns the string with NUL terminator
+    pub fn as_slice_with_nul(&self) -> &[u16] {
+        &self.inner
+    }
+
+    /// Returns length of the string in UTF-16 code units, without
+    /// NUL terminator
+    pub fn len(&self) -> usize {
+        self.inner.len() - 1
+    }
+
+    /// Checks if the string has no characters besides NUL terminator
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Converts the string to Rust string, see `from_utf16`
+    pub fn to_utf8(&self) -> Result<String, Utf16Error> {
+        from_utf16(self.as_slice())
+    }
+
+    /// Converts the string to Rust string, see `from_utf16_lossy`
+    pub fn to_utf8_lossy(&self) -> String {
+        from_utf16_lossy(self.as_slice())
+    }
+}
+
+impl fmt::Debug for WStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_utf8_lossy(), f)
+    }
+}
+
+impl fmt::Display for WStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_utf8_lossy(), f)
+    }
+}
+
+impl AsRef<[u16]> for WStr {
+    fn as_ref(&self) -> &[u16] {
+        self.as_slice()
+    }
+}
+
+impl ToOwned for WStr {
+    type Owned = WString;
+
+    fn to_owned(&self) -> WString {
+        WString {
+            inner: self.inner.to_vec(),
+        }
+    }
+}
+
+/// Owned NUL-terminated UTF-16 string, passed to 1C platform.
+/// Like `CString`, it always ends with a single NUL character and has no
+/// other NUL characters
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct WString {
+    inner: Vec<u16>,
+}
+
+impl WString {
+    /// Converts Rust string to UTF-16, characters outside of the Basic
+    /// Multilingual Plane are encoded as surrogate pairs
+    /// # Arguments
+    /// * `s` - Rust string
+    /// # Returns
+    /// `Result<WString, NulError>` - string or error if `s` has NUL character
+    pub fn new(s: &str) -> Result<Self, NulError> {
+        Self::from_vec(s.encode_utf16().collect())
+    }
+
+    /// Appends NUL terminator to UTF-16 string
+    /// # Arguments
+    /// * `v` - UTF-16 string without NUL terminator
+    /// # Returns
+    /// `Result<WString, NulError>` - string or error if `v` has NUL character
+    pub fn from_vec(mut v: Vec<u16>) -> Result<Self, NulError> {
+        if let Some(position) = v.iter().position(|&c| c == 0) {
+            return Err(NulError::Interior(position));
+        }
+        v.push(0);
+        Ok(Self { inner: v })
+    }
+
+    /// Converts Rust string to UTF-16, cutting it at the first NUL character,
+    /// as 1C platform would read it
+    /// # Arguments
+    /// * `s` - Rust string
+    /// # Returns
+    /// `WString` - string
+    pub fn from_str_truncate(s: &str) -> Self {
+        let mut inner: Vec<u16> =
+            s.encode_utf16().take_while(|&c| c != 0).collect();
+        inner.push(0);
+        Self { inner }
+    }
+
+    /// Returns the string as `WStr`
+    pub fn as_wstr(&self) -> &WStr {
+        unsafe { WStr::from_slice_with_nul_unchecked(&self.inner) }
+    }
+
+    /// Returns mutable pointer to the string, for 1C platform methods, that
+    /// take non-const strings but do not modify them
+    pub fn as_mut_ptr(&mut self) -> *mut u16 {
+        self.inner.as_mut_ptr()
+    }
+
+    /// Converts the string to UTF-16 data without NUL terminator
+    pub fn into_vec(mut self) -> Vec<u16> {
+        self.inner.pop();
+        self.inner
+    }
+
+    /// Converts the string to UTF-16 data with NUL terminator
+    pub fn into_vec_with_nul(self) -> Vec<u16> {
+        self.inner
+    }
+}
+
+impl Default for WString {
+    fn default() -> Self {
+        Self { inner: vec![0] }
+    }
+}
+
+impl Deref for WString {
+    type Target = WStr;
+
+    fn deref(&self) -> &WStr {
+        self.as_wstr()
+    }
+}
+
+impl Borrow<WStr> for WString {
+    fn borrow(&self) -> &WStr {
+        self.as_wstr()
+    }
+}
+
+impl AsRef<WStr> for WString {
+    fn as_ref(&self) -> &WStr {
+        self.as_wstr()
+    }
+}
+
+impl AsRef<[u16]> for WString {
+    fn as_ref(&self) -> &[u16] {
+        self.as_slice()
+    }
+}
+
+impl fmt::Debug for WString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_wstr(), f)
+    }
+}
+
+impl fmt::Display for WString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_wstr(), f)
+    }
+}
+
+/// Helper function to convert pointer to UTF-16 string to Rust slice
 /// # Arguments
-/// * `s` - Rust string
+/// * `s` - pointer to UTF-16 string
 /// # Returns
-/// `Vec<u16>` - UTF-16 string without null terminator
-#[cfg(target_family = "unix")]
-pub fn os_string_nil(s: &str) -> Vec<u16> {
-    s.encode_utf16().collect()
+/// `&[u16]` - slice of UTF-16 characters, including NUL terminator
+/// # Safety
+/// This function is unsafe because it takes a raw pointer and dereferences it
+#[deprecated(note = "use `WStr::from_ptr`, that tracks the NUL terminator")]
+pub unsafe fn get_str<'a>(s: *const u16) -> &'a [u16] {
+    WStr::from_ptr(s).as_slice_with_nul()
 }
 
 /// Helper function to convert Rust string to UTF-16 string
@@ -35,12 +338,9 @@ pub fn os_string_nil(s: &str) -> Vec<u16> {
 /// * `s` - Rust string
 /// # Returns
 /// `Vec<u16>` - UTF-16 string with null terminator
-#[cfg(target_family = "windows")]
+#[deprecated(note = "use `WString`")]
 pub fn os_string_nil(s: &str) -> Vec<u16> {
-    let os_str = std::ffi::OsStr::new(s);
-    std::os::windows::prelude::OsStrExt::encode_wide(os_str)
-        .chain(Some(0).into_iter())
-        .collect()
+    s.encode_utf16().chain(Some(0)).collect()
 }
 
 /// Helper function to convert Rust string to UTF-16 string
@@ -48,29 +348,106 @@ pub fn os_string_nil(s: &str) -> Vec<u16> {
 /// * `s` - Rust string
 /// # Returns
 /// `Vec<u16>` - UTF-16 string without null terminator
-#[cfg(target_family = "unix")]
+#[deprecated(note = "use `str::encode_utf16` or `WString::into_vec`")]
 pub fn os_string(s: &str) -> Vec<u16> {
     s.encode_utf16().collect()
 }
 
-/// Helper function to convert Rust string to UTF-16 string
-/// # Arguments
-/// * `s` - Rust string
-/// # Returns
-/// `Vec<u16>` - UTF-16 string with null terminator
-#[cfg(target_family = "windows")]
-pub fn os_string(s: &str) -> Vec<u16> {
-    let os_str = std::ffi::OsStr::new(s);
-    std::os::windows::prelude::OsStrExt::encode_wide(os_str).collect()
-}
-
 /// Helper function to convert UTF-16 string to Rust string
 /// # Arguments
 /// * `s` - UTF-16 string
 /// # Returns
 /// `String` - Rust string
+#[deprecated(note = "use `from_utf16`, `from_utf16_lossy` or `WStr`")]
 pub fn from_os_string(s: &[u16]) -> String {
     String::from_utf16_lossy(s)
         .trim_end_matches(char::from(0))
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{from_utf16, NulError, Utf16Error, WStr, WString};
+
+    /// Musical symbol G clef, encoded as a surrogate pair
+    const CLEF: [u16; 2] = [0xD834, 0xDD1E];
+
+    #[test]
+    fn terminator_invariants() {
+        let s = WString::new("Тест").unwrap();
+        assert_eq!(s.len(), 4);
+        assert_eq!(s.as_slice(), "Тест".encode_utf16().collect::<Vec<_>>());
+        assert_eq!(s.as_slice_with_nul().last(), Some(&0));
+        assert_eq!(s.as_slice_with_nul().len(), 5);
+
+        let borrowed = unsafe { WStr::from_ptr(s.as_ptr()) };
+        assert_eq!(borrowed, s.as_wstr());
+        assert_eq!(borrowed.to_owned(), s);
+        assert_eq!(s.clone().into_vec(), s.as_slice());
+        assert_eq!(s.into_vec_with_nul(), borrowed.as_slice_with_nul());
+
+        let empty = WString::default();
+        assert!(empty.is_empty());
+        assert_eq!(empty.as_slice_with_nul(), [0]);
+        assert_eq!(unsafe { WStr::from_ptr_opt(std::ptr::null()) }, None);
+    }
+
+    #[test]
+    fn interior_nul_is_rejected() {
+        assert_eq!(WString::new("a\0b"), Err(NulError::Interior(1)));
+        assert_eq!(WString::from_vec(vec![0]), Err(NulError::Interior(0)));
+        assert_eq!(
+            WStr::from_slice_with_nul(&[0x61, 0, 0x62, 0]),
+            Err(NulError::Interior(1))
+        );
+        assert_eq!(
+            WStr::from_slice_with_nul(&[0x61]),
+            Err(NulError::NotTerminated)
+        );
+        assert_eq!(
+            WStr::from_slice_with_nul(&[]),
+            Err(NulError::NotTerminated)
+        );
+        assert!(WStr::from_slice_with_nul(&[0x61, 0]).is_ok());
+        assert_eq!(WString::from_str_truncate("a\0b").as_slice(), [0x61]);
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        let s = WString::new("\u{1D11E}").unwrap();
+        assert_eq!(s.as_slice(), CLEF);
+        assert_eq!(s.to_utf8().unwrap(), "\u{1D11E}");
+        assert_eq!(
+            from_utf16(&[0x41, CLEF[0], CLEF[1]]).unwrap(),
+            "A\u{1D11E}"
+        );
+    }
+
+    #[test]
+    fn unpaired_surrogate_position() {
+        assert_eq!(
+            from_utf16(&[0x41, 0xD834, 0x42]),
+            Err(Utf16Error {
+                position: 1,
+                unit: 0xD834
+            })
+        );
+        // Position is counted in UTF-16 code units, so the pair counts as 2
+        assert_eq!(
+            from_utf16(&[CLEF[0], CLEF[1], 0xDC00]),
+            Err(Utf16Error {
+                position: 2,
+                unit: 0xDC00
+            })
+        );
+    }
+
+    #[test]
+    fn lossy_conversion() {
+        let s =
+            WString::from_vec(vec![0x41, 0xDC00, CLEF[0], CLEF[1]]).unwrap();
+        assert!(s.to_utf8().is_err());
+        assert_eq!(s.to_utf8_lossy(), "A\u{FFFD}\u{1D11E}");
+        assert_eq!(s.to_string(), s.to_utf8_lossy());
+    }
+}
@@ -1,8 +1,61 @@
-use crate::ffi::{
-    connection::Connection,
-    provided_types::{ParamValue, ReturnValue},
+use std::{fmt, iter};
+
+use crate::{
+    executor::Executor,
+    ffi::{
+        connection::Connection,
+        provided_types::{ParamValue, Params, ReturnValue},
+        AppCapabilities, AttachType,
+    },
 };
 
+/// Error, returned by `TryAddInWrapper` methods. It is reported to 1C platform
+/// with `Connection::add_error` and `MessageCode::Fail`, so the exception,
+/// raised in 1C, has the description of the error
+/// # Fields
+/// * `source` - source of the error, shown by 1C platform along with description
+/// * `description` - description of the error, errors with empty description
+///   are not reported
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddInError {
+    pub source: String,
+    pub description: String,
+}
+
+impl AddInError {
+    /// Creates an error with the given description
+    pub fn new(description: impl Into<String>) -> Self {
+        Self {
+            source: String::new(),
+            description: description.into(),
+        }
+    }
+
+    /// Sets the source of the error
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = source.into();
+        self
+    }
+}
+
+impl fmt::Display for AddInError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source.is_empty() {
+            true => write!(f, "{}", self.description),
+            false => write!(f, "{}: {}", self.source, self.description),
+        }
+    }
+}
+
+impl<E: std::error::Error> From<E> for AddInError {
+    fn from(err: E) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+/// Result of `TryAddInWrapper` methods
+pub type AddInResult<T = ()> = Result<T, AddInError>;
+
 /// `AddInWrapper` trait is used to implement the 1C AddIn interface,
 /// and is used in FFI to get necessary information about the AddIn
 /// and call its methods.
@@ -30,6 +83,31 @@ pub trait AddInWrapper {
         2000
     }
 
+    /// Used by `GetAttachType` export to tell 1C platform, whether the AddIn can be
+    /// loaded into 1C platform process, into a separate process or both. If 1C platform
+    /// reports, that the AddIn was attached by other scheme, `init` is not called
+    /// and the AddIn fails to load
+    /// # Returns
+    /// `AttachType` - supported attaching schemes
+    fn attach_type() -> AttachType
+    where
+        Self: Sized,
+    {
+        AttachType::Any
+    }
+
+    /// Used by `SetPlatformCapabilities` export to negotiate `AppCapabilities` with
+    /// 1C platform. Negotiated value is the lowest of the levels, supported by 1C platform
+    /// and by all AddIns of the library, and can be read with `Connection::app_capabilities`
+    /// # Returns
+    /// `AppCapabilities` - highest supported capabilities level
+    fn supported_capabilities() -> AppCapabilities
+    where
+        Self: Sized,
+    {
+        AppCapabilities::LAST
+    }
+
     /// Equivalent to `Done` from Native API interface and is called when the AddIn is unloaded by 1C platform
     fn done(&mut self);
 
@@ -48,7 +126,7 @@ pub trait AddInWrapper {
     /// Equivalent to `FindProp` from Native API interface and is used to get the index of the property
     /// with the given name
     /// # Arguments
-    /// * `name` - name of the property in UTF-16
+    /// * `name` - name of the property in UTF-16 with NUL terminator
     /// # Returns
     /// `Option<usize>` - index of the property or None if the property was not found
     fn find_prop(&self, name: &[u16]) -> Option<usize>;
@@ -105,7 +183,7 @@ pub trait AddInWrapper {
     /// Equivalent to `FindMethod` from Native API interface and is used to get the index of method
     /// with the given name
     /// # Arguments
-    /// * `name` - name of method in UTF-16
+    /// * `name` - name of method in UTF-16 with NUL terminator
     /// # Returns
     /// `Option<usize>` - index of method or None if method was not found
     fn find_method(&self, name: &[u16]) -> Option<usize>;
@@ -181,11 +259,530 @@ pub trait AddInWrapper {
     /// Equivalent to `SetLocale` from Native API interface and is used to set the locale
     /// of the AddIn. It's marked as deprecated in 1C documentation, but is still available
     /// for use with platform versions prior to 8.3.21
+    /// # Arguments
+    /// * `loc` - locale name in UTF-16 with NUL terminator
     fn set_locale(&mut self, loc: &[u16]);
 
     /// Equivalent to `SetUserInterfaceLanguageCode` from Native API interface and is used to
     /// pass the language code of the 1C platform interface to the AddIn
     /// # Arguments
-    /// * `lang` - language code in UTF-16, two letters
+    /// * `lang` - language code in UTF-16, two letters with NUL terminator
     fn set_user_interface_language_code(&mut self, lang: &[u16]);
 }
+
+/// `TryAddInWrapper` trait is a variant of `AddInWrapper`, whose methods return
+/// `AddInResult` instead of bool. It is used by FFI, and every `AddInWrapper` implements it,
+/// returning `AddInError` with empty description instead of false.
+///
+/// When a method returns an error, its description is passed to `Connection::add_error`
+/// with `MessageCode::Fail` before the failure is returned to 1C platform, so
+/// `ОписаниеОшибки()` in `Попытка/Исключение` block contains it.
+///
+/// Methods have the same meaning as in `AddInWrapper`.
+pub trait TryAddInWrapper {
+    /// Equivalent to `Init` from Native API interface, see `AddInWrapper::init`
+    /// # Arguments
+    /// * `interface` - pointer to the 1C Connection object
+    /// # Returns
+    /// `AddInResult` - operation result
+    fn init(&mut self, interface: &'static Connection) -> AddInResult;
+
+    /// Equivalent to `GetInfo` from Native API interface, see `AddInWrapper::get_info`
+    /// # Returns
+    /// `u16` - Native API version
+    fn get_info(&self) -> u16 {
+        2000
+    }
+
+    /// Used by `GetAttachType` export, see `AddInWrapper::attach_type`
+    /// # Returns
+    /// `AttachType` - supported attaching schemes
+    fn attach_type() -> AttachType
+    where
+        Self: Sized,
+    {
+        AttachType::Any
+    }
+
+    /// Used by `SetPlatformCapabilities` export, see `AddInWrapper::supported_capabilities`
+    /// # Returns
+    /// `AppCapabilities` - highest supported capabilities level
+    fn supported_capabilities() -> AppCapabilities
+    where
+        Self: Sized,
+    {
+        AppCapabilities::LAST
+    }
+
+    /// Equivalent to `Done` from Native API interface, see `AddInWrapper::done`
+    fn done(&mut self);
+
+    /// Equivalent to `RegisterExtensionAs` from Native API interface,
+    /// see `AddInWrapper::register_extension_as`
+    /// # Returns
+    /// `&[u16]` - name of the AddIn in UTF-16
+    fn register_extension_as(&mut self) -> &[u16];
+
+    /// Equivalent to `GetNProps` from Native API interface, see `AddInWrapper::get_n_props`
+    /// # Returns
+    /// `usize` - number of properties
+    fn get_n_props(&self) -> usize;
+
+    /// Equivalent to `FindProp` from Native API interface, see `AddInWrapper::find_prop`
+    /// # Arguments
+    /// * `name` - name of the property in UTF-16, without NUL terminator
+    /// # Returns
+    /// `Option<usize>` - index of the property or None if the property was not found
+    fn find_prop(&self, name: &[u16]) -> Option<usize>;
+
+    /// Equivalent to `GetPropName` from Native API interface, see `AddInWrapper::get_prop_name`
+    /// # Arguments
+    /// * `num` - index of the property
+    /// * `alias` - alias of the property, usually 0 for Russian and 1 for English
+    /// # Returns
+    /// `Option<Vec<u16>>` - name of the property in UTF-16 or None if the property was not found
+    fn get_prop_name(&self, num: usize, alias: usize) -> Option<Vec<u16>>;
+
+    /// Equivalent to `GetPropVal` from Native API interface, see `AddInWrapper::get_prop_val`
+    /// # Arguments
+    /// * `num` - index of the property
+    /// * `val` - pointer to the ReturnValue object that will be used to return the value
+    /// # Returns
+    /// `AddInResult` - operation result
+    fn get_prop_val(&self, num: usize, val: ReturnValue) -> AddInResult;
+
+    /// Equivalent to `SetPropVal` from Native API interface, see `AddInWrapper::set_prop_val`
+    /// # Arguments
+    /// * `num` - index of the property
+    /// * `val` - pointer to the ParamValue object that contains the value
+    /// # Returns
+    /// `AddInResult` - operation result
+    fn set_prop_val(&mut self, num: usize, val: &ParamValue) -> AddInResult;
+
+    /// Equivalent to `IsPropReadable` from Native API interface, see `AddInWrapper::is_prop_readable`
+    /// # Arguments
+    /// * `num` - index of the property
+    /// # Returns
+    /// `bool` - if the property is readable
+    fn is_prop_readable(&self, num: usize) -> bool;
+
+    /// Equivalent to `IsPropWritable` from Native API interface, see `AddInWrapper::is_prop_writable`
+    /// # Arguments
+    /// * `num` - index of the property
+    /// # Returns
+    /// `bool` - if the property is writable
+    fn is_prop_writable(&self, num: usize) -> bool;
+
+    /// Equivalent to `GetNMethods` from Native API interface, see `AddInWrapper::get_n_methods`
+    /// # Returns
+    /// `usize` - number of methods
+    fn get_n_methods(&self) -> usize;
+
+    /// Equivalent to `FindMethod` from Native API interface, see `AddInWrapper::find_method`
+    /// # Arguments
+    /// * `name` - name of method in UTF-16, without NUL terminator
+    /// # Returns
+    /// `Option<usize>` - index of method or None if method was not found
+    fn find_method(&self, name: &[u16]) -> Option<usize>;
+
+    /// Equivalent to `GetMethodName` from Native API interface, see `AddInWrapper::get_method_name`
+    /// # Arguments
+    /// * `num` - index of method
+    /// * `alias` - alias of method, usually 0 for Russian and 1 for English
+    /// # Returns
+    /// `Option<Vec<u16>>` - name of method in UTF-16 or None if method was not found
+    fn get_method_name(&self, num: usize, alias: usize) -> Option<Vec<u16>>;
+
+    /// Equivalent to `GetNParams` from Native API interface, see `AddInWrapper::get_n_params`
+    /// # Arguments
+    /// * `num` - index of method
+    /// # Returns
+    /// `usize` - number of parameters
+    fn get_n_params(&self, num: usize) -> usize;
+
+    /// Equivalent to `GetParamDefValue` from Native API interface, see `AddInWrapper::get_param_def_value`
+    /// # Arguments
+    /// * `method_num` - index of method
+    /// * `param_num` - index of the parameter
+    /// * `value` - pointer to the ReturnValue object that will be used to return the value
+    /// # Returns
+    /// `bool` - if the parameter has default value
+    fn get_param_def_value(
+        &self,
+        method_num: usize,
+        param_num: usize,
+        value: ReturnValue,
+    ) -> bool;
+
+    /// Equivalent to `HasRetVal` from Native API interface, see `AddInWrapper::has_ret_val`
+    /// # Arguments
+    /// * `method_num` - index of method
+    /// # Returns
+    /// `bool` - if method returns a value
+    fn has_ret_val(&self, method_num: usize) -> bool;
+
+    /// Equivalent to `CallAsProc` from Native API interface, see `AddInWrapper::call_as_proc`.
+    /// Parameters are borrowed from 1C platform without copying, and only the parameters,
+    /// written with `Params::set`, are copied back
+    /// # Arguments
+    /// * `method_num` - index of method
+    /// * `params` - parameters of method
+    /// # Returns
+    /// `AddInResult` - operation result
+    fn call_as_proc(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+    ) -> AddInResult;
+
+    /// Equivalent to `CallAsFunc` from Native API interface, see `AddInWrapper::call_as_func`.
+    /// Parameters are passed the same way as to `call_as_proc`
+    /// # Arguments
+    /// * `method_num` - index of method
+    /// * `params` - parameters of method
+    /// * `val` - pointer to the ReturnValue object that will be used to return the value
+    /// # Returns
+    /// `AddInResult` - operation result
+    fn call_as_func(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+        val: ReturnValue,
+    ) -> AddInResult;
+
+    /// Equivalent to `SetLocale` from Native API interface, see `AddInWrapper::set_locale`
+    /// # Arguments
+    /// * `loc` - locale name in UTF-16, without NUL terminator
+    fn set_locale(&mut self, loc: &[u16]);
+
+    /// Equivalent to `SetUserInterfaceLanguageCode` from Native API interface,
+    /// see `AddInWrapper::set_user_interface_language_code`
+    /// # Arguments
+    /// * `lang` - language code in UTF-16, two letters without NUL terminator
+    fn set_user_interface_language_code(&mut self, lang: &[u16]);
+}
+
+/// Converts result of `AddInWrapper` method to `AddInResult`
+fn bool_result(success: bool) -> AddInResult {
+    match success {
+        true => Ok(()),
+        false => Err(AddInError::default()),
+    }
+}
+
+/// Appends NUL terminator to the name, `AddInWrapper` receives names with it
+fn with_nul(name: &[u16]) -> Vec<u16> {
+    name.iter().copied().chain(iter::once(0)).collect()
+}
+
+impl<T: AddInWrapper> TryAddInWrapper for T {
+    fn init(&mut self, interface: &'static Connection) -> AddInResult {
+        bool_result(AddInWrapper::init(self, interface))
+    }
+
+    fn get_info(&self) -> u16 {
+        AddInWrapper::get_info(self)
+    }
+
+    fn attach_type() -> AttachType {
+        <T as AddInWrapper>::attach_type()
+    }
+
+    fn supported_capabilities() -> AppCapabilities {
+        <T as AddInWrapper>::supported_capabilities()
+    }
+
+    fn done(&mut self) {
+        AddInWrapper::done(self)
+    }
+
+    fn register_extension_as(&mut self) -> &[u16] {
+        AddInWrapper::register_extension_as(self)
+    }
+
+    fn get_n_props(&self) -> usize {
+        AddInWrapper::get_n_props(self)
+    }
+
+    fn find_prop(&self, name: &[u16]) -> Option<usize> {
+        AddInWrapper::find_prop(self, &with_nul(name))
+    }
+
+    fn get_prop_name(&self, num: usize, alias: usize) -> Option<Vec<u16>> {
+        AddInWrapper::get_prop_name(self, num, alias)
+    }
+
+    fn get_prop_val(&self, num: usize, val: ReturnValue) -> AddInResult {
+        bool_result(AddInWrapper::get_prop_val(self, num, val))
+    }
+
+    fn set_prop_val(&mut self, num: usize, val: &ParamValue) -> AddInResult {
+        bool_result(AddInWrapper::set_prop_val(self, num, val))
+    }
+
+    fn is_prop_readable(&self, num: usize) -> bool {
+        AddInWrapper::is_prop_readable(self, num)
+    }
+
+    fn is_prop_writable(&self, num: usize) -> bool {
+        AddInWrapper::is_prop_writable(self, num)
+    }
+
+    fn get_n_methods(&self) -> usize {
+        AddInWrapper::get_n_methods(self)
+    }
+
+    fn find_method(&self, name: &[u16]) -> Option<usize> {
+        AddInWrapper::find_method(self, &with_nul(name))
+    }
+
+    fn get_method_name(&self, num: usize, alias: usize) -> Option<Vec<u16>> {
+        AddInWrapper::get_method_name(self, num, alias)
+    }
+
+    fn get_n_params(&self, num: usize) -> usize {
+        AddInWrapper::get_n_params(self, num)
+    }
+
+    fn get_param_def_value(
+        &self,
+        method_num: usize,
+        param_num: usize,
+        value: ReturnValue,
+    ) -> bool {
+        AddInWrapper::get_param_def_value(self, method_num, param_num, value)
+    }
+
+    fn has_ret_val(&self, method_num: usize) -> bool {
+        AddInWrapper::has_ret_val(self, method_num)
+    }
+
+    fn call_as_proc(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+    ) -> AddInResult {
+        let mut values = params.to_values();
+        bool_result(AddInWrapper::call_as_proc(self, method_num, &mut values))?;
+        Ok(params.write_changed(&values)?)
+    }
+
+    fn call_as_func(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+        val: ReturnValue,
+    ) -> AddInResult {
+        let mut values = params.to_values();
+        bool_result(AddInWrapper::call_as_func(
+            self,
+            method_num,
+            &mut values,
+            val,
+        ))?;
+        Ok(params.write_changed(&values)?)
+    }
+
+    fn set_locale(&mut self, loc: &[u16]) {
+        AddInWrapper::set_locale(self, &with_nul(loc))
+    }
+
+    fn set_user_interface_language_code(&mut self, lang: &[u16]) {
+        AddInWrapper::set_user_interface_language_code(self, &with_nul(lang))
+    }
+}
+
+/// `AddInMethods` trait is implemented by `#[add_in_methods]` attribute and is used
+/// by `#[derive(AddIn)]` to implement method-related part of `TryAddInWrapper`.
+/// Its methods have the same meaning as the methods of `TryAddInWrapper`
+#[doc(hidden)]
+pub trait AddInMethods {
+    /// Called from `TryAddInWrapper::init` after connection fields are set
+    fn on_init(&mut self, interface: &'static Connection) -> AddInResult;
+
+    /// Called from `TryAddInWrapper::done` before connection fields are cleared
+    fn on_done(&mut self);
+
+    fn get_n_methods(&self) -> usize;
+
+    fn find_method(&self, name: &[u16]) -> Option<usize>;
+
+    fn get_method_name(&self, num: usize, alias: usize) -> Option<Vec<u16>>;
+
+    fn get_n_params(&self, num: usize) -> usize;
+
+    fn get_param_def_value(
+        &self,
+        method_num: usize,
+        param_num: usize,
+        value: ReturnValue,
+    ) -> bool;
+
+    fn has_ret_val(&self, method_num: usize) -> bool;
+
+    fn call_as_proc(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+    ) -> AddInResult;
+
+    fn call_as_func(
+        &mut self,
+        method_num: usize,
+        params: &mut Params,
+        val: ReturnValue,
+    ) -> AddInResult;
+}
+
+/// `AddInExecutor` trait is implemented by `#[derive(AddIn)]` for a struct
+/// with a field, marked with `#[add_in_executor]`, and is used by
+/// `#[add_in_methods]` to run async methods
+#[doc(hidden)]
+pub trait AddInExecutor {
+    fn executor(&self) -> &Executor;
+}
+
+#[cfg(test)]
+mod tests {
+    use utf16_lit::{utf16, utf16_null};
+
+    use super::{AddInWrapper, TryAddInWrapper};
+    use crate::{
+        ffi::{
+            connection::Connection,
+            provided_types::{ParamValue, ReturnValue},
+        },
+        testing::TestHost,
+    };
+
+    /// `AddInWrapper`, that compares names with NUL-terminated literals
+    #[derive(Default)]
+    struct Legacy {
+        locale: Vec<u16>,
+        lang: Vec<u16>,
+    }
+
+    impl AddInWrapper for Legacy {
+        fn init(&mut self, _interface: &'static Connection) -> bool {
+            true
+        }
+
+        fn done(&mut self) {}
+
+        fn register_extension_as(&mut self) -> &[u16] {
+            &utf16_null!("Legacy")
+        }
+
+        fn get_n_props(&self) -> usize {
+            1
+        }
+
+        fn find_prop(&self, name: &[u16]) -> Option<usize> {
+            (name == utf16_null!("Value")).then_some(0)
+        }
+
+        fn get_prop_name(
+            &self,
+            _num: usize,
+            _alias: usize,
+        ) -> Option<Vec<u16>> {
+            None
+        }
+
+        fn get_prop_val(&self, _num: usize, _val: ReturnValue) -> bool {
+            false
+        }
+
+        fn set_prop_val(&mut self, _num: usize, _val: &ParamValue) -> bool {
+            false
+        }
+
+        fn is_prop_readable(&self, _num: usize) -> bool {
+            false
+        }
+
+        fn is_prop_writable(&self, _num: usize) -> bool {
+            false
+        }
+
+        fn get_n_methods(&self) -> usize {
+            1
+        }
+
+        fn find_method(&self, name: &[u16]) -> Option<usize> {
+            (name == utf16_null!("Run")).then_some(0)
+        }
+
+        fn get_method_name(
+            &self,
+            _num: usize,
+            _alias: usize,
+        ) -> Option<Vec<u16>> {
+            None
+        }
+
+        fn get_n_params(&self, _num: usize) -> usize {
+            0
+        }
+
+        fn get_param_def_value(
+            &self,
+            _method_num: usize,
+            _param_num: usize,
+            _value: ReturnValue,
+        ) -> bool {
+            false
+        }
+
+        fn has_ret_val(&self, _method_num: usize) -> bool {
+            false
+        }
+
+        fn call_as_proc(
+            &mut self,
+            _method_num: usize,
+            _params: &mut [ParamValue],
+        ) -> bool {
+            true
+        }
+
+        fn call_as_func(
+            &mut self,
+            _method_num: usize,
+            _params: &mut [ParamValue],
+            _val: ReturnValue,
+        ) -> bool {
+            false
+        }
+
+        fn set_locale(&mut self, loc: &[u16]) {
+            self.locale = loc.to_vec();
+        }
+
+        fn set_user_interface_language_code(&mut self, lang: &[u16]) {
+            self.lang = lang.to_vec();
+        }
+    }
+
+    #[test]
+    fn legacy_names_keep_nul_terminator() {
+        let mut host = TestHost::new(Legacy::default());
+        assert!(host.init());
+        assert_eq!(host.find_prop("Value"), Some(0));
+        assert_eq!(host.find_prop("Other"), None);
+        assert_eq!(host.find_method("Run"), Some(0));
+        assert_eq!(host.find_method("Other"), None);
+    }
+
+    #[test]
+    fn legacy_locale_keeps_nul_terminator() {
+        let mut addin = Legacy::default();
+        TryAddInWrapper::set_locale(&mut addin, &utf16!("ru_RU"));
+        TryAddInWrapper::set_user_interface_language_code(
+            &mut addin,
+            &utf16!("ru"),
+        );
+        assert_eq!(addin.locale, utf16_null!("ru_RU"));
+        assert_eq!(addin.lang, utf16_null!("ru"));
+    }
+}
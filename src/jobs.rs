@@ -0,0 +1,615 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, OnceLock,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    ffi::{
+        connection::Connection,
+        conversion::{param, FromParam, IntoReturn},
+        event_sender::EventSender,
+        panic_guard,
+        provided_types::{ParamRef, ParamValue, Params, ReturnValue},
+    },
+    interface::{AddInError, AddInResult},
+    names::NameRegistry,
+};
+
+/// Default name of the external event, sent when a job finishes
+const DEFAULT_EVENT_NAME: &str = "JobFinished";
+
+/// Identifier of a job, passed to 1C platform as integer Number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(i32);
+
+impl JobId {
+    /// Returns the identifier as integer, the same as seen by 1C platform
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl FromParam<'_> for JobId {
+    const EXPECTED: &'static str = "job identifier";
+
+    fn from_param(param: ParamRef<'_>) -> Option<Self> {
+        i32::from_param(param).map(Self)
+    }
+}
+
+impl IntoReturn for JobId {
+    fn into_return(self, val: ReturnValue) {
+        val.set_i32(self.0)
+    }
+}
+
+/// Status of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// Job is waiting for a free worker
+    Pending,
+    /// Job is running on a worker
+    Running,
+    /// Job finished successfully, its result can be taken
+    Completed,
+    /// Job returned an error
+    Failed,
+    /// Job was cancelled before it finished
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Name of the status, returned to 1C platform by `JobStatus` method
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pending => "Pending",
+            Self::Running => "Running",
+            Self::Completed => "Completed",
+            Self::Failed => "Failed",
+            Self::Cancelled => "Cancelled",
+        }
+    }
+
+    /// Checks if the job will not change its status anymore
+    pub fn is_finished(self) -> bool {
+        !matches!(self, Self::Pending | Self::Running)
+    }
+}
+
+/// Flag, that is set when the job is cancelled. Jobs are expected to check
+/// it periodically and return early, their result is discarded anyway
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Checks if the job was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+}
+
+/// Job, tracked by `JobManager`
+struct Job {
+    status: JobStatus,
+    token: CancelToken,
+    result: Option<AddInResult<ParamValue>>,
+}
+
+/// Jobs, shared between `JobManager` and its workers
+#[derive(Default)]
+struct Jobs {
+    jobs: HashMap<JobId, Job>,
+    next_id: i32,
+}
+
+type SharedJobs = Arc<Mutex<Jobs>>;
+type Task = Box<dyn FnOnce() + Send>;
+
+fn lock(jobs: &SharedJobs) -> MutexGuard<'_, Jobs> {
+    jobs.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Worker threads and the channel, that feeds them with tasks
+struct Pool {
+    tasks: mpsc::Sender<Task>,
+    workers: Vec<JoinHandle<()>>,
+    events: EventSender,
+    source: String,
+}
+
+/// Runs long operations of the AddIn on a pool of worker threads, so that
+/// methods, called by 1C platform, return immediately with a job
+/// identifier instead of blocking the UI.
+///
+/// When a job finishes, external event with name `JobFinished` (see
+/// `with_event_name`) and the job identifier as data is sent to 1C platform.
+/// Status and result of the job are available through methods `JobStatus`,
+/// `JobResult` and `CancelJob`, that the AddIn exposes with `find_method`,
+/// `call_method` and related functions of this type. `#[derive(AddIn)]`
+/// does it for a field marked with `#[add_in_jobs]`.
+///
+/// Workers are started by `start`, called from `init` of the AddIn, and
+/// stopped by `shutdown`, called from `done`. Outstanding jobs are cancelled
+/// and workers are joined on shutdown, so a running job, that never checks
+/// its `CancelToken`, makes `done` wait until the job finishes
+pub struct JobManager {
+    n_workers: usize,
+    event_name: String,
+    jobs: SharedJobs,
+    pool: Option<Pool>,
+}
+
+impl JobManager {
+    /// Number of methods, exposed by the job manager
+    pub const N_METHODS: usize = 3;
+
+    /// Creates job manager, whose pool has the given number of workers
+    /// # Arguments
+    /// * `n_workers` - number of worker threads, at least one is started
+    pub fn new(n_workers: usize) -> Self {
+        Self {
+            n_workers: n_workers.max(1),
+            event_name: DEFAULT_EVENT_NAME.to_owned(),
+            jobs: SharedJobs::default(),
+            pool: None,
+        }
+    }
+
+    /// Sets name of the external event, sent when a job finishes
+    pub fn with_event_name(mut self, name: &str) -> Self {
+        self.event_name = name.to_owned();
+        self
+    }
+
+    /// Starts worker threads, does nothing if they are already started
+    /// # Arguments
+    /// * `connection` - connection to 1C platform, used to send events
+    /// * `source` - source of the events, usually the name of the AddIn
+    pub fn start(&mut self, connection: &'static Connection, source: &str) {
+        if self.pool.is_some() {
+            return;
+        }
+        let (tasks, receiver) = mpsc::channel::<Task>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..self.n_workers)
+            .map(|_| {
+                let receiver = receiver.clone();
+                thread::spawn(move || loop {
+                    let task = receiver
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .recv();
+                    match task {
+                        Ok(task) => task(),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        self.pool = Some(Pool {
+            tasks,
+            workers,
+            events: connection.event_sender(),
+            source: source.to_owned(),
+        });
+    }
+
+    /// Cancels outstanding jobs and joins worker threads. Results of jobs,
+    /// finished before shutdown, can still be taken. Blocks until running
+    /// jobs return, cancellation only sets their `CancelToken`
+    pub fn shutdown(&mut self) {
+        let Some(pool) = self.pool.take() else {
+            return;
+        };
+        for job in lock(&self.jobs).jobs.values_mut() {
+            cancel(job);
+        }
+        drop(pool.tasks);
+        for worker in pool.workers {
+            let _ = worker.join();
+        }
+    }
+
+    /// Starts a job on the worker pool
+    /// # Arguments
+    /// * `job` - closure, that does the work and returns its result. It
+    ///   receives the token, that is set when the job is cancelled
+    /// # Returns
+    /// `AddInResult<JobId>` - identifier of the job, or error if workers
+    /// are not started
+    pub fn spawn<F>(&self, job: F) -> AddInResult<JobId>
+    where
+        F: FnOnce(&CancelToken) -> AddInResult<ParamValue> + Send + 'static,
+    {
+        let Some(pool) = &self.pool else {
+            return Err(AddInError::new("job manager is not started"));
+        };
+        let token = CancelToken::default();
+        let id = {
+            let mut jobs = lock(&self.jobs);
+            // After wrapping around, identifiers of jobs, whose results
+            // were not taken yet, are skipped
+            let id = loop {
+                jobs.next_id = jobs.next_id.wrapping_add(1).max(1);
+                let id = JobId(jobs.next_id);
+                if !jobs.jobs.contains_key(&id) {
+                    break id;
+                }
+            };
+            jobs.jobs.insert(
+                id,
+                Job {
+                    status: JobStatus::Pending,
+                    token: token.clone(),
+                    result: None,
+                },
+            );
+            id
+        };
+
+        let jobs = self.jobs.clone();
+        let events = pool.events.clone();
+        let source = pool.source.clone();
+        let event_name = self.event_name.clone();
+        let task = move || {
+            if !set_status(&jobs, id, JobStatus::Running) {
+                return;
+            }
+            let result =
+                panic_guard::catch(|| job(&token)).unwrap_or_else(|caught| {
+                    Err(AddInError::new(caught.to_string()))
+                });
+            let status = match (&result, token.is_cancelled()) {
+                (_, true) => JobStatus::Cancelled,
+                (Ok(_), false) => JobStatus::Completed,
+                (Err(_), false) => JobStatus::Failed,
+            };
+            if let Some(job) = lock(&jobs).jobs.get_mut(&id) {
+                job.status = status;
+                job.result = Some(result);
+            }
+            if status != JobStatus::Cancelled {
+                let _ = events.send(&source, &event_name, &id.to_string());
+            }
+        };
+        if pool.tasks.send(Box::new(task)).is_err() {
+            lock(&self.jobs).jobs.remove(&id);
+            return Err(AddInError::new("job manager is stopped"));
+        }
+        Ok(id)
+    }
+
+    /// Returns status of the job
+    /// # Returns
+    /// `Option<JobStatus>` - status or None if there is no such job or
+    /// its result was already taken
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        lock(&self.jobs).jobs.get(&id).map(|job| job.status)
+    }
+
+    /// Takes result of a finished job and forgets the job
+    /// # Returns
+    /// `Option<AddInResult<ParamValue>>` - result or None if the job is not
+    /// finished or does not exist. Result of a cancelled job is an error
+    pub fn take_result(&self, id: JobId) -> Option<AddInResult<ParamValue>> {
+        let mut jobs = lock(&self.jobs);
+        if !jobs.jobs.get(&id)?.status.is_finished() {
+            return None;
+        }
+        let job = jobs.jobs.remove(&id)?;
+        match job.status {
+            JobStatus::Cancelled => {
+                Some(Err(AddInError::new(format!("job {id} was cancelled"))))
+            }
+            _ => job.result,
+        }
+    }
+
+    /// Cancels the job. Pending job is not started, running job is
+    /// expected to check its `CancelToken`
+    /// # Returns
+    /// `bool` - if the job exists and was not finished
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut jobs = lock(&self.jobs);
+        let Some(job) = jobs.jobs.get_mut(&id) else {
+            return false;
+        };
+        match job.status.is_finished() {
+            true => false,
+            false => {
+                cancel(job);
+                true
+            }
+        }
+    }
+
+    /// Searches for a method of the job manager, see `NameRegistry::find`
+    pub fn find_method(name: &[u16]) -> Option<usize> {
+        method_names().find(name)
+    }
+
+    /// Returns name of a method of the job manager,
+    /// see `NameRegistry::get_name`
+    pub fn get_method_name(num: usize, alias: usize) -> Option<&'static [u16]> {
+        method_names().get_name(num, alias)
+    }
+
+    /// Returns number of parameters of a method of the job manager, each
+    /// method takes the job identifier
+    pub fn get_n_params(num: usize) -> usize {
+        match num < Self::N_METHODS {
+            true => 1,
+            false => 0,
+        }
+    }
+
+    /// Calls a method of the job manager, all methods return a value:
+    /// * `JobStatus(Id)` - name of `JobStatus`, or `Undefined` for unknown
+    ///   job
+    /// * `JobResult(Id)` - result of the finished job, the job is forgotten.
+    ///   Fails, if the job is not finished, failed or was cancelled
+    /// * `CancelJob(Id)` - if the job was cancelled
+    /// # Arguments
+    /// * `num` - index of the method
+    /// * `params` - parameters of the call
+    /// # Returns
+    /// `AddInResult<ParamValue>` - value, returned by the method
+    pub fn call_method(
+        &self,
+        num: usize,
+        params: &Params,
+    ) -> AddInResult<ParamValue> {
+        let name = Self::get_method_name(num, 1)
+            .map(String::from_utf16_lossy)
+            .unwrap_or_default();
+        if params.len() != Self::get_n_params(num) {
+            return Err(AddInError::new(format!(
+                "{name}: expected 1 parameters, found {}",
+                params.len(),
+            )));
+        }
+        let id: JobId = param(params, 0)
+            .map_err(|err| AddInError::new(format!("{name}: {err}")))?;
+        match num {
+            0 => Ok(self.status(id).map_or(ParamValue::Empty, |status| {
+                ParamValue::Str(status.name().encode_utf16().collect())
+            })),
+            1 => match self.take_result(id) {
+                Some(result) => result,
+                None => Err(AddInError::new(format!(
+                    "{name}: job {id} is not finished or does not exist",
+                ))),
+            },
+            2 => Ok(ParamValue::Bool(self.cancel(id))),
+            _ => Err(AddInError::new(format!("method #{num} not found"))),
+        }
+    }
+}
+
+impl Default for JobManager {
+    /// Creates job manager with a worker per available CPU
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+impl Drop for JobManager {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Names of the methods of the job manager
+fn method_names() -> &'static NameRegistry {
+    static NAMES: OnceLock<NameRegistry> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        [
+            ("JobStatus", "СтатусЗадания"),
+            ("JobResult", "РезультатЗадания"),
+            ("CancelJob", "ОтменитьЗадание"),
+        ]
+        .into_iter()
+        .collect()
+    })
+}
+
+/// Changes status of a pending job
+/// # Returns
+/// `bool` - if the job was pending, cancelled jobs are not started
+fn set_status(jobs: &SharedJobs, id: JobId, status: JobStatus) -> bool {
+    let mut jobs = lock(jobs);
+    match jobs.jobs.get_mut(&id) {
+        Some(job) if job.status == JobStatus::Pending => {
+            job.status = status;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Sets cancellation flag of the job, pending job is marked as cancelled
+/// right away, as it will not be started
+fn cancel(job: &mut Job) {
+    job.token.cancel();
+    if job.status == JobStatus::Pending {
+        job.status = JobStatus::Cancelled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        rc::Rc,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{lock, JobId, JobManager, JobStatus};
+    use crate::{
+        dynamic::DynamicAddIn,
+        ffi::provided_types::{ParamValue, Params},
+        interface::AddInError,
+        testing::TestHost,
+    };
+
+    /// Starts the job manager with `init` of a test AddIn and stops it
+    /// with `done`
+    fn host(manager: &Rc<RefCell<JobManager>>) -> TestHost {
+        let addin = DynamicAddIn::new("Jobs")
+            .on_init({
+                let manager = manager.clone();
+                move |connection| {
+                    manager.borrow_mut().start(connection, "Jobs");
+                    Ok(())
+                }
+            })
+            .on_done({
+                let manager = manager.clone();
+                move || manager.borrow_mut().shutdown()
+            });
+        let mut host = TestHost::new(addin);
+        assert!(host.init());
+        host
+    }
+
+    fn wait_for(manager: &JobManager, id: JobId, status: JobStatus) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while manager.status(id) != Some(status) {
+            assert!(Instant::now() < deadline, "job {id} is not {status:?}");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn call(manager: &JobManager, num: usize, id: JobId) -> ParamValue {
+        let mut values = [ParamValue::I32(id.get())];
+        let params = Params::from(&mut values[..]);
+        manager.call_method(num, &params).unwrap()
+    }
+
+    #[test]
+    fn jobs_report_status_result_and_event() {
+        let manager = Rc::new(RefCell::new(JobManager::new(1)));
+        let mut host = host(&manager);
+        let (release, released) = mpsc::channel::<()>();
+        let (first, pending, failed) = {
+            let manager = manager.borrow();
+            let first = manager
+                .spawn(move |_| {
+                    released.recv()?;
+                    Ok(ParamValue::I32(42))
+                })
+                .unwrap();
+            let pending = manager.spawn(|_| Ok(ParamValue::Null)).unwrap();
+            let failed = manager
+                .spawn(|_| Err(AddInError::new("job failed")))
+                .unwrap();
+            (first, pending, failed)
+        };
+
+        {
+            let manager = manager.borrow();
+            wait_for(&manager, first, JobStatus::Running);
+            assert_eq!(manager.status(pending), Some(JobStatus::Pending));
+            assert!(matches!(
+                call(&manager, 0, pending),
+                ParamValue::Str(s) if String::from_utf16_lossy(&s) == "Pending"
+            ));
+            assert!(manager.take_result(first).is_none());
+
+            assert!(manager.cancel(pending));
+            assert_eq!(manager.status(pending), Some(JobStatus::Cancelled));
+            assert!(!manager.cancel(pending));
+
+            release.send(()).unwrap();
+            wait_for(&manager, first, JobStatus::Completed);
+            wait_for(&manager, failed, JobStatus::Failed);
+            assert!(!manager.cancel(first));
+
+            assert!(matches!(call(&manager, 1, first), ParamValue::I32(42)));
+            assert_eq!(manager.status(first), None);
+            assert!(matches!(call(&manager, 0, first), ParamValue::Empty));
+            let err = manager.take_result(failed).unwrap().unwrap_err();
+            assert_eq!(err.description, "job failed");
+            let err = manager.take_result(pending).unwrap().unwrap_err();
+            assert_eq!(err.description, format!("job {pending} was cancelled"));
+        }
+
+        // Cancelled job does not send the event
+        let mut data = host
+            .connection()
+            .events()
+            .into_iter()
+            .map(|event| (event.source, event.name, event.data))
+            .collect::<Vec<_>>();
+        data.sort();
+        let event =
+            |id: JobId| ("Jobs".into(), "JobFinished".into(), id.to_string());
+        let mut expected = vec![event(first), event(failed)];
+        expected.sort();
+        assert_eq!(data, expected);
+        host.done();
+    }
+
+    #[test]
+    fn running_job_is_cancelled() {
+        let manager = Rc::new(RefCell::new(JobManager::new(1)));
+        let mut host = host(&manager);
+        let id = manager
+            .borrow()
+            .spawn(|token| {
+                while !token.is_cancelled() {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Ok(ParamValue::Empty)
+            })
+            .unwrap();
+        {
+            let manager = manager.borrow();
+            wait_for(&manager, id, JobStatus::Running);
+            assert!(matches!(call(&manager, 2, id), ParamValue::Bool(true)));
+            wait_for(&manager, id, JobStatus::Cancelled);
+            assert!(manager.take_result(id).unwrap().is_err());
+        }
+        host.done();
+        assert!(host.connection().events().is_empty());
+    }
+
+    #[test]
+    fn ids_skip_unfinished_jobs() {
+        let manager = Rc::new(RefCell::new(JobManager::new(1)));
+        let mut host = host(&manager);
+        {
+            let manager = manager.borrow();
+            let first = manager.spawn(|_| Ok(ParamValue::Empty)).unwrap();
+            assert_eq!(first.get(), 1);
+            lock(&manager.jobs).next_id = i32::MAX - 1;
+            let last = manager.spawn(|_| Ok(ParamValue::Empty)).unwrap();
+            assert_eq!(last.get(), i32::MAX);
+            // Result of the first job is not taken, so its id is skipped
+            let wrapped = manager.spawn(|_| Ok(ParamValue::Empty)).unwrap();
+            assert_eq!(wrapped.get(), 2);
+        }
+        host.done();
+        assert!(manager.borrow().spawn(|_| Ok(ParamValue::Empty)).is_err());
+    }
+}
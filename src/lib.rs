@@ -7,7 +7,23 @@
 //! While it is possible to use this crate to implement your Native API
 //! Component, it is intended to be used with native_api_1c crate.
 
+/// Module for AddIns, whose members are registered at runtime
+pub mod dynamic;
+/// Module for running async methods of AddIns
+pub mod executor;
 /// Module for implementations of Native API FFI
 pub mod ffi;
 /// Module for high level interface of Native API
 pub mod interface;
+/// Module for running long operations of AddIns on background threads
+pub mod jobs;
+/// Module for `log` crate backend, that shows records in 1C platform
+#[cfg(feature = "log")]
+pub mod logger;
+/// Module for case-insensitive bilingual names of AddIn properties and methods
+pub mod names;
+/// Module for testing AddIns without 1C platform
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub use native_api_1c_core_derive::{add_in_methods, AddIn};
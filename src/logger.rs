@@ -0,0 +1,318 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    ptr,
+    sync::{Mutex, MutexGuard},
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::ffi::connection::{Connection, MessageCode};
+
+/// Maximal number of records, logged from other threads and waiting for
+/// delivery to 1C platform
+const PENDING_CAPACITY: usize = 256;
+
+/// Connections to 1C platform of initialized AddIn objects and the threads,
+/// that can use them. The logger uses the most recently attached one
+static CONNECTIONS: Mutex<Vec<(&'static Connection, ThreadId)>> =
+    Mutex::new(Vec::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Passes connection to 1C platform to the logger, called after successful
+/// `init` of the AddIn. Records are delivered with the connection only on
+/// the calling thread, records from other threads wait for the next record
+/// on this thread, `log::logger().flush()` or `detach`
+pub fn attach(connection: &'static Connection) {
+    lock(&CONNECTIONS).push((connection, thread::current().id()));
+}
+
+/// Delivers waiting records and stops using the connection, called after
+/// `done` of the AddIn or when its object is destroyed. If other AddIn
+/// objects are alive, the logger returns to the connection of the most
+/// recently initialized one
+pub fn detach(connection: &Connection) {
+    log::logger().flush();
+    lock(&CONNECTIONS).retain(|(attached, _)| !ptr::eq(*attached, connection));
+}
+
+/// Returns the connection, used by the logger
+fn current() -> Option<(&'static Connection, ThreadId)> {
+    lock(&CONNECTIONS).last().copied()
+}
+
+/// Where records go, when there is no connection to 1C platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fallback {
+    /// Records are discarded
+    None,
+    /// Records are written to standard error stream
+    Stderr,
+    /// Records are appended to the file
+    File(PathBuf),
+}
+
+/// Record, prepared for delivery
+struct Entry {
+    level: Level,
+    target: String,
+    message: String,
+}
+
+/// Mutable state of the logger
+/// # Fields
+/// * `window_start` - start of the current rate limit window
+/// * `in_window` - number of records, delivered in the current window
+/// * `suppressed` - number of records, dropped by rate limit or because
+///   of full `pending` queue
+/// * `pending` - records from other threads, waiting for delivery
+/// * `file` - opened fallback file
+#[derive(Default)]
+struct State {
+    window_start: Option<Instant>,
+    in_window: usize,
+    suppressed: usize,
+    pending: Vec<Entry>,
+    file: Option<File>,
+}
+
+/// Implementation of `log::Log`, that shows records in the message window
+/// of 1C platform with `Connection::add_error`, optionally mirrors them to
+/// the status line, limits rate of records and writes them to the fallback
+/// before the connection is attached.
+///
+/// Connection is attached and detached by `init` and `done` of the AddIn,
+/// so only one logger has to be installed per library, usually from
+/// `init_my_add_in` or the first `init`
+pub struct Logger {
+    level: LevelFilter,
+    codes: [MessageCode; 5],
+    status_line: bool,
+    rate_limit: Option<(usize, Duration)>,
+    fallback: Fallback,
+    state: Mutex<State>,
+}
+
+impl Logger {
+    /// Creates logger with default settings: level `Info`, no status line,
+    /// at most 20 records per second and `Fallback::Stderr`. Levels are
+    /// mapped to message codes as `Error` - `Fail`, `Warn` - `Important`,
+    /// `Info` - `Attention`, `Debug` and `Trace` - `Info`
+    pub fn new() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            codes: [
+                MessageCode::Fail,
+                MessageCode::Important,
+                MessageCode::Attention,
+                MessageCode::Info,
+                MessageCode::Info,
+            ],
+            status_line: false,
+            rate_limit: Some((20, Duration::from_secs(1))),
+            fallback: Fallback::Stderr,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Sets maximal level of logged records
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets message code, used for records of the level
+    pub fn with_message_code(
+        mut self,
+        level: Level,
+        code: MessageCode,
+    ) -> Self {
+        self.codes[level as usize - 1] = code;
+        self
+    }
+
+    /// Enables showing the last record in the status line of 1C platform
+    pub fn with_status_line(mut self, enabled: bool) -> Self {
+        self.status_line = enabled;
+        self
+    }
+
+    /// Sets maximal number of records per interval, excess records are
+    /// dropped and their number is reported with the next delivered record
+    /// # Arguments
+    /// * `limit` - maximal number of records and the interval, None
+    ///   disables the limit
+    pub fn with_rate_limit(mut self, limit: Option<(usize, Duration)>) -> Self {
+        self.rate_limit = limit;
+        self
+    }
+
+    /// Sets where records go before the connection is attached
+    pub fn with_fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Installs the logger as the global logger of `log` crate
+    /// # Returns
+    /// `Result<(), SetLoggerError>` - error if the global logger is already
+    /// installed
+    pub fn install(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    /// Checks the rate limit for a new record
+    /// # Returns
+    /// `bool` - if the record can be delivered
+    fn admit(&self, state: &mut State) -> bool {
+        let Some((limit, interval)) = self.rate_limit else {
+            return true;
+        };
+        let now = Instant::now();
+        match state.window_start {
+            Some(start) if now.duration_since(start) < interval => {}
+            _ => {
+                state.window_start = Some(now);
+                state.in_window = 0;
+            }
+        }
+        if state.in_window >= limit {
+            state.suppressed += 1;
+            return false;
+        }
+        state.in_window += 1;
+        true
+    }
+
+    /// Takes the record about suppressed records, if there are any
+    fn take_suppressed(state: &mut State) -> Option<Entry> {
+        if state.suppressed == 0 {
+            return None;
+        }
+        let suppressed = Entry {
+            level: Level::Warn,
+            target: String::new(),
+            message: format!("{} records suppressed", state.suppressed),
+        };
+        state.suppressed = 0;
+        Some(suppressed)
+    }
+
+    /// Takes waiting records, preceded by the number of suppressed records,
+    /// for delivery with the connection
+    fn take_pending(state: &mut State) -> Vec<Entry> {
+        let pending = std::mem::take(&mut state.pending);
+        Self::take_suppressed(state)
+            .into_iter()
+            .chain(pending)
+            .collect()
+    }
+
+    /// Delivers the entries with the connection. It is called without
+    /// the lock of the state, so records, logged by 1C platform calls,
+    /// do not deadlock
+    fn deliver(&self, connection: &Connection, entries: Vec<Entry>) {
+        for entry in entries {
+            let code = self.codes[entry.level as usize - 1];
+            connection.add_error(code, &entry.target, &entry.message);
+            if self.status_line {
+                connection.set_status_line(&entry.message);
+            }
+        }
+    }
+
+    /// Writes the entry to the fallback, reporting suppressed records first
+    fn write_fallback(&self, state: &mut State, entry: &Entry) {
+        if let Some(suppressed) = Self::take_suppressed(state) {
+            self.write(state, &suppressed);
+        }
+        self.write(state, entry);
+    }
+
+    fn write(&self, state: &mut State, entry: &Entry) {
+        let line =
+            format!("{} {}: {}\n", entry.level, entry.target, entry.message);
+        let _ = match &self.fallback {
+            Fallback::None => Ok(()),
+            Fallback::Stderr => io::stderr().write_all(line.as_bytes()),
+            Fallback::File(path) => {
+                if state.file.is_none() {
+                    state.file = OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)
+                        .ok();
+                }
+                match &mut state.file {
+                    Some(file) => file.write_all(line.as_bytes()),
+                    None => Ok(()),
+                }
+            }
+        };
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let connection = current();
+        let entry = Entry {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+        };
+        let mut state = lock(&self.state);
+        if !self.admit(&mut state) {
+            return;
+        }
+        match connection {
+            Some((connection, owner)) if owner == thread::current().id() => {
+                state.pending.push(entry);
+                let entries = Self::take_pending(&mut state);
+                drop(state);
+                self.deliver(connection, entries);
+            }
+            Some(_) if state.pending.len() < PENDING_CAPACITY => {
+                state.pending.push(entry)
+            }
+            Some(_) => state.suppressed += 1,
+            None => self.write_fallback(&mut state, &entry),
+        }
+    }
+
+    fn flush(&self) {
+        let connection = current();
+        let mut state = lock(&self.state);
+        if let Some(file) = &mut state.file {
+            let _ = file.flush();
+        }
+        if let Some((connection, owner)) = connection {
+            if owner == thread::current().id() {
+                let entries = Self::take_pending(&mut state);
+                drop(state);
+                self.deliver(connection, entries);
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/// Bilingual name of a property or a method, stored in UTF-16
+/// # Fields
+/// * `name` - English name
+/// * `name_ru` - Russian name
+/// * `folded` - case folded English name, used for lookup
+/// * `folded_ru` - case folded Russian name, used for lookup
+struct Entry {
+    name: Vec<u16>,
+    name_ru: Vec<u16>,
+    folded: Vec<u16>,
+    folded_ru: Vec<u16>,
+}
+
+/// Registry of bilingual names of AddIn properties or methods. Names are
+/// compared ignoring case of Latin and Cyrillic letters, as 1C platform
+/// does. Names are encoded and case folded once, when added, so `find` and
+/// `get_name` do not allocate
+#[derive(Default)]
+pub struct NameRegistry {
+    entries: Vec<Entry>,
+}
+
+impl NameRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a name to the registry
+    /// # Arguments
+    /// * `name` - English name
+    /// * `name_ru` - Russian name
+    /// # Returns
+    /// `usize` - index of the name, that is returned by `find`
+    pub fn add(&mut self, name: &str, name_ru: &str) -> usize {
+        let name = name.encode_utf16().collect::<Vec<_>>();
+        let name_ru = name_ru.encode_utf16().collect::<Vec<_>>();
+        self.entries.push(Entry {
+            folded: name.iter().copied().map(fold_case).collect(),
+            folded_ru: name_ru.iter().copied().map(fold_case).collect(),
+            name,
+            name_ru,
+        });
+        self.entries.len() - 1
+    }
+
+    /// Returns the number of names in the registry
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks if the registry has no names
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Searches for the name among both aliases ignoring case, suitable for
+    /// `find_prop` and `find_method`
+    /// # Arguments
+    /// * `name` - name in UTF-16, trailing NUL terminator is ignored
+    /// # Returns
+    /// `Option<usize>` - index of the first matching name or None if
+    /// the name was not found
+    pub fn find(&self, name: &[u16]) -> Option<usize> {
+        let name = name.strip_suffix(&[0]).unwrap_or(name);
+        self.entries.iter().position(|entry| {
+            eq_folded(&entry.folded, name) || eq_folded(&entry.folded_ru, name)
+        })
+    }
+
+    /// Returns the name with the given index, suitable for `get_prop_name`
+    /// and `get_method_name`
+    /// # Arguments
+    /// * `num` - index of the name
+    /// * `alias` - `0` for Russian name and any other value for English one
+    /// # Returns
+    /// `Option<&[u16]>` - name in UTF-16 without NUL terminator or None
+    /// if there is no name with the given index
+    pub fn get_name(&self, num: usize, alias: usize) -> Option<&[u16]> {
+        let entry = self.entries.get(num)?;
+        match alias {
+            0 => Some(&entry.name_ru),
+            _ => Some(&entry.name),
+        }
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a str)> for NameRegistry {
+    fn from_iter<I: IntoIterator<Item = (&'a str, &'a str)>>(iter: I) -> Self {
+        let mut registry = Self::new();
+        for (name, name_ru) in iter {
+            registry.add(name, name_ru);
+        }
+        registry
+    }
+}
+
+/// Compares case folded name with a name in any case
+fn eq_folded(folded: &[u16], name: &[u16]) -> bool {
+    folded.len() == name.len()
+        && folded.iter().zip(name).all(|(&a, &b)| a == fold_case(b))
+}
+
+/// Simple Unicode case folding of Latin and Cyrillic letters. Other UTF-16
+/// code units, including surrogates, are returned unchanged
+fn fold_case(c: u16) -> u16 {
+    match c {
+        // Basic Latin
+        0x41..=0x5A => c + 0x20,
+        // Latin-1 Supplement
+        0xB5 => 0x3BC,
+        0xC0..=0xD6 | 0xD8..=0xDE => c + 0x20,
+        // Latin Extended-A, pairs of upper and lower case letters
+        0x100..=0x12F | 0x132..=0x137 | 0x14A..=0x177 if is_even(c) => c + 1,
+        0x139..=0x148 | 0x179..=0x17E if !is_even(c) => c + 1,
+        0x178 => 0xFF,
+        0x17F => 0x73,
+        // Cyrillic
+        0x400..=0x40F => c + 0x50,
+        0x410..=0x42F => c + 0x20,
+        0x460..=0x481 | 0x48A..=0x4BF | 0x4D0..=0x52F if is_even(c) => c + 1,
+        0x4C0 => 0x4CF,
+        0x4C1..=0x4CE if !is_even(c) => c + 1,
+        _ => c,
+    }
+}
+
+/// Upper case letters of the paired ranges have even or odd code depending
+/// on the range
+fn is_even(c: u16) -> bool {
+    c & 1 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold_case, NameRegistry};
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn cyrillic_io() {
+        assert_eq!(fold_case(0x401), 0x451);
+        assert_eq!(fold_case(0x451), 0x451);
+        assert_eq!(fold_case(0x415), 0x435);
+        let registry = [("Elka", "Ёлка")].into_iter().collect::<NameRegistry>();
+        assert_eq!(registry.find(&utf16("ёЛКА")), Some(0));
+        // Ё and Е are different letters
+        assert_eq!(registry.find(&utf16("Елка")), None);
+    }
+
+    #[test]
+    fn latin_pairs() {
+        let pairs = [
+            // Latin-1 Supplement
+            (0xC0, 0xE0),
+            (0xC4, 0xE4),
+            (0xD6, 0xF6),
+            (0xD8, 0xF8),
+            (0xDE, 0xFE),
+            // Latin Extended-A
+            (0x100, 0x101),
+            (0x12E, 0x12F),
+            (0x132, 0x133),
+            (0x139, 0x13A),
+            (0x147, 0x148),
+            (0x14A, 0x14B),
+            (0x176, 0x177),
+            (0x178, 0xFF),
+            (0x179, 0x17A),
+            (0x17D, 0x17E),
+        ];
+        for (upper, lower) in pairs {
+            assert_eq!(fold_case(upper), lower, "{upper:#x}");
+            assert_eq!(fold_case(lower), lower, "{lower:#x}");
+        }
+        // Multiplication and division signs are not letters
+        assert_eq!(fold_case(0xD7), 0xD7);
+        assert_eq!(fold_case(0xF7), 0xF7);
+        // Special foldings
+        assert_eq!(fold_case(0xB5), 0x3BC);
+        assert_eq!(fold_case(0x17F), 0x73);
+        assert_eq!(fold_case(0xD834), 0xD834);
+    }
+
+    #[test]
+    fn matches_lowercase() {
+        // Latin up to Latin Extended-A and Cyrillic with its Supplement
+        let ranges = [0x0..=0x17F, 0x400..=0x52F];
+        for c in ranges.into_iter().flatten() {
+            // Simple folding of these letters differs from lowercase
+            if matches!(c, 0xB5 | 0x17F) {
+                continue;
+            }
+            let mut lower = char::from_u32(c).unwrap().to_lowercase();
+            let expected = match (lower.next(), lower.next()) {
+                (Some(lower), None) => lower as u16,
+                // Letters with multi-character lowercase are not folded
+                _ => c as u16,
+            };
+            assert_eq!(fold_case(c as u16), expected, "{c:#x}");
+        }
+    }
+}
@@ -0,0 +1,1266 @@
+//!
+//! In-process replacement for 1C platform, that allows to drive AddIns
+//! through the same vtables 1C platform uses. Interfaces, provided by the
+//! platform (`IAddInDefBaseEx`, `IMemoryManager`, `IMsgBox`,
+//! `IPlatformInfo`, `IAttachedInfo`), are implemented in Rust and record calls, made by the AddIn, so they can be
+//! checked in tests.
+//!
+//! The module is available with `testing` feature, usually enabled only for
+//! dev-dependencies, so the mock host is not compiled into the AddIn library.
+//!
+//! ```ignore
+//! let mut host = TestHost::new(MyAddIn::default());
+//! assert!(host.init());
+//! let method = host.find_method("Sum").unwrap();
+//! let mut params = [ParamValue::I32(1), ParamValue::I32(2)];
+//! assert!(host.call_as_func(method, &mut params) == Some(ParamValue::I32(3)));
+//! ```
+//!
+use std::{
+    alloc::{self, Layout},
+    collections::HashMap,
+    ffi::{c_int, c_long, c_ulong, c_ushort, c_void},
+    ptr,
+    sync::Mutex,
+};
+
+use crate::{
+    ffi::{
+        attached_info::AttachedType,
+        connection::Connection,
+        create_component, destroy_component,
+        exports::set_platform_capabilities,
+        memory_manager::{MemoryManager, PlatformStr},
+        platform_info::ApplicationType,
+        provided_types::{ParamValue, TVariant},
+        string_utils::{WStr, WString},
+        AppCapabilities,
+    },
+    interface::TryAddInWrapper,
+};
+
+/// Call of `Connection::add_error`, recorded by `TestConnection`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddedError {
+    /// Message code, see [MessageCode](crate::ffi::connection::MessageCode)
+    pub code: u16,
+    /// Source of the error
+    pub source: String,
+    /// Description of the error
+    pub description: String,
+}
+
+/// Call of `Connection::external_event`, recorded by `TestConnection`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalEvent {
+    /// Name of the event caller
+    pub source: String,
+    /// Name of the event
+    pub name: String,
+    /// Data of the event
+    pub data: String,
+}
+
+#[derive(Default)]
+struct ConnectionState {
+    errors: Vec<AddedError>,
+    events: Vec<ExternalEvent>,
+    event_buffer_depth: c_long,
+    reject_events: bool,
+    status_line: Option<String>,
+    profile: Option<String>,
+    profiles: HashMap<String, HashMap<String, ParamValue>>,
+}
+
+#[derive(Default)]
+struct MsgBoxState {
+    alerts: Vec<String>,
+    confirmations: Vec<String>,
+    confirm_answer: Option<bool>,
+}
+
+/// VTable of `IMsgBox`, as seen by 1C platform
+#[repr(C)]
+struct TestMsgBoxVTable {
+    confirm: unsafe extern "system" fn(
+        &TestMsgBox,
+        *const u16,
+        *mut TVariant,
+    ) -> bool,
+    alert: unsafe extern "system" fn(&TestMsgBox, *const u16) -> bool,
+}
+
+unsafe extern "system" fn confirm(
+    this: &TestMsgBox,
+    query_text: *const u16,
+    answer: *mut TVariant,
+) -> bool {
+    let mut state = this.state();
+    state.confirmations.push(read_str(query_text));
+    let Some(confirm_answer) = state.confirm_answer else {
+        return false;
+    };
+    (*answer).update_to_bool(confirm_answer);
+    true
+}
+
+unsafe extern "system" fn alert(this: &TestMsgBox, text: *const u16) -> bool {
+    this.state().alerts.push(read_str(text));
+    true
+}
+
+static TEST_MSG_BOX_VTABLE: TestMsgBoxVTable =
+    TestMsgBoxVTable { confirm, alert };
+
+/// Rust implementation of `IMsgBox`, returned from `GetInterface`
+/// of `TestConnection`
+#[repr(C)]
+struct TestMsgBox {
+    vptr: &'static TestMsgBoxVTable,
+    state: Mutex<MsgBoxState>,
+}
+
+impl TestMsgBox {
+    fn state(&self) -> std::sync::MutexGuard<'_, MsgBoxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// `IPlatformInfo::AppInfo`, as seen by 1C platform
+#[repr(C)]
+struct TestAppInfo {
+    app_version: *const u16,
+    application: c_int,
+    user_agent_information: *const u16,
+}
+
+struct PlatformInfoState {
+    version: WString,
+    user_agent: Option<WString>,
+    app_info: TestAppInfo,
+}
+
+/// VTable of `IPlatformInfo`, as seen by 1C platform
+#[repr(C)]
+struct TestPlatformInfoVTable {
+    get_platform_info:
+        unsafe extern "system" fn(&TestPlatformInfo) -> *const TestAppInfo,
+}
+
+unsafe extern "system" fn get_platform_info(
+    this: &TestPlatformInfo,
+) -> *const TestAppInfo {
+    let mut state = this.state();
+    let state = &mut *state;
+    state.app_info.app_version = state.version.as_ptr();
+    state.app_info.user_agent_information = match &state.user_agent {
+        Some(user_agent) => user_agent.as_ptr(),
+        None => ptr::null(),
+    };
+    &state.app_info
+}
+
+static TEST_PLATFORM_INFO_VTABLE: TestPlatformInfoVTable =
+    TestPlatformInfoVTable { get_platform_info };
+
+/// Rust implementation of `IPlatformInfo`, returned from `GetInterface`
+/// of `TestConnection`
+#[repr(C)]
+struct TestPlatformInfo {
+    vptr: &'static TestPlatformInfoVTable,
+    state: Mutex<PlatformInfoState>,
+}
+
+impl TestPlatformInfo {
+    fn state(&self) -> std::sync::MutexGuard<'_, PlatformInfoState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// VTable of `IAttachedInfo`, as seen by 1C platform
+#[repr(C)]
+struct TestAttachedInfoVTable {
+    get_attached_info: unsafe extern "system" fn(&TestAttachedInfo) -> c_int,
+}
+
+unsafe extern "system" fn get_attached_info(this: &TestAttachedInfo) -> c_int {
+    *this.attached.lock().unwrap_or_else(|e| e.into_inner()) as c_int
+}
+
+static TEST_ATTACHED_INFO_VTABLE: TestAttachedInfoVTable =
+    TestAttachedInfoVTable { get_attached_info };
+
+/// Rust implementation of `IAttachedInfo`, returned from `GetInterface`
+/// of `TestConnection`
+#[repr(C)]
+struct TestAttachedInfo {
+    vptr: &'static TestAttachedInfoVTable,
+    attached: Mutex<AttachedType>,
+}
+
+/// VTable of `IAddInDefBaseEx`, as seen by 1C platform
+#[repr(C)]
+struct TestConnectionVTable {
+    dtor: usize,
+    #[cfg(target_family = "unix")]
+    dtor2: usize,
+    add_error: unsafe extern "system" fn(
+        &TestConnection,
+        c_ushort,
+        *const u16,
+        *const u16,
+        c_long,
+    ) -> bool,
+    read: unsafe extern "system" fn(
+        &TestConnection,
+        *mut u16,
+        *mut TVariant,
+        *mut c_long,
+        *mut *mut u16,
+    ) -> bool,
+    write: unsafe extern "system" fn(
+        &TestConnection,
+        *mut u16,
+        *mut TVariant,
+    ) -> bool,
+    register_profile_as:
+        unsafe extern "system" fn(&TestConnection, *mut u16) -> bool,
+    set_event_buffer_depth:
+        unsafe extern "system" fn(&TestConnection, c_long) -> bool,
+    get_event_buffer_depth:
+        unsafe extern "system" fn(&TestConnection) -> c_long,
+    external_event: unsafe extern "system" fn(
+        &TestConnection,
+        *mut u16,
+        *mut u16,
+        *mut u16,
+    ) -> bool,
+    clean_event_buffer: unsafe extern "system" fn(&TestConnection),
+    set_status_line:
+        unsafe extern "system" fn(&TestConnection, *mut u16) -> bool,
+    reset_status_line: unsafe extern "system" fn(&TestConnection),
+    get_interface:
+        unsafe extern "system" fn(&TestConnection, c_int) -> *mut c_void,
+}
+
+unsafe extern "system" fn add_error(
+    this: &TestConnection,
+    code: c_ushort,
+    source: *const u16,
+    description: *const u16,
+    _scode: c_long,
+) -> bool {
+    let error = AddedError {
+        code,
+        source: read_str(source),
+        description: read_str(description),
+    };
+    this.state().errors.push(error);
+    true
+}
+
+unsafe extern "system" fn read(
+    this: &TestConnection,
+    name: *mut u16,
+    value: *mut TVariant,
+    error_code: *mut c_long,
+    error_description: *mut *mut u16,
+) -> bool {
+    let name = read_str(name);
+    let mut state = this.state();
+    let state = &mut *state;
+    let stored = state
+        .profile
+        .as_ref()
+        .and_then(|profile| state.profiles.get(profile))
+        .and_then(|values| values.get(&name));
+    // Like 1C platform, memory of the value and the description is
+    // allocated with MemoryManager and must be released by the reader
+    let mem = (*this.memory).as_memory_manager();
+    match stored {
+        Some(stored) => {
+            let _ = (*value).set_param(mem, stored.into());
+            true
+        }
+        None => {
+            let description = format!("{name} not found")
+                .encode_utf16()
+                .collect::<Vec<_>>();
+            *error_code = 1;
+            *error_description = match PlatformStr::new(mem, &description) {
+                Ok(description) => description.into_raw(),
+                Err(_) => ptr::null_mut(),
+            };
+            false
+        }
+    }
+}
+
+unsafe extern "system" fn write(
+    this: &TestConnection,
+    name: *mut u16,
+    value: *mut TVariant,
+) -> bool {
+    let name = read_str(name);
+    let value = ParamValue::from(&*value);
+    let mut state = this.state();
+    let state = &mut *state;
+    let Some(profile) = &state.profile else {
+        return false;
+    };
+    state
+        .profiles
+        .entry(profile.clone())
+        .or_default()
+        .insert(name, value);
+    true
+}
+
+unsafe extern "system" fn register_profile_as(
+    this: &TestConnection,
+    name: *mut u16,
+) -> bool {
+    this.state().profile = Some(read_str(name));
+    true
+}
+
+unsafe extern "system" fn set_event_buffer_depth(
+    this: &TestConnection,
+    depth: c_long,
+) -> bool {
+    this.state().event_buffer_depth = depth;
+    true
+}
+
+unsafe extern "system" fn get_event_buffer_depth(
+    this: &TestConnection,
+) -> c_long {
+    this.state().event_buffer_depth
+}
+
+unsafe extern "system" fn external_event(
+    this: &TestConnection,
+    source: *mut u16,
+    name: *mut u16,
+    data: *mut u16,
+) -> bool {
+    let mut state = this.state();
+    if state.reject_events {
+        return false;
+    }
+    state.events.push(ExternalEvent {
+        source: read_str(source),
+        name: read_str(name),
+        data: read_str(data),
+    });
+    true
+}
+
+unsafe extern "system" fn clean_event_buffer(this: &TestConnection) {
+    this.state().events.clear();
+}
+
+unsafe extern "system" fn set_status_line(
+    this: &TestConnection,
+    status_line: *mut u16,
+) -> bool {
+    this.state().status_line = Some(read_str(status_line));
+    true
+}
+
+unsafe extern "system" fn reset_status_line(this: &TestConnection) {
+    this.state().status_line = None;
+}
+
+unsafe extern "system" fn get_interface(
+    this: &TestConnection,
+    interface: c_int,
+) -> *mut c_void {
+    match interface {
+        0 => &this.msg_box as *const TestMsgBox as *mut c_void,
+        1 => &this.platform_info as *const TestPlatformInfo as *mut c_void,
+        2 => &this.attached_info as *const TestAttachedInfo as *mut c_void,
+        _ => ptr::null_mut(),
+    }
+}
+
+static TEST_CONNECTION_VTABLE: TestConnectionVTable = TestConnectionVTable {
+    dtor: 0,
+    #[cfg(target_family = "unix")]
+    dtor2: 0,
+    add_error,
+    read,
+    write,
+    register_profile_as,
+    set_event_buffer_depth,
+    get_event_buffer_depth,
+    external_event,
+    clean_event_buffer,
+    set_status_line,
+    reset_status_line,
+    get_interface,
+};
+
+/// Rust implementation of `IAddInDefBaseEx`, that is passed to the AddIn
+/// instead of 1C platform Connection object and records calls made to it
+#[repr(C)]
+pub struct TestConnection {
+    vptr: &'static TestConnectionVTable,
+    state: Mutex<ConnectionState>,
+    memory: *const TestMemoryManager,
+    msg_box: TestMsgBox,
+    platform_info: TestPlatformInfo,
+    attached_info: TestAttachedInfo,
+}
+
+impl TestConnection {
+    /// Creates the connection, that allocates memory for 1C platform
+    /// values with `memory`, like 1C platform does
+    fn new(memory: *const TestMemoryManager) -> Self {
+        Self {
+            vptr: &TEST_CONNECTION_VTABLE,
+            state: Mutex::new(ConnectionState {
+                event_buffer_depth: 1,
+                ..Default::default()
+            }),
+            memory,
+            msg_box: TestMsgBox {
+                vptr: &TEST_MSG_BOX_VTABLE,
+                state: Mutex::new(MsgBoxState {
+                    confirm_answer: Some(true),
+                    ..Default::default()
+                }),
+            },
+            platform_info: TestPlatformInfo {
+                vptr: &TEST_PLATFORM_INFO_VTABLE,
+                state: Mutex::new(PlatformInfoState {
+                    version: WString::from_str_truncate("8.3.24.1000"),
+                    user_agent: None,
+                    app_info: TestAppInfo {
+                        app_version: ptr::null(),
+                        application: ApplicationType::ThinClient as c_int,
+                        user_agent_information: ptr::null(),
+                    },
+                }),
+            },
+            attached_info: TestAttachedInfo {
+                vptr: &TEST_ATTACHED_INFO_VTABLE,
+                attached: Mutex::new(AttachedType::NotIsolated),
+            },
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ConnectionState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the object as Connection, that is passed to the AddIn
+    /// # Safety
+    /// Lifetime of the returned reference is not checked, the caller must
+    /// ensure the AddIn does not use it after the object is dropped.
+    /// `TestHost` keeps the object until the component is destroyed
+    unsafe fn as_connection(&self) -> &'static Connection {
+        &*(self as *const Self as *const Connection)
+    }
+
+    /// Returns errors, added by the AddIn with `Connection::add_error`
+    pub fn errors(&self) -> Vec<AddedError> {
+        self.state().errors.clone()
+    }
+
+    /// Returns events, sent by the AddIn with `Connection::external_event`
+    /// and not cleaned by `Connection::clean_event_buffer`
+    pub fn events(&self) -> Vec<ExternalEvent> {
+        self.state().events.clone()
+    }
+
+    /// Returns current event buffer depth
+    pub fn event_buffer_depth(&self) -> c_long {
+        self.state().event_buffer_depth
+    }
+
+    /// Returns current status line, or `None` if it was reset
+    pub fn status_line(&self) -> Option<String> {
+        self.state().status_line.clone()
+    }
+
+    /// Returns texts, shown by the AddIn with `MsgBox::alert`
+    pub fn alerts(&self) -> Vec<String> {
+        self.msg_box.state().alerts.clone()
+    }
+
+    /// Returns questions, asked by the AddIn with `MsgBox::confirm`
+    pub fn confirmations(&self) -> Vec<String> {
+        self.msg_box.state().confirmations.clone()
+    }
+
+    /// Sets the answer, returned to the AddIn from `MsgBox::confirm`,
+    /// `Some(true)` by default. `None` makes the dialog fail, as 1C platform
+    /// does when the dialog can not be shown
+    pub fn set_confirm_answer(&self, answer: Option<bool>) {
+        self.msg_box.state().confirm_answer = answer;
+    }
+
+    /// Makes `Connection::external_event` reject events, as 1C platform
+    /// does when its event buffer is full, `false` by default
+    pub fn set_reject_events(&self, reject: bool) {
+        self.state().reject_events = reject;
+    }
+
+    /// Sets information, returned to the AddIn from
+    /// `PlatformInfo::get_platform_info`, by default it is thin client
+    /// of version `8.3.24.1000`
+    /// # Arguments
+    /// * `version` - version of 1C platform
+    /// * `application` - type of the application
+    /// * `user_agent` - user agent of the browser
+    pub fn set_platform_info(
+        &self,
+        version: &str,
+        application: ApplicationType,
+        user_agent: Option<&str>,
+    ) {
+        let mut state = self.platform_info.state();
+        state.version = WString::from_str_truncate(version);
+        state.user_agent = user_agent.map(WString::from_str_truncate);
+        state.app_info.application = application as c_int;
+    }
+
+    /// Sets the scheme, returned to the AddIn from
+    /// `AttachedInfo::get_attached_info`, `NotIsolated` by default
+    pub fn set_attached_type(&self, attached: AttachedType) {
+        *self
+            .attached_info
+            .attached
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = attached;
+    }
+
+    /// Returns name of the profile, registered by the AddIn with
+    /// `Connection::register_profile_as`
+    pub fn profile(&self) -> Option<String> {
+        self.state().profile.clone()
+    }
+
+    /// Returns value, stored in the profile storage
+    /// # Arguments
+    /// * `profile` - name of the profile
+    /// * `name` - name of the value
+    pub fn profile_value(
+        &self,
+        profile: &str,
+        name: &str,
+    ) -> Option<ParamValue> {
+        let state = self.state();
+        state.profiles.get(profile)?.get(name).cloned()
+    }
+
+    /// Puts value to the profile storage, so it can be read by the AddIn
+    /// # Arguments
+    /// * `profile` - name of the profile
+    /// * `name` - name of the value
+    /// * `value` - value to store
+    pub fn set_profile_value(
+        &self,
+        profile: &str,
+        name: &str,
+        value: ParamValue,
+    ) {
+        let mut state = self.state();
+        state
+            .profiles
+            .entry(profile.to_owned())
+            .or_default()
+            .insert(name.to_owned(), value);
+    }
+}
+
+/// VTable of `IMemoryManager`, as seen by 1C platform
+#[repr(C)]
+struct TestMemoryManagerVTable {
+    dtor: usize,
+    #[cfg(target_family = "unix")]
+    dtor2: usize,
+    alloc_memory: unsafe extern "system" fn(
+        &TestMemoryManager,
+        *mut *mut c_void,
+        c_ulong,
+    ) -> bool,
+    free_memory:
+        unsafe extern "system" fn(&TestMemoryManager, *mut *mut c_void),
+}
+
+unsafe extern "system" fn alloc_memory(
+    this: &TestMemoryManager,
+    ptr: *mut *mut c_void,
+    size: c_ulong,
+) -> bool {
+    let Ok(layout) = Layout::from_size_align(size.max(1) as usize, 8) else {
+        return false;
+    };
+    let allocated = alloc::alloc_zeroed(layout);
+    if allocated.is_null() {
+        return false;
+    }
+    this.allocations().insert(allocated as usize, layout);
+    *ptr = allocated as *mut c_void;
+    true
+}
+
+unsafe extern "system" fn free_memory(
+    this: &TestMemoryManager,
+    ptr: *mut *mut c_void,
+) {
+    if ptr.is_null() || (*ptr).is_null() {
+        return;
+    }
+    match this.allocations().remove(&(*ptr as usize)) {
+        Some(layout) => alloc::dealloc(*ptr as *mut u8, layout),
+        None => {
+            *this.invalid_frees.lock().unwrap_or_else(|e| e.into_inner()) += 1
+        }
+    }
+    *ptr = ptr::null_mut();
+}
+
+static TEST_MEMORY_MANAGER_VTABLE: TestMemoryManagerVTable =
+    TestMemoryManagerVTable {
+        dtor: 0,
+        #[cfg(target_family = "unix")]
+        dtor2: 0,
+        alloc_memory,
+        free_memory,
+    };
+
+/// Rust implementation of `IMemoryManager`, that is passed to the AddIn
+/// instead of 1C platform MemoryManager object and tracks allocations
+#[repr(C)]
+pub struct TestMemoryManager {
+    vptr: &'static TestMemoryManagerVTable,
+    allocations: Mutex<HashMap<usize, Layout>>,
+    invalid_frees: Mutex<usize>,
+}
+
+impl TestMemoryManager {
+    pub(crate) fn new() -> Self {
+        Self {
+            vptr: &TEST_MEMORY_MANAGER_VTABLE,
+            allocations: Mutex::default(),
+            invalid_frees: Mutex::default(),
+        }
+    }
+
+    fn allocations(&self) -> std::sync::MutexGuard<'_, HashMap<usize, Layout>> {
+        self.allocations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn as_memory_manager(&self) -> &MemoryManager {
+        unsafe { &*(self as *const Self as *const MemoryManager) }
+    }
+
+    fn free(&self, ptr: *mut c_void) {
+        let mut ptr = ptr;
+        unsafe { free_memory(self, &mut ptr) }
+    }
+
+    /// Returns number of memory blocks, allocated and not freed yet
+    pub fn live_allocations(&self) -> usize {
+        self.allocations().len()
+    }
+
+    /// Returns number of attempts to free memory, that was not allocated
+    /// by this MemoryManager or was already freed
+    pub fn invalid_frees(&self) -> usize {
+        *self.invalid_frees.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// VTable of `IInitDoneBase`, as seen by 1C platform
+#[repr(C)]
+struct InitDoneVTable {
+    dtor: usize,
+    #[cfg(target_family = "unix")]
+    dtor2: usize,
+    init: unsafe extern "system" fn(*mut c_void, *const c_void) -> bool,
+    set_mem_manager:
+        unsafe extern "system" fn(*mut c_void, *const c_void) -> bool,
+    get_info: unsafe extern "system" fn(*mut c_void) -> c_long,
+    done: unsafe extern "system" fn(*mut c_void),
+}
+
+/// VTable of `ILanguageExtenderBase`, as seen by 1C platform
+#[repr(C)]
+struct LanguageExtenderVTable {
+    dtor: usize,
+    #[cfg(target_family = "unix")]
+    dtor2: usize,
+    register_extension_as:
+        unsafe extern "system" fn(*mut c_void, *mut *mut u16) -> bool,
+    get_n_props: unsafe extern "system" fn(*mut c_void) -> c_long,
+    find_prop: unsafe extern "system" fn(*mut c_void, *const u16) -> c_long,
+    get_prop_name:
+        unsafe extern "system" fn(*mut c_void, c_long, c_long) -> *const u16,
+    get_prop_val:
+        unsafe extern "system" fn(*mut c_void, c_long, *mut TVariant) -> bool,
+    set_prop_val:
+        unsafe extern "system" fn(*mut c_void, c_long, *mut TVariant) -> bool,
+    is_prop_readable: unsafe extern "system" fn(*mut c_void, c_long) -> bool,
+    is_prop_writable: unsafe extern "system" fn(*mut c_void, c_long) -> bool,
+    get_n_methods: unsafe extern "system" fn(*mut c_void) -> c_long,
+    find_method: unsafe extern "system" fn(*mut c_void, *const u16) -> c_long,
+    get_method_name:
+        unsafe extern "system" fn(*mut c_void, c_long, c_long) -> *const u16,
+    get_n_params: unsafe extern "system" fn(*mut c_void, c_long) -> c_long,
+    get_param_def_value: unsafe extern "system" fn(
+        *mut c_void,
+        c_long,
+        c_long,
+        *mut TVariant,
+    ) -> bool,
+    has_ret_val: unsafe extern "system" fn(*mut c_void, c_long) -> bool,
+    call_as_proc: unsafe extern "system" fn(
+        *mut c_void,
+        c_long,
+        *mut TVariant,
+        c_long,
+    ) -> bool,
+    call_as_func: unsafe extern "system" fn(
+        *mut c_void,
+        c_long,
+        *mut TVariant,
+        *mut TVariant,
+        c_long,
+    ) -> bool,
+}
+
+/// VTable of `LocaleBase`, as seen by 1C platform
+#[repr(C)]
+struct LocaleVTable {
+    dtor: usize,
+    #[cfg(target_family = "unix")]
+    dtor2: usize,
+    set_locale: unsafe extern "system" fn(*mut c_void, *const u16),
+}
+
+/// VTable of `UserLanguageBase`, as seen by 1C platform
+#[repr(C)]
+struct UserLanguageVTable {
+    dtor: usize,
+    #[cfg(target_family = "unix")]
+    dtor2: usize,
+    set_user_interface_language_code:
+        unsafe extern "system" fn(*mut c_void, *const u16),
+}
+
+/// Fake 1C platform, that owns the component object and calls its
+/// vtables the same way 1C platform does
+pub struct TestHost {
+    component: *mut c_void,
+    initialized: bool,
+    connection: Box<TestConnection>,
+    memory: Box<TestMemoryManager>,
+}
+
+impl TestHost {
+    /// Negotiates capabilities with the library, like `SetPlatformCapabilities`
+    /// does, creates the component object for the AddIn, like `GetClassObject`
+    /// does, and passes MemoryManager to it
+    /// # Arguments
+    /// * `addin` - AddIn object to drive
+    pub fn new<T: TryAddInWrapper>(addin: T) -> Self {
+        Self::with_capabilities(addin, AppCapabilities::LAST)
+    }
+
+    /// Same as `new`, but simulates 1C platform with given capabilities.
+    /// Negotiated capabilities are global for the library, so tests using
+    /// capabilities below `Capabilities1` should run in a separate test
+    /// binary, as other tests need platform interfaces
+    /// # Arguments
+    /// * `addin` - AddIn object to drive
+    /// * `capabilities` - capabilities of simulated 1C platform
+    pub fn with_capabilities<T: TryAddInWrapper>(
+        addin: T,
+        capabilities: AppCapabilities,
+    ) -> Self {
+        set_platform_capabilities(
+            capabilities as c_int,
+            &[T::supported_capabilities()],
+        );
+        let mut component = ptr::null_mut();
+        unsafe { create_component(&mut component, addin) };
+        let memory = Box::new(TestMemoryManager::new());
+        let host = Self {
+            component,
+            initialized: false,
+            connection: Box::new(TestConnection::new(&*memory)),
+            memory,
+        };
+
+        let (this, vtable) = host.interface::<InitDoneVTable>(0);
+        let memory = host.memory.as_ref() as *const TestMemoryManager;
+        unsafe { (vtable.set_mem_manager)(this, memory as *const c_void) };
+        host
+    }
+
+    /// Returns Connection object, passed to the AddIn
+    pub fn connection(&self) -> &TestConnection {
+        &self.connection
+    }
+
+    /// Returns MemoryManager object, passed to the AddIn
+    pub fn memory(&self) -> &TestMemoryManager {
+        &self.memory
+    }
+
+    fn interface<V>(&self, offset: usize) -> (*mut c_void, &V) {
+        unsafe {
+            let this = (self.component as *mut *const V).add(offset);
+            (this as *mut c_void, &**this)
+        }
+    }
+
+    fn alloc_variant(&self, value: &ParamValue) -> TVariant {
+        let mem = self.memory.as_memory_manager();
+        let mut variant = TVariant::default();
+        unsafe {
+            let _ = variant.set_param(mem, value.into());
+        }
+        variant
+    }
+
+    fn free_variant(&self, variant: &mut TVariant) {
+        unsafe { variant.clear(self.memory.as_memory_manager()) };
+    }
+
+    fn take_variant(&self, variant: &mut TVariant) -> ParamValue {
+        let value = ParamValue::from(&*variant);
+        self.free_variant(variant);
+        value
+    }
+
+    fn take_str(&self, s: *const u16) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let value = unsafe { read_str(s) };
+        self.memory.free(s as *mut c_void);
+        Some(value)
+    }
+
+    /// Calls `IInitDoneBase::Init`, passing `TestConnection` to the AddIn
+    /// # Returns
+    /// `bool` - result, returned by the AddIn
+    pub fn init(&mut self) -> bool {
+        let (this, vtable) = self.interface::<InitDoneVTable>(0);
+        let result = unsafe {
+            let connection: *const Connection = self.connection.as_connection();
+            (vtable.init)(this, connection as *const c_void)
+        };
+        self.initialized = result;
+        result
+    }
+
+    /// Calls `IInitDoneBase::Done`, it is also called when the host is
+    /// dropped after successful `init`. After failed `init` the component
+    /// is destroyed without `done`
+    pub fn done(&mut self) {
+        if self.initialized {
+            let (this, vtable) = self.interface::<InitDoneVTable>(0);
+            unsafe { (vtable.done)(this) };
+            self.initialized = false;
+        }
+    }
+
+    /// Calls `IInitDoneBase::GetInfo`
+    pub fn get_info(&mut self) -> c_long {
+        let (this, vtable) = self.interface::<InitDoneVTable>(0);
+        unsafe { (vtable.get_info)(this) }
+    }
+
+    /// Calls `ILanguageExtenderBase::RegisterExtensionAs`
+    /// # Returns
+    /// `Option<String>` - name of the AddIn or None if the call failed
+    pub fn register_extension_as(&mut self) -> Option<String> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let mut name = ptr::null_mut();
+        if !unsafe { (vtable.register_extension_as)(this, &mut name) } {
+            return None;
+        }
+        self.take_str(name)
+    }
+
+    /// Calls `ILanguageExtenderBase::GetNProps`
+    pub fn get_n_props(&mut self) -> usize {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        unsafe { (vtable.get_n_props)(this) as usize }
+    }
+
+    /// Calls `ILanguageExtenderBase::FindProp`
+    pub fn find_prop(&mut self, name: &str) -> Option<usize> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let name = WString::from_str_truncate(name);
+        let num = unsafe { (vtable.find_prop)(this, name.as_ptr()) };
+        usize::try_from(num).ok()
+    }
+
+    /// Calls `ILanguageExtenderBase::GetPropName`
+    pub fn get_prop_name(
+        &mut self,
+        num: usize,
+        alias: usize,
+    ) -> Option<String> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let name = unsafe {
+            (vtable.get_prop_name)(this, num as c_long, alias as c_long)
+        };
+        self.take_str(name)
+    }
+
+    /// Calls `ILanguageExtenderBase::GetPropVal`
+    /// # Returns
+    /// `Option<ParamValue>` - value of the property or None if the call failed
+    pub fn get_prop_val(&mut self, num: usize) -> Option<ParamValue> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let mut variant = TVariant::default();
+        let result =
+            unsafe { (vtable.get_prop_val)(this, num as c_long, &mut variant) };
+        let value = self.take_variant(&mut variant);
+        result.then_some(value)
+    }
+
+    /// Calls `ILanguageExtenderBase::SetPropVal`
+    pub fn set_prop_val(&mut self, num: usize, value: &ParamValue) -> bool {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let mut variant = self.alloc_variant(value);
+        let result =
+            unsafe { (vtable.set_prop_val)(this, num as c_long, &mut variant) };
+        self.free_variant(&mut variant);
+        result
+    }
+
+    /// Calls `ILanguageExtenderBase::IsPropReadable`
+    pub fn is_prop_readable(&mut self, num: usize) -> bool {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        unsafe { (vtable.is_prop_readable)(this, num as c_long) }
+    }
+
+    /// Calls `ILanguageExtenderBase::IsPropWritable`
+    pub fn is_prop_writable(&mut self, num: usize) -> bool {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        unsafe { (vtable.is_prop_writable)(this, num as c_long) }
+    }
+
+    /// Calls `ILanguageExtenderBase::GetNMethods`
+    pub fn get_n_methods(&mut self) -> usize {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        unsafe { (vtable.get_n_methods)(this) as usize }
+    }
+
+    /// Calls `ILanguageExtenderBase::FindMethod`
+    pub fn find_method(&mut self, name: &str) -> Option<usize> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let name = WString::from_str_truncate(name);
+        let num = unsafe { (vtable.find_method)(this, name.as_ptr()) };
+        usize::try_from(num).ok()
+    }
+
+    /// Calls `ILanguageExtenderBase::GetMethodName`
+    pub fn get_method_name(
+        &mut self,
+        num: usize,
+        alias: usize,
+    ) -> Option<String> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let name = unsafe {
+            (vtable.get_method_name)(this, num as c_long, alias as c_long)
+        };
+        self.take_str(name)
+    }
+
+    /// Calls `ILanguageExtenderBase::GetNParams`
+    pub fn get_n_params(&mut self, num: usize) -> usize {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        unsafe { (vtable.get_n_params)(this, num as c_long) as usize }
+    }
+
+    /// Calls `ILanguageExtenderBase::GetParamDefValue`
+    /// # Returns
+    /// `Option<ParamValue>` - default value or None if the call failed
+    pub fn get_param_def_value(
+        &mut self,
+        method_num: usize,
+        param_num: usize,
+    ) -> Option<ParamValue> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let mut variant = TVariant::default();
+        let result = unsafe {
+            (vtable.get_param_def_value)(
+                this,
+                method_num as c_long,
+                param_num as c_long,
+                &mut variant,
+            )
+        };
+        let value = self.take_variant(&mut variant);
+        result.then_some(value)
+    }
+
+    /// Calls `ILanguageExtenderBase::HasRetVal`
+    pub fn has_ret_val(&mut self, method_num: usize) -> bool {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        unsafe { (vtable.has_ret_val)(this, method_num as c_long) }
+    }
+
+    /// Calls `ILanguageExtenderBase::CallAsProc`. Parameters, modified
+    /// by the AddIn, are written back to `params`
+    /// # Returns
+    /// `bool` - result, returned by the AddIn
+    pub fn call_as_proc(
+        &mut self,
+        method_num: usize,
+        params: &mut [ParamValue],
+    ) -> bool {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let mut variants = params
+            .iter()
+            .map(|param| self.alloc_variant(param))
+            .collect::<Vec<_>>();
+        let result = unsafe {
+            (vtable.call_as_proc)(
+                this,
+                method_num as c_long,
+                variants.as_mut_ptr(),
+                variants.len() as c_long,
+            )
+        };
+        for (param, variant) in params.iter_mut().zip(variants.iter_mut()) {
+            *param = self.take_variant(variant);
+        }
+        result
+    }
+
+    /// Calls `ILanguageExtenderBase::CallAsFunc`. Parameters, modified
+    /// by the AddIn, are written back to `params`
+    /// # Returns
+    /// `Option<ParamValue>` - returned value or None if the call failed
+    pub fn call_as_func(
+        &mut self,
+        method_num: usize,
+        params: &mut [ParamValue],
+    ) -> Option<ParamValue> {
+        let (this, vtable) = self.interface::<LanguageExtenderVTable>(1);
+        let mut variants = params
+            .iter()
+            .map(|param| self.alloc_variant(param))
+            .collect::<Vec<_>>();
+        let mut ret_value = TVariant::default();
+        let result = unsafe {
+            (vtable.call_as_func)(
+                this,
+                method_num as c_long,
+                &mut ret_value,
+                variants.as_mut_ptr(),
+                variants.len() as c_long,
+            )
+        };
+        for (param, variant) in params.iter_mut().zip(variants.iter_mut()) {
+            *param = self.take_variant(variant);
+        }
+        let value = self.take_variant(&mut ret_value);
+        result.then_some(value)
+    }
+
+    /// Calls `LocaleBase::SetLocale`
+    pub fn set_locale(&mut self, locale: &str) {
+        let (this, vtable) = self.interface::<LocaleVTable>(2);
+        let locale = WString::from_str_truncate(locale);
+        unsafe { (vtable.set_locale)(this, locale.as_ptr()) }
+    }
+
+    /// Calls `UserLanguageBase::SetUserInterfaceLanguageCode`
+    pub fn set_user_interface_language_code(&mut self, lang: &str) {
+        let (this, vtable) = self.interface::<UserLanguageVTable>(3);
+        let lang = WString::from_str_truncate(lang);
+        unsafe {
+            (vtable.set_user_interface_language_code)(this, lang.as_ptr())
+        }
+    }
+}
+
+impl Drop for TestHost {
+    fn drop(&mut self) {
+        self.done();
+        unsafe { destroy_component(&mut self.component) };
+    }
+}
+
+unsafe fn read_str(s: *const u16) -> String {
+    WStr::from_ptr_opt(s)
+        .map(WStr::to_utf8_lossy)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::TestHost;
+    use crate::{
+        ffi::{
+            connection::Connection,
+            provided_types::{ParamRef, ParamValue, Params, ReturnValue},
+        },
+        interface::{AddInError, AddInResult, TryAddInWrapper},
+    };
+
+    /// AddIn with property `Total` and function `Add(Value)`, that adds the
+    /// value to the property and returns the sum
+    struct Counter {
+        total: i32,
+        initialized: Arc<AtomicBool>,
+        done: Arc<AtomicBool>,
+    }
+
+    fn name(name: &str) -> Vec<u16> {
+        name.encode_utf16().collect()
+    }
+
+    impl TryAddInWrapper for Counter {
+        fn init(&mut self, _: &'static Connection) -> AddInResult {
+            self.initialized.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn done(&mut self) {
+            self.done.store(true, Ordering::SeqCst);
+        }
+
+        fn register_extension_as(&mut self) -> &[u16] {
+            &utf16_lit::utf16_null!("Counter")
+        }
+
+        fn get_n_props(&self) -> usize {
+            1
+        }
+
+        fn find_prop(&self, name: &[u16]) -> Option<usize> {
+            (String::from_utf16_lossy(name) == "Total").then_some(0)
+        }
+
+        fn get_prop_name(&self, num: usize, _: usize) -> Option<Vec<u16>> {
+            (num == 0).then(|| name("Total"))
+        }
+
+        fn get_prop_val(&self, _: usize, val: ReturnValue) -> AddInResult {
+            val.set_i32(self.total);
+            Ok(())
+        }
+
+        fn set_prop_val(&mut self, _: usize, _: &ParamValue) -> AddInResult {
+            Err(AddInError::new("Total is read only"))
+        }
+
+        fn is_prop_readable(&self, _: usize) -> bool {
+            true
+        }
+
+        fn is_prop_writable(&self, _: usize) -> bool {
+            false
+        }
+
+        fn get_n_methods(&self) -> usize {
+            1
+        }
+
+        fn find_method(&self, name: &[u16]) -> Option<usize> {
+            (String::from_utf16_lossy(name) == "Add").then_some(0)
+        }
+
+        fn get_method_name(&self, num: usize, _: usize) -> Option<Vec<u16>> {
+            (num == 0).then(|| name("Add"))
+        }
+
+        fn get_n_params(&self, _: usize) -> usize {
+            1
+        }
+
+        fn get_param_def_value(
+            &self,
+            _: usize,
+            _: usize,
+            _: ReturnValue,
+        ) -> bool {
+            false
+        }
+
+        fn has_ret_val(&self, _: usize) -> bool {
+            true
+        }
+
+        fn call_as_proc(&mut self, _: usize, _: &mut Params) -> AddInResult {
+            Err(AddInError::new("Add is a function"))
+        }
+
+        fn call_as_func(
+            &mut self,
+            _: usize,
+            params: &mut Params,
+            val: ReturnValue,
+        ) -> AddInResult {
+            let Some(ParamRef::I32(value)) = params.get(0) else {
+                return Err(AddInError::new("Value must be a number"));
+            };
+            self.total += value;
+            val.set_i32(self.total);
+            Ok(())
+        }
+
+        fn set_locale(&mut self, _: &[u16]) {}
+
+        fn set_user_interface_language_code(&mut self, _: &[u16]) {}
+    }
+
+    #[test]
+    fn drives_addin_through_vtables() {
+        let initialized = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicBool::new(false));
+        let mut host = TestHost::new(Counter {
+            total: 0,
+            initialized: initialized.clone(),
+            done: done.clone(),
+        });
+
+        assert!(host.init());
+        assert!(initialized.load(Ordering::SeqCst));
+        assert_eq!(host.register_extension_as().as_deref(), Some("Counter"));
+
+        let add = host.find_method("Add").unwrap();
+        assert_eq!(host.get_method_name(add, 0).as_deref(), Some("Add"));
+        let mut params = [ParamValue::I32(2)];
+        assert!(
+            host.call_as_func(add, &mut params) == Some(ParamValue::I32(2))
+        );
+        let mut params = [ParamValue::I32(3)];
+        assert!(
+            host.call_as_func(add, &mut params) == Some(ParamValue::I32(5))
+        );
+
+        let total = host.find_prop("Total").unwrap();
+        assert!(host.get_prop_val(total) == Some(ParamValue::I32(5)));
+        assert!(!host.set_prop_val(total, &ParamValue::I32(0)));
+
+        let mut params = [ParamValue::Bool(true)];
+        assert!(host.call_as_func(add, &mut params).is_none());
+        let errors = host.connection().errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].description, "Value must be a number");
+
+        host.done();
+        assert!(done.load(Ordering::SeqCst));
+        assert_eq!(host.memory().live_allocations(), 0);
+        assert_eq!(host.memory().invalid_frees(), 0);
+    }
+}
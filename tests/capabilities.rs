@@ -0,0 +1,48 @@
+//! Negotiated capabilities are global for the process, so they are tested
+//! in a separate binary, where other tests do not need interfaces
+use std::{cell::Cell, rc::Rc};
+
+use native_api_1c_core::{
+    dynamic::DynamicAddIn,
+    ffi::{
+        connection::{Connection, Interfaces},
+        AppCapabilities,
+    },
+    testing::TestHost,
+};
+
+/// Creates initialized host with given capabilities and returns the
+/// connection, passed to the AddIn
+fn connect(capabilities: AppCapabilities) -> (TestHost, &'static Connection) {
+    let connection: Rc<Cell<Option<&'static Connection>>> = Default::default();
+    let addin = DynamicAddIn::new("Capabilities").on_init({
+        let connection = connection.clone();
+        move |interface| {
+            connection.set(Some(interface));
+            Ok(())
+        }
+    });
+    let mut host = TestHost::with_capabilities(addin, capabilities);
+    assert!(host.init());
+    (host, connection.get().unwrap())
+}
+
+#[test]
+fn interfaces_need_negotiated_capabilities() {
+    let (_host, connection) = connect(AppCapabilities::Invalid);
+    assert_eq!(connection.app_capabilities(), AppCapabilities::Invalid);
+    assert!(connection.get_interface(Interfaces::MsgBox).is_none());
+    assert!(connection.msg_box().is_none());
+    assert!(connection.platform_info().is_none());
+    assert!(connection.attached_info().is_none());
+
+    let (_host, connection) = connect(AppCapabilities::Capabilities1);
+    assert_eq!(
+        connection.app_capabilities(),
+        AppCapabilities::Capabilities1
+    );
+    assert!(connection.get_interface(Interfaces::MsgBox).is_some());
+    assert!(connection.msg_box().is_some());
+    assert!(connection.platform_info().is_some());
+    assert!(connection.attached_info().is_some());
+}
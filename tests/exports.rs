@@ -0,0 +1,177 @@
+//! `export_add_ins!` generates `#[no_mangle]` functions, so it is expanded
+//! in a separate binary, where exported names do not clash with other tests
+use std::{
+    ffi::{c_int, c_void},
+    ptr,
+    sync::Mutex,
+};
+
+use native_api_1c_core::{
+    ffi::{
+        connection::Connection,
+        provided_types::{ParamValue, Params, ReturnValue},
+        string_utils::WStr,
+        AppCapabilities, AttachType,
+    },
+    interface::{AddInResult, TryAddInWrapper},
+};
+
+static DROPPED: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+macro_rules! class {
+    ($name:ident, $attach_type:expr) => {
+        #[derive(Default)]
+        struct $name;
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                DROPPED.lock().unwrap().push(stringify!($name));
+            }
+        }
+
+        impl TryAddInWrapper for $name {
+            fn init(&mut self, _: &'static Connection) -> AddInResult {
+                Ok(())
+            }
+
+            fn attach_type() -> AttachType {
+                $attach_type
+            }
+
+            fn done(&mut self) {}
+
+            fn register_extension_as(&mut self) -> &[u16] {
+                &utf16_lit::utf16_null!(stringify!($name))
+            }
+
+            fn get_n_props(&self) -> usize {
+                0
+            }
+
+            fn find_prop(&self, _: &[u16]) -> Option<usize> {
+                None
+            }
+
+            fn get_prop_name(&self, _: usize, _: usize) -> Option<Vec<u16>> {
+                None
+            }
+
+            fn get_prop_val(&self, _: usize, _: ReturnValue) -> AddInResult {
+                Ok(())
+            }
+
+            fn set_prop_val(
+                &mut self,
+                _: usize,
+                _: &ParamValue,
+            ) -> AddInResult {
+                Ok(())
+            }
+
+            fn is_prop_readable(&self, _: usize) -> bool {
+                false
+            }
+
+            fn is_prop_writable(&self, _: usize) -> bool {
+                false
+            }
+
+            fn get_n_methods(&self) -> usize {
+                0
+            }
+
+            fn find_method(&self, _: &[u16]) -> Option<usize> {
+                None
+            }
+
+            fn get_method_name(&self, _: usize, _: usize) -> Option<Vec<u16>> {
+                None
+            }
+
+            fn get_n_params(&self, _: usize) -> usize {
+                0
+            }
+
+            fn get_param_def_value(
+                &self,
+                _: usize,
+                _: usize,
+                _: ReturnValue,
+            ) -> bool {
+                false
+            }
+
+            fn has_ret_val(&self, _: usize) -> bool {
+                false
+            }
+
+            fn call_as_proc(
+                &mut self,
+                _: usize,
+                _: &mut Params,
+            ) -> AddInResult {
+                Ok(())
+            }
+
+            fn call_as_func(
+                &mut self,
+                _: usize,
+                _: &mut Params,
+                _: ReturnValue,
+            ) -> AddInResult {
+                Ok(())
+            }
+
+            fn set_locale(&mut self, _: &[u16]) {}
+
+            fn set_user_interface_language_code(&mut self, _: &[u16]) {}
+        }
+    };
+}
+
+class!(Printer, AttachType::NotIsolated);
+class!(Scanner, AttachType::Any);
+
+native_api_1c_core::export_add_ins! {
+    "Printer" => Printer,
+    "Scanner" => Scanner,
+}
+
+fn name(name: &str) -> Vec<u16> {
+    name.encode_utf16().chain([0]).collect()
+}
+
+#[test]
+fn exports_create_and_destroy_objects() {
+    let names = unsafe { WStr::from_ptr(GetClassNames()) };
+    assert_eq!(names.to_utf8_lossy(), "Printer|Scanner");
+    assert_eq!(GetClassNames(), GetClassNames());
+
+    assert_eq!(GetAttachType(), AttachType::NotIsolated);
+    assert_eq!(
+        SetPlatformCapabilities(AppCapabilities::Capabilities2 as c_int),
+        AppCapabilities::LAST
+    );
+
+    let mut component: *mut c_void = ptr::null_mut();
+    unsafe {
+        assert_eq!(GetClassObject(name("Copier").as_ptr(), &mut component), 0);
+        assert_eq!(GetClassObject(ptr::null(), &mut component), 0);
+        assert!(component.is_null());
+
+        for class in ["Printer", "Scanner"] {
+            let class_name = name(class);
+            let class_name = class_name.as_ptr();
+            assert_ne!(GetClassObject(class_name, &mut component), 0);
+            assert!(!component.is_null());
+            // Occupied slot is not overwritten
+            assert_eq!(GetClassObject(class_name, &mut component), 0);
+            assert_eq!(DestroyObject(&mut component), 0);
+            assert!(component.is_null());
+            assert_eq!(DROPPED.lock().unwrap().last(), Some(&class));
+        }
+        assert_eq!(DestroyObject(&mut component), -1);
+        assert_eq!(DestroyObject(ptr::null_mut()), -1);
+    }
+    assert_eq!(*DROPPED.lock().unwrap(), ["Printer", "Scanner"]);
+}
@@ -0,0 +1,301 @@
+//! Logger state is global for the process, so it is tested in a separate
+//! binary, that does not share it with other tests
+use std::{
+    fs,
+    sync::{Mutex, MutexGuard, Once},
+    thread,
+    time::Duration,
+};
+
+use log::{Level, LevelFilter, Log, Record};
+
+use native_api_1c_core::{
+    dynamic::DynamicAddIn,
+    ffi::{
+        attached_info::AttachedType,
+        connection::{Connection, MessageCode},
+        provided_types::{ParamValue, Params, ReturnValue},
+        AttachType,
+    },
+    interface::{AddInError, AddInResult, TryAddInWrapper},
+    logger::{Fallback, Logger},
+    testing::TestHost,
+};
+
+fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        Logger::new()
+            .with_rate_limit(None)
+            .with_fallback(Fallback::None)
+            .install()
+            .unwrap()
+    });
+}
+
+/// Connections of initialized objects are global too, so tests, that
+/// create objects, run one at a time
+fn serial() -> MutexGuard<'static, ()> {
+    static SERIAL: Mutex<()> = Mutex::new(());
+    SERIAL.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn messages(host: &TestHost) -> Vec<String> {
+    let errors = host.connection().errors();
+    errors.into_iter().map(|error| error.description).collect()
+}
+
+/// Passes the record to the logger directly, so loggers with different
+/// settings can be tested without installing them
+fn log(logger: &Logger, level: Level, message: &str) {
+    logger.log(
+        &Record::builder()
+            .level(level)
+            .target("test")
+            .args(format_args!("{message}"))
+            .build(),
+    );
+}
+
+/// Logger without rate limit and fallback
+fn test_logger() -> Logger {
+    Logger::new()
+        .with_rate_limit(None)
+        .with_fallback(Fallback::None)
+}
+
+fn attached_host() -> TestHost {
+    let mut host = TestHost::new(DynamicAddIn::new("Logged"));
+    assert!(host.init());
+    host
+}
+
+/// AddIn, that can only be loaded into 1C platform process
+struct InProcess;
+
+impl TryAddInWrapper for InProcess {
+    fn init(&mut self, _: &'static Connection) -> AddInResult {
+        Ok(())
+    }
+
+    fn attach_type() -> AttachType {
+        AttachType::NotIsolated
+    }
+
+    fn done(&mut self) {}
+
+    fn register_extension_as(&mut self) -> &[u16] {
+        &utf16_lit::utf16_null!("InProcess")
+    }
+
+    fn get_n_props(&self) -> usize {
+        0
+    }
+
+    fn find_prop(&self, _: &[u16]) -> Option<usize> {
+        None
+    }
+
+    fn get_prop_name(&self, _: usize, _: usize) -> Option<Vec<u16>> {
+        None
+    }
+
+    fn get_prop_val(&self, _: usize, _: ReturnValue) -> AddInResult {
+        Ok(())
+    }
+
+    fn set_prop_val(&mut self, _: usize, _: &ParamValue) -> AddInResult {
+        Ok(())
+    }
+
+    fn is_prop_readable(&self, _: usize) -> bool {
+        false
+    }
+
+    fn is_prop_writable(&self, _: usize) -> bool {
+        false
+    }
+
+    fn get_n_methods(&self) -> usize {
+        0
+    }
+
+    fn find_method(&self, _: &[u16]) -> Option<usize> {
+        None
+    }
+
+    fn get_method_name(&self, _: usize, _: usize) -> Option<Vec<u16>> {
+        None
+    }
+
+    fn get_n_params(&self, _: usize) -> usize {
+        0
+    }
+
+    fn get_param_def_value(&self, _: usize, _: usize, _: ReturnValue) -> bool {
+        false
+    }
+
+    fn has_ret_val(&self, _: usize) -> bool {
+        false
+    }
+
+    fn call_as_proc(&mut self, _: usize, _: &mut Params) -> AddInResult {
+        Ok(())
+    }
+
+    fn call_as_func(
+        &mut self,
+        _: usize,
+        _: &mut Params,
+        _: ReturnValue,
+    ) -> AddInResult {
+        Ok(())
+    }
+
+    fn set_locale(&mut self, _: &[u16]) {}
+
+    fn set_user_interface_language_code(&mut self, _: &[u16]) {}
+}
+
+#[test]
+fn records_go_to_the_latest_alive_object() {
+    let _serial = serial();
+    install();
+    let mut first = TestHost::new(DynamicAddIn::new("First"));
+    let mut second = TestHost::new(DynamicAddIn::new("Second"));
+    assert!(first.init());
+    assert!(second.init());
+    log::info!("to second");
+
+    let mut failed = TestHost::new(
+        DynamicAddIn::new("Failed")
+            .on_init(|_| Err(AddInError::new("init failed"))),
+    );
+    assert!(!failed.init());
+    log::info!("still to second");
+
+    second.done();
+    log::info!("to first");
+    assert_eq!(messages(&second), ["to second", "still to second"]);
+    assert_eq!(messages(&failed), ["init failed"]);
+    assert_eq!(messages(&first), ["to first"]);
+
+    drop(first);
+    log::info!("nowhere");
+    assert_eq!(messages(&failed), ["init failed"]);
+}
+
+#[test]
+fn refused_object_is_not_attached() {
+    let _serial = serial();
+    install();
+    let mut attached = TestHost::new(DynamicAddIn::new("Attached"));
+    assert!(attached.init());
+
+    let mut refused = TestHost::new(InProcess);
+    refused
+        .connection()
+        .set_attached_type(AttachedType::Isolated);
+    assert!(!refused.init());
+    log::info!("to attached");
+
+    assert_eq!(
+        messages(&refused),
+        ["AddIn can not be attached as Isolated"]
+    );
+    assert_eq!(messages(&attached), ["to attached"]);
+}
+
+#[test]
+fn levels_are_mapped_to_message_codes() {
+    let _serial = serial();
+    let host = attached_host();
+    let logger = test_logger()
+        .with_level(LevelFilter::Trace)
+        .with_message_code(Level::Debug, MessageCode::Ordinary);
+    for level in [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ] {
+        log(&logger, level, level.as_str());
+    }
+    let default = test_logger();
+    log(&default, Level::Debug, "filtered");
+    log(&default, Level::Trace, "filtered");
+    log(&default, Level::Info, "INFO");
+
+    let errors = host.connection().errors();
+    let codes = errors
+        .iter()
+        .map(|error| (error.description.as_str(), error.code))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            ("ERROR", MessageCode::Fail as u16),
+            ("WARN", MessageCode::Important as u16),
+            ("INFO", MessageCode::Attention as u16),
+            ("DEBUG", MessageCode::Ordinary as u16),
+            ("TRACE", MessageCode::Info as u16),
+            ("INFO", MessageCode::Attention as u16),
+        ]
+    );
+    assert!(errors.iter().all(|error| error.source == "test"));
+    assert_eq!(host.connection().status_line(), None);
+}
+
+#[test]
+fn records_are_mirrored_to_status_line() {
+    let _serial = serial();
+    let host = attached_host();
+    let logger = test_logger().with_status_line(true);
+    log(&logger, Level::Info, "first");
+    log(&logger, Level::Warn, "second");
+    assert_eq!(host.connection().status_line().as_deref(), Some("second"));
+    assert_eq!(messages(&host), ["first", "second"]);
+}
+
+#[test]
+fn floods_are_summarized() {
+    let _serial = serial();
+    let host = attached_host();
+    let interval = Duration::from_millis(200);
+    let logger = test_logger().with_rate_limit(Some((2, interval)));
+    for i in 0..5 {
+        log(&logger, Level::Info, &format!("record {i}"));
+    }
+    assert_eq!(messages(&host), ["record 0", "record 1"]);
+
+    thread::sleep(interval + Duration::from_millis(50));
+    log(&logger, Level::Info, "after");
+    let errors = host.connection().errors();
+    assert_eq!(errors[2].description, "3 records suppressed");
+    assert_eq!(errors[2].code, MessageCode::Important as u16);
+    assert_eq!(errors[3].description, "after");
+    assert_eq!(errors.len(), 4);
+}
+
+#[test]
+fn records_go_to_fallback_file_without_connection() {
+    let _serial = serial();
+    let path = std::env::temp_dir()
+        .join(format!("native_api_1c_logger_{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let logger = test_logger().with_fallback(Fallback::File(path.clone()));
+    log(&logger, Level::Error, "failed");
+    log(&logger, Level::Info, "Запись");
+    logger.flush();
+    let written = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(written, "ERROR test: failed\nINFO test: Запись\n");
+
+    // Attached connection takes precedence over the fallback
+    let host = attached_host();
+    log(&logger, Level::Info, "to host");
+    assert_eq!(messages(&host), ["to host"]);
+    assert!(!path.exists());
+}